@@ -9,7 +9,7 @@ CREATE TABLE diet (
     record_date     DATE NOT NULL,
     created_at      TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP,
     updated_at      TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
-)
+);
 
 COMMENT ON COLUMN diet.milk IS 'Unit: milliliters (ml)';
 COMMENT ON COLUMN diet.meat IS 'Unit: grams (g)';
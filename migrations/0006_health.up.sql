@@ -7,4 +7,4 @@ CREATE TABLE health (
     record_date         DATE NOT NULL,
     created_at          TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP,
     updated_at          TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
-)
\ No newline at end of file
+);
\ No newline at end of file
@@ -5,4 +5,4 @@ CREATE TABLE remark (
     content     TEXT NOT NULL,
     created_at  TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP,
     updated_at  TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
-)
\ No newline at end of file
+);
\ No newline at end of file
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS family_invitation;
+DROP TABLE IF EXISTS family_member;
+DROP TABLE IF EXISTS family;
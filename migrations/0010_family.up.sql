@@ -0,0 +1,33 @@
+CREATE TABLE family (
+    id          BIGSERIAL PRIMARY KEY,
+    name        VARCHAR(255) NOT NULL,
+    created_by  BIGINT NOT NULL REFERENCES account(id),
+    created_at  TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- an account belongs to at most one family
+CREATE TABLE family_member (
+    family_id   BIGINT NOT NULL REFERENCES family(id) ON DELETE CASCADE,
+    account_id  BIGINT NOT NULL UNIQUE REFERENCES account(id) ON DELETE CASCADE,
+    role        VARCHAR(16) NOT NULL DEFAULT 'viewer'
+                CHECK (role IN ('owner', 'parent', 'caregiver', 'viewer')),
+    created_at  TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (family_id, account_id)
+);
+
+-- one-time invite codes, consumed by either accepting or declining
+CREATE TABLE family_invitation (
+    id              BIGSERIAL PRIMARY KEY,
+    family_id       BIGINT NOT NULL REFERENCES family(id) ON DELETE CASCADE,
+    code            VARCHAR(32) UNIQUE NOT NULL,
+    role            VARCHAR(16) NOT NULL
+                    CHECK (role IN ('parent', 'caregiver', 'viewer')),
+    invited_by      BIGINT NOT NULL REFERENCES account(id),
+    invitee_id      BIGINT REFERENCES account(id),
+    status          VARCHAR(16) NOT NULL DEFAULT 'pending'
+                    CHECK (status IN ('pending', 'accepted', 'declined', 'revoked')),
+    expires_at      TIMESTAMP WITHOUT TIME ZONE NOT NULL, -- UTC
+    responded_at    TIMESTAMP WITHOUT TIME ZONE,
+    created_at      TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,12 @@
+CREATE TABLE child (
+    id          BIGSERIAL PRIMARY KEY,
+    family_id   BIGINT NOT NULL REFERENCES family(id) ON DELETE CASCADE,
+    name        VARCHAR(255) NOT NULL,
+    birthday    DATE,
+    sex         VARCHAR(16),
+    avatar_url  VARCHAR(255),
+    created_at  TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX child_family_id_idx ON child (family_id);
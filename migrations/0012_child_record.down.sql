@@ -0,0 +1,3 @@
+ALTER TABLE IF EXISTS behavior DROP COLUMN IF EXISTS child_id;
+ALTER TABLE IF EXISTS diet DROP COLUMN IF EXISTS child_id;
+ALTER TABLE IF EXISTS health DROP COLUMN IF EXISTS child_id;
@@ -0,0 +1,38 @@
+-- health, diet and behavior records belong to a child of a family
+ALTER TABLE health ADD COLUMN child_id BIGINT REFERENCES child(id) ON DELETE CASCADE;
+ALTER TABLE diet ADD COLUMN child_id BIGINT REFERENCES child(id) ON DELETE CASCADE;
+ALTER TABLE behavior ADD COLUMN child_id BIGINT REFERENCES child(id) ON DELETE CASCADE;
+
+-- the records kept so far were about a single child, they move to a child of the family
+-- of the first account, which becomes its owner if it has none and can invite the others
+DO $$
+DECLARE
+    legacy_owner    BIGINT;
+    legacy_family   BIGINT;
+    legacy_child    BIGINT;
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM health) AND NOT EXISTS (SELECT 1 FROM diet) AND NOT EXISTS (SELECT 1 FROM behavior) THEN
+        RETURN;
+    END IF;
+
+    SELECT MIN(id) INTO legacy_owner FROM account;
+    IF legacy_owner IS NULL THEN
+        RAISE EXCEPTION 'health, diet or behavior records exist but no account to own them, add one with create-user first';
+    END IF;
+
+    SELECT m.family_id INTO legacy_family FROM family_member m WHERE m.account_id = legacy_owner;
+    IF legacy_family IS NULL THEN
+        INSERT INTO family (name, created_by) VALUES ('Family', legacy_owner) RETURNING id INTO legacy_family;
+        INSERT INTO family_member (family_id, account_id, role) VALUES (legacy_family, legacy_owner, 'owner');
+    END IF;
+
+    INSERT INTO child (family_id, name) VALUES (legacy_family, 'Child') RETURNING id INTO legacy_child;
+
+    UPDATE health SET child_id = legacy_child WHERE child_id IS NULL;
+    UPDATE diet SET child_id = legacy_child WHERE child_id IS NULL;
+    UPDATE behavior SET child_id = legacy_child WHERE child_id IS NULL;
+END $$;
+
+ALTER TABLE health ALTER COLUMN child_id SET NOT NULL;
+ALTER TABLE diet ALTER COLUMN child_id SET NOT NULL;
+ALTER TABLE behavior ALTER COLUMN child_id SET NOT NULL;
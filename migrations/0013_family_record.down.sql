@@ -0,0 +1,2 @@
+ALTER TABLE IF EXISTS wish DROP COLUMN IF EXISTS family_id;
+ALTER TABLE IF EXISTS journal DROP COLUMN IF EXISTS family_id;
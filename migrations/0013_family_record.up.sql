@@ -0,0 +1,34 @@
+-- journals and wishes are shared within a family
+ALTER TABLE journal ADD COLUMN family_id BIGINT REFERENCES family(id) ON DELETE CASCADE;
+ALTER TABLE wish ADD COLUMN family_id BIGINT REFERENCES family(id) ON DELETE CASCADE;
+
+-- wishes kept so far go to the family of their author, the ones left and the journals to the
+-- family of the first account, like the child records before them
+UPDATE wish SET family_id = m.family_id FROM family_member m WHERE m.account_id = wish.user_id;
+
+DO $$
+DECLARE
+    legacy_owner    BIGINT;
+    legacy_family   BIGINT;
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM journal) AND NOT EXISTS (SELECT 1 FROM wish WHERE family_id IS NULL) THEN
+        RETURN;
+    END IF;
+
+    SELECT MIN(id) INTO legacy_owner FROM account;
+    IF legacy_owner IS NULL THEN
+        RAISE EXCEPTION 'journal or wish records exist but no account to own them, add one with create-user first';
+    END IF;
+
+    SELECT m.family_id INTO legacy_family FROM family_member m WHERE m.account_id = legacy_owner;
+    IF legacy_family IS NULL THEN
+        INSERT INTO family (name, created_by) VALUES ('Family', legacy_owner) RETURNING id INTO legacy_family;
+        INSERT INTO family_member (family_id, account_id, role) VALUES (legacy_family, legacy_owner, 'owner');
+    END IF;
+
+    UPDATE journal SET family_id = legacy_family WHERE family_id IS NULL;
+    UPDATE wish SET family_id = legacy_family WHERE family_id IS NULL;
+END $$;
+
+ALTER TABLE journal ALTER COLUMN family_id SET NOT NULL;
+ALTER TABLE wish ALTER COLUMN family_id SET NOT NULL;
@@ -0,0 +1,7 @@
+-- site wide roles, independent of the family roles in family_member
+CREATE TABLE account_role (
+    account_id  BIGINT NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+    role        VARCHAR(32) NOT NULL CHECK (role IN ('admin', 'author')),
+    created_at  TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, role)
+);
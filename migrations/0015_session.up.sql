@@ -0,0 +1,15 @@
+-- a login session, access tokens issued for it carry its id as jti
+CREATE TABLE session (
+    id              VARCHAR(64) PRIMARY KEY,
+    account_id      BIGINT NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+    refresh_hash    CHAR(64) UNIQUE NOT NULL,
+    -- hash of the refresh token rotated out last, presenting it again revokes the session
+    previous_hash   CHAR(64),
+    expires_at      TIMESTAMP WITHOUT TIME ZONE NOT NULL, -- UTC
+    revoked_at      TIMESTAMP WITHOUT TIME ZONE,
+    created_at      TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at      TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX session_account_id_idx ON session (account_id);
+CREATE INDEX session_previous_hash_idx ON session (previous_hash);
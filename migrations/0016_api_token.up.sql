@@ -0,0 +1,17 @@
+-- personal API tokens for scripts and other non-browser clients, only the hash is stored
+CREATE TABLE api_token (
+    id              BIGSERIAL PRIMARY KEY,
+    account_id      BIGINT NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+    name            VARCHAR(255) NOT NULL,
+    token_hash      CHAR(64) UNIQUE NOT NULL,
+    -- leading characters of the token, to tell tokens apart in listings
+    token_prefix    VARCHAR(16) NOT NULL,
+    -- NULL means the token carries every permission of the account
+    scopes          VARCHAR(32)[],
+    expires_at      TIMESTAMP WITHOUT TIME ZONE, -- UTC
+    last_used_at    TIMESTAMP WITHOUT TIME ZONE,
+    revoked_at      TIMESTAMP WITHOUT TIME ZONE,
+    created_at      TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX api_token_account_id_idx ON api_token (account_id);
@@ -0,0 +1,13 @@
+-- one-time codes mailed to reset a forgotten password, only the hash is stored
+CREATE TABLE password_reset (
+    id              BIGSERIAL PRIMARY KEY,
+    account_id      BIGINT NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+    code_hash       CHAR(64) NOT NULL,
+    -- wrong codes tried against it, the code is dead once it reaches the limit
+    attempts        INT NOT NULL DEFAULT 0,
+    expires_at      TIMESTAMP WITHOUT TIME ZONE NOT NULL, -- UTC
+    used_at         TIMESTAMP WITHOUT TIME ZONE,
+    created_at      TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX password_reset_account_id_idx ON password_reset (account_id);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS account_username_lower_idx;
+
+ALTER TABLE IF EXISTS account ADD CONSTRAINT account_username_key UNIQUE (username);
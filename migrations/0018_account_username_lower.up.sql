@@ -0,0 +1,22 @@
+-- usernames are unique regardless of case, accounts registered before that differ
+-- only in case have to be renamed by hand before the index can be built
+DO $$
+DECLARE
+    clashes TEXT;
+BEGIN
+    SELECT string_agg(names, '; ') INTO clashes
+    FROM (
+        SELECT string_agg(username, ', ' ORDER BY id) AS names
+        FROM account
+        GROUP BY LOWER(username)
+        HAVING COUNT(*) > 1
+    ) duplicated;
+
+    IF clashes IS NOT NULL THEN
+        RAISE EXCEPTION 'usernames differing only in case, rename all but one of each: %', clashes;
+    END IF;
+END $$;
+
+ALTER TABLE account DROP CONSTRAINT IF EXISTS account_username_key;
+
+CREATE UNIQUE INDEX account_username_lower_idx ON account (LOWER(username));
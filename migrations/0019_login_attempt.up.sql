@@ -0,0 +1,12 @@
+-- audit of login attempts, recent failures slow down or lock further attempts
+CREATE TABLE login_attempt (
+    id              BIGSERIAL PRIMARY KEY,
+    -- lowercased, the account may not exist
+    username        VARCHAR(255) NOT NULL,
+    ip              INET,
+    succeeded       BOOLEAN NOT NULL,
+    created_at      TIMESTAMP WITHOUT TIME ZONE DEFAULT (NOW() AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX login_attempt_username_idx ON login_attempt (username, created_at);
+CREATE INDEX login_attempt_ip_idx ON login_attempt (ip, created_at);
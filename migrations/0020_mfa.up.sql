@@ -0,0 +1,20 @@
+-- TOTP second factor, the secret is pending until confirmed with a first code
+CREATE TABLE account_mfa (
+    account_id      BIGINT PRIMARY KEY REFERENCES account(id) ON DELETE CASCADE,
+    secret          VARCHAR(64) NOT NULL,
+    confirmed_at    TIMESTAMP WITHOUT TIME ZONE,
+    -- time step of the last accepted code, older or equal steps are refused against replay
+    last_used_step  BIGINT,
+    created_at      TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- single-use codes to login when the authenticator is lost, only the hash is stored
+CREATE TABLE mfa_recovery_code (
+    id              BIGSERIAL PRIMARY KEY,
+    account_id      BIGINT NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+    code_hash       CHAR(64) NOT NULL,
+    used_at         TIMESTAMP WITHOUT TIME ZONE,
+    created_at      TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX mfa_recovery_code_account_id_idx ON mfa_recovery_code (account_id);
@@ -0,0 +1,9 @@
+-- token buckets of the postgres rate limit backend, keyed by scope and user or ip
+CREATE TABLE rate_limit_bucket (
+    key             VARCHAR(255) PRIMARY KEY,
+    tokens          DOUBLE PRECISION NOT NULL,
+    updated_at      TIMESTAMP WITHOUT TIME ZONE NOT NULL
+);
+
+-- idle buckets are pruned periodically
+CREATE INDEX rate_limit_bucket_updated_at_idx ON rate_limit_bucket (updated_at);
@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS article_visible_idx;
+
+ALTER TABLE IF EXISTS article
+    DROP COLUMN IF EXISTS deleted_at,
+    DROP COLUMN IF EXISTS publish_at,
+    DROP COLUMN IF EXISTS status;
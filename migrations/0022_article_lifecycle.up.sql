@@ -0,0 +1,12 @@
+-- articles are drafted, published, possibly at a later time, archived, and deleted softly.
+-- the ones written so far were visible to everyone, so they start out published.
+-- created_at is in the time zone of the server, publish_at in UTC like the other new columns
+ALTER TABLE article
+    ADD COLUMN status       VARCHAR(16) NOT NULL DEFAULT 'published'
+        CONSTRAINT article_status_check CHECK (status IN ('draft', 'published', 'archived')),
+    ADD COLUMN publish_at   TIMESTAMP WITHOUT TIME ZONE,
+    ADD COLUMN deleted_at   TIMESTAMP WITHOUT TIME ZONE;
+
+UPDATE article SET publish_at = created_at::TIMESTAMP WITH TIME ZONE AT TIME ZONE 'UTC';
+
+CREATE INDEX article_visible_idx ON article (publish_at DESC) WHERE status = 'published' AND deleted_at IS NULL;
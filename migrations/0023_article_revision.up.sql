@@ -0,0 +1,16 @@
+-- every version of the title, summary and text of an article, the latest one is the current version
+CREATE TABLE article_revision (
+    id              BIGSERIAL PRIMARY KEY,
+    article_id      BIGINT NOT NULL REFERENCES article(id) ON DELETE CASCADE,
+    title           VARCHAR(255) NOT NULL,
+    summary         TEXT,
+    text            TEXT,
+    editor_id       BIGINT NOT NULL,
+    created_at      TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX article_revision_article_id_idx ON article_revision (article_id, id DESC);
+
+-- the articles written so far start their history with what they are now
+INSERT INTO article_revision (article_id, title, summary, text, editor_id, created_at)
+SELECT id, title, summary, text, author_id, updated_at FROM article;
@@ -0,0 +1,3 @@
+ALTER TABLE IF EXISTS wish DROP COLUMN IF EXISTS search_vector;
+ALTER TABLE IF EXISTS journal DROP COLUMN IF EXISTS search_vector;
+ALTER TABLE IF EXISTS article DROP COLUMN IF EXISTS search_vector;
@@ -0,0 +1,25 @@
+-- full-text search, the 'simple' configuration neither stems nor drops stop words,
+-- so articles and journals are found whatever language they are written in
+ALTER TABLE article
+    ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', COALESCE(title, '')), 'A')
+        || setweight(to_tsvector('simple', COALESCE(summary, '')), 'B')
+        || setweight(to_tsvector('simple', COALESCE(text, '')), 'C')
+    ) STORED;
+
+CREATE INDEX article_search_idx ON article USING GIN (search_vector);
+
+ALTER TABLE journal
+    ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', title), 'A')
+        || setweight(to_tsvector('simple', content), 'C')
+    ) STORED;
+
+CREATE INDEX journal_search_idx ON journal USING GIN (search_vector);
+
+ALTER TABLE wish
+    ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+        to_tsvector('simple', content)
+    ) STORED;
+
+CREATE INDEX wish_search_idx ON wish USING GIN (search_vector);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS article_author_id_idx;
+DROP INDEX IF EXISTS article_tags_idx;
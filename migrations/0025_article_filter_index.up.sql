@@ -0,0 +1,3 @@
+-- the tag filters use the array operators && and @>, which only a GIN index serves
+CREATE INDEX article_tags_idx ON article USING GIN (tags);
+CREATE INDEX article_author_id_idx ON article (author_id);
@@ -0,0 +1,2 @@
+ALTER TABLE IF EXISTS article_category DROP CONSTRAINT IF EXISTS article_category_levels_key;
+ALTER TABLE IF EXISTS article DROP COLUMN IF EXISTS category_id;
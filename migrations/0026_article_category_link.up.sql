@@ -0,0 +1,63 @@
+-- articles are filed under a category by id, kind keeps the level3 name for older clients
+ALTER TABLE article
+    ADD COLUMN category_id INTEGER REFERENCES article_category(id) ON DELETE RESTRICT;
+
+-- categories added twice are merged into the first of them before the levels are made unique,
+-- nothing refers to a category by id yet
+DO $$
+DECLARE
+    duplicated TEXT;
+BEGIN
+    SELECT string_agg(format('%s / %s / %s (ids %s)', level1, level2, level3, ids), '; ') INTO duplicated
+    FROM (
+        SELECT level1, level2, level3, string_agg(id::TEXT, ', ' ORDER BY id) AS ids
+        FROM article_category
+        GROUP BY level1, level2, level3
+        HAVING COUNT(*) > 1
+    ) merged;
+
+    IF duplicated IS NOT NULL THEN
+        RAISE NOTICE 'merging duplicated article categories into the lowest id: %', duplicated;
+    END IF;
+END $$;
+
+UPDATE article_category c
+SET description = COALESCE(c.description, (
+    SELECT d.description FROM article_category d
+    WHERE (d.level1, d.level2, d.level3) = (c.level1, c.level2, c.level3) AND d.description IS NOT NULL
+    ORDER BY d.id
+    LIMIT 1
+))
+WHERE c.id = (
+    SELECT MIN(k.id) FROM article_category k
+    WHERE (k.level1, k.level2, k.level3) = (c.level1, c.level2, c.level3)
+);
+
+DELETE FROM article_category c
+USING article_category k
+WHERE (k.level1, k.level2, k.level3) = (c.level1, c.level2, c.level3) AND k.id < c.id;
+
+ALTER TABLE article_category
+    ADD CONSTRAINT article_category_levels_key UNIQUE (level1, level2, level3);
+
+-- kind only names the level3, an article is filed when exactly one category has it,
+-- the others are left without a category and reported to be filed by hand
+UPDATE article
+SET category_id = (SELECT c.id FROM article_category c WHERE c.level3 = article.kind)
+WHERE (SELECT COUNT(*) FROM article_category c WHERE c.level3 = article.kind) = 1;
+
+DO $$
+DECLARE
+    ambiguous TEXT;
+BEGIN
+    SELECT string_agg(format('%s (kind %s)', a.id, a.kind), ', ' ORDER BY a.id) INTO ambiguous
+    FROM article a
+    WHERE a.category_id IS NULL
+      AND (SELECT COUNT(*) FROM article_category c WHERE c.level3 = a.kind) > 1;
+
+    IF ambiguous IS NOT NULL THEN
+        RAISE NOTICE 'articles whose kind names several categories are left without one: %', ambiguous;
+    END IF;
+END $$;
+
+CREATE INDEX article_category_id_idx ON article (category_id);
@@ -1,15 +1,153 @@
 use std::fmt::Debug;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use crate::biz::account::recorder::ApiTokenRecord;
+use crate::infra::error::biz::BizKind::ValidationFailed;
+use crate::infra::error::error::{FieldError, ServiceError};
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::password::PasswordPolicy;
 
-#[derive(Serialize, Debug)]
-pub struct RespBodyForAuth {
-    pub user_id: i64,
+const MIN_USERNAME_LEN: usize = 3;
+const MAX_USERNAME_LEN: usize = 32;
+
+#[derive(Deserialize)]
+pub struct ReqBodyForAuth {
     pub username: String,
+    pub password: String,
+}
+
+/// Wrap field errors into a `ValidationFailed` error, `Ok` if there is none.
+pub fn field_errors_to_result(errors: Vec<FieldError>) -> Result<(), ServiceError> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    Err(
+        ServiceError::build()
+            .belong(BizError(ValidationFailed))
+            .message(&format!("{} fields are invalid", errors.len()))
+            .fields(errors)
+            .done()
+    )
+}
+
+/// Usernames are 3 to 32 letters, digits, `_`, `.` or `-`, starting with a letter or digit.
+pub fn check_username(username: &str) -> Vec<FieldError> {
+    let mut errors = vec![];
+
+    let len = username.chars().count();
+
+    if !(MIN_USERNAME_LEN..=MAX_USERNAME_LEN).contains(&len) {
+        errors.push(FieldError::new(
+            "username",
+            &format!("must be {} to {} characters", MIN_USERNAME_LEN, MAX_USERNAME_LEN),
+        ));
+    }
+    if !username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-') {
+        errors.push(FieldError::new("username", "may only contain letters, digits, '_', '.' and '-'"));
+    }
+    if !username.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        errors.push(FieldError::new("username", "must start with a letter or digit"));
+    }
+
+    errors
 }
 
+impl ReqBodyForAuth {
+    /// Only used on registration, login accepts whatever was valid when the account was created.
+    pub fn validate(&self, policy: &PasswordPolicy) -> Result<(), ServiceError> {
+        let mut errors = check_username(&self.username);
+
+        errors.extend(policy.check("password", &self.password, &self.username));
+
+        field_errors_to_result(errors)
+    }
+}
 
 #[derive(Deserialize)]
-pub struct ReqBodyForAuth {
+pub struct ReqBodyForPasswordChange {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ReqBodyForResetCode {
     pub username: String,
-    pub password: String,
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize)]
+pub struct ReqBodyForPasswordReset {
+    pub username: String,
+    pub code: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ReqBodyForApiToken {
+    pub name: String,
+    pub scopes: Option<Vec<String>>,
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct RespBodyForApiToken {
+    pub id: i64,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Option<Vec<String>>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<ApiTokenRecord> for RespBodyForApiToken {
+    fn from(record: ApiTokenRecord) -> Self {
+        RespBodyForApiToken {
+            id: record.id,
+            name: record.name,
+            token_prefix: record.token_prefix,
+            scopes: record.scopes,
+            expires_at: record.expires_at,
+            last_used_at: record.last_used_at,
+            created_at: record.created_at,
+        }
+    }
+}
+
+/// The plaintext token is only returned once, on creation.
+#[derive(Serialize, Debug, Default)]
+pub struct RespBodyForCreatedApiToken {
+    pub token: String,
+    #[serde(flatten)]
+    pub detail: RespBodyForApiToken,
+}
+
+#[derive(Deserialize)]
+pub struct ReqBodyForMfaCode {
+    // a TOTP code, or a recovery code where accepted
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct ReqBodyForMfaLogin {
+    pub mfa_token: String,
+    pub code: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct RespBodyForMfaEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Returned by login instead of cookies when the account has two-factor authentication.
+#[derive(Serialize, Debug, Default)]
+pub struct RespBodyForMfaPending {
+    pub mfa_token: String,
+}
+
+/// Recovery codes are only returned once, when two-factor authentication is confirmed.
+#[derive(Serialize, Debug, Default)]
+pub struct RespBodyForRecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
@@ -1,27 +1,57 @@
+use std::net::IpAddr;
 use std::ops::Add;
-use actix_web::{Error, HttpRequest, HttpResponse, post, web};
-use actix_web::cookie::{Cookie, SameSite};
+use actix_web::{delete, Error, get, HttpRequest, HttpResponse, post, put, web};
+use actix_web::cookie::{Cookie, CookieBuilder, SameSite};
 use actix_web::cookie::time::OffsetDateTime;
+use actix_web::http::header::{HeaderValue, RETRY_AFTER};
 use bcrypt::verify;
-use chrono::{Local, TimeDelta};
-use jsonwebtoken::{encode, EncodingKey, Header};
-use log::{debug};
+use chrono::{TimeDelta, Utc};
+use jsonwebtoken::{Algorithm, decode, DecodingKey, encode, EncodingKey, Header, Validation};
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+use deadpool_postgres::Client as PgClient;
 use crate::AppState;
-use crate::biz::account::courier::ReqBodyForAuth;
-use crate::biz::courier::{SadCourier};
-use crate::biz::internal::get_pg;
-use crate::infra::error::biz::BizKind::AuthorizationFailed;
-use crate::infra::error::error::ServiceError;
+use crate::biz::account::courier::{ReqBodyForApiToken, ReqBodyForAuth, ReqBodyForMfaCode, ReqBodyForMfaLogin, ReqBodyForPasswordChange, ReqBodyForPasswordReset, ReqBodyForResetCode, RespBodyForApiToken, RespBodyForCreatedApiToken, RespBodyForMfaEnrollment, RespBodyForMfaPending, RespBodyForRecoveryCodes, field_errors_to_result};
+use crate::biz::courier::{HappyCourier, SadCourier};
+use crate::biz::internal::{extract_claims, get_pg};
+use crate::biz::user::recorder::query_account_by_id;
+use crate::infra::config::CookieConfig;
+use crate::infra::crypto::{random_code, random_token, sha256_hex};
+use crate::infra::error::biz::BizKind::{AuthorizationFailed, DataConflict, DataNotFound, PermissionDenied, RateLimited, TokenInvalid, ValidationFailed};
+use crate::infra::error::error::{FieldError, ServiceError};
 use crate::infra::error::error::Kind::{BizError, InfraError};
-use super::recorder::{select, add_account};
-use crate::infra::middleware::jwt::{Claims, JWT_AUTH_KEY};
+use super::recorder::{select, add_account, insert_session, rotate_session, revoke_replayed_session, revoke_session, revoke_all_sessions, insert_api_token, select_api_tokens, revoke_api_token, update_password, revoke_other_sessions, insert_password_reset, redeem_password_reset, insert_login_attempt, select_login_failures, MfaRecord, select_mfa, upsert_pending_mfa, use_totp_step, replace_recovery_codes, use_recovery_code, delete_mfa};
+use crate::infra::mail::Mail;
+use crate::infra::totp;
+use crate::infra::middleware::authorize::Permission;
+use crate::infra::middleware::jwt::{API_TOKEN_PREFIX, Claims, JwtMiddleware, JWT_AUTH_KEY, REFRESH_KEY};
 
-const TOKEN_SPAN: i64 = 30;
+const REFRESH_TOKEN_BYTES: usize = 32;
+const SESSION_ID_BYTES: usize = 18;
+const API_TOKEN_BYTES: usize = 32;
+// characters kept in clear to tell tokens apart
+const API_TOKEN_PREFIX_LEN: usize = 12;
+// the refresh cookie is only sent to the account scope
+const REFRESH_COOKIE_PATH: &str = "/api/account";
+// minutes
+const RESET_CODE_SPAN: i64 = 15;
+const RESET_CODE_LEN: usize = 8;
+// wrong codes tolerated before a reset code is burnt
+const RESET_CODE_ATTEMPTS: i32 = 5;
+const MFA_ISSUER: &str = "Hammer";
+// the audience keeps pending tokens from passing as access tokens
+const MFA_AUDIENCE: &str = "mfa";
+// minutes
+const MFA_TOKEN_SPAN: i64 = 5;
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
 
-fn generate_token(id: i64, jwt_secret: &[u8], expired_at: i64) -> Result<String, ServiceError> {
+fn generate_token(id: i64, session_id: &str, jwt_secret: &[u8], expired_at: i64) -> Result<String, ServiceError> {
     let claims = Claims {
         exp: expired_at,
         sub: id,
+        jti: session_id.to_string(),
+        scopes: None,
     };
 
 
@@ -34,7 +64,7 @@ fn generate_token(id: i64, jwt_secret: &[u8], expired_at: i64) -> Result<String,
     Ok(jwt_token)
 }
 
-fn generate_cookie(token: &str, expires: i64) -> Result<Cookie, ServiceError> {
+fn generate_cookie(config: &CookieConfig, name: &'static str, value: String, path: &'static str, expires: i64) -> Result<Cookie<'static>, ServiceError> {
     let expires = OffsetDateTime::from_unix_timestamp(expires)
         .map_err(|err| {
             ServiceError::build()
@@ -43,90 +73,767 @@ fn generate_cookie(token: &str, expires: i64) -> Result<Cookie, ServiceError> {
                 .done()
         })?;
 
-    let cookie = Cookie::build(JWT_AUTH_KEY, token)
+    let cookie = cookie_builder(config, name, value, path)
         .expires(expires)
-        .domain("127.0.0.1")
-        .path("/")
-        .secure(true)
-        .same_site(SameSite::None)
-        .http_only(true)
         .finish();
 
     Ok(cookie)
 }
 
+fn cookie_builder(config: &CookieConfig, name: &'static str, value: String, path: &'static str) -> CookieBuilder<'static> {
+    let builder = Cookie::build(name, value)
+        .path(path)
+        .secure(config.secure)
+        .same_site(config.same_site().unwrap_or(SameSite::Lax))
+        .http_only(true);
+
+    // without a domain the cookie is only sent back to the exact host
+    if config.domain.is_empty() {
+        builder
+    } else {
+        builder.domain(config.domain.clone())
+    }
+}
+
+fn removal_cookie(config: &CookieConfig, name: &'static str, path: &'static str) -> Cookie<'static> {
+    let mut cookie = cookie_builder(config, name, "".to_string(), path).finish();
+
+    cookie.make_removal();
+
+    cookie
+}
+
+/// Issue a short-lived access cookie and a refresh cookie for the session.
+fn generate_session_cookies(app_state: &AppState, account_id: i64, session_id: &str, refresh_token: String, refresh_expires: i64) -> Result<(Cookie<'static>, Cookie<'static>), ServiceError> {
+    let access_expires = Utc::now().add(TimeDelta::minutes(app_state.token.access_minutes)).timestamp();
+
+    let token = generate_token(account_id, session_id, app_state.jwt_secret.as_bytes(), access_expires)?;
+
+    let access_cookie = generate_cookie(&app_state.cookie, JWT_AUTH_KEY, token, "/", access_expires)?;
+
+    let refresh_cookie = generate_cookie(&app_state.cookie, REFRESH_KEY, refresh_token, REFRESH_COOKIE_PATH, refresh_expires)?;
+
+    Ok((access_cookie, refresh_cookie))
+}
+
+/// Start a new session for the account and return its cookies.
+async fn start_session(app_state: &AppState, pg_client: &PgClient, account_id: i64) -> Result<(Cookie<'static>, Cookie<'static>), ServiceError> {
+    let session_id = random_token(SESSION_ID_BYTES)?;
+
+    let refresh_token = random_token(REFRESH_TOKEN_BYTES)?;
+
+    let refresh_expires = Utc::now().add(TimeDelta::days(app_state.token.refresh_days));
+
+    insert_session(pg_client, &session_id, account_id, &sha256_hex(&refresh_token), refresh_expires.naive_utc()).await?;
+
+    generate_session_cookies(app_state, account_id, &session_id, refresh_token, refresh_expires.timestamp())
+}
+
+/// Record a failed login for the audit, and so that further attempts are slowed down.
+async fn fail_login(pg_client: &PgClient, username: &str, ip: Option<IpAddr>, reason: &str) -> Result<HttpResponse, Error> {
+    insert_login_attempt(pg_client, username, ip, false).await?;
+
+    warn!("failed login of {} from {:?}: {}", username, ip, reason);
+
+    Err(
+        ServiceError::build()
+            .belong(BizError(AuthorizationFailed))
+            .message(reason)
+            .done()
+            .into()
+    )
+}
+
+/// A 429 response if recent failures of the username or ip ask to wait before the next attempt.
+async fn hold_back_login(app_state: &AppState, pg_client: &PgClient, username: &str, ip: Option<IpAddr>) -> Result<Option<HttpResponse>, ServiceError> {
+    let failures = select_login_failures(pg_client, username, ip, app_state.login_guard.window()).await?;
+
+    match app_state.login_guard.retry_after(&failures, Utc::now().naive_utc()) {
+        Some(retry_after) => {
+            debug!("login of {} from {:?} is held back for {}s", username, ip, retry_after);
+
+            let mut response = HttpResponse::from_error(
+                ServiceError::build()
+                    .belong(BizError(RateLimited))
+                    .message("Too many failed login attempts, try again later")
+                    .done()
+            );
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(retry_after));
+
+            Ok(Some(response))
+        }
+        None => Ok(None),
+    }
+}
+
 #[post("/login")]
 async fn login(app_state: web::Data<AppState>, body: web::Json<ReqBodyForAuth>, req: HttpRequest) -> Result<HttpResponse, Error> {
-    debug!("login req: {:?}", req);
+    debug!("login req: {} {}", req.method(), req.path());
+    let ip = app_state.rate_limiter.client_ip(&req);
     let req = body.into_inner();
 
+    let username = req.username.to_lowercase();
+
+    let pg_client = get_pg(&app_state).await?;
+
+    if let Some(held_back) = hold_back_login(&app_state, &pg_client, &username, ip).await? {
+        return Ok(held_back);
+    }
+
+    let queried_account = match select(&pg_client, &req.username).await?.pop() {
+        Some(account) => account,
+        None => return fail_login(&pg_client, &username, ip, "User does not exist").await,
+    };
+
+    if !verify(req.password, &queried_account.password).map_err(ServiceError::from)? {
+        return fail_login(&pg_client, &username, ip, "Password is incorrect").await;
+    }
+
+    // the attempt only counts as a success once the second factor is passed as well
+    if select_mfa(&pg_client, queried_account.id).await?.filter(MfaRecord::is_enabled).is_some() {
+        let mfa_token = generate_mfa_token(queried_account.id, app_state.jwt_secret.as_bytes())?;
+
+        return Ok(
+            HttpResponse::Ok().json(
+                HappyCourier::build()
+                    .message("Two-factor code is required")
+                    .data(RespBodyForMfaPending { mfa_token })
+                    .done()
+            )
+        );
+    }
+
+    insert_login_attempt(&pg_client, &username, ip, true).await?;
+
+    let (access_cookie, refresh_cookie) = start_session(&app_state, &pg_client, queried_account.id).await?;
+
+    Ok(
+        HttpResponse::Ok()
+            .cookie(access_cookie)
+            .cookie(refresh_cookie)
+            .json(
+                SadCourier::brief("Login success")
+            )
+    )
+}
+
+
+#[post("/register")]
+async fn register(app_state: web::Data<AppState>, account_json: web::Json<ReqBodyForAuth>) -> Result<HttpResponse, Error> {
+    let body = account_json.into_inner();
+
+    body.validate(&app_state.password_policy)?;
+
+    let client = get_pg(&app_state).await?;
+
+    let account_record = match add_account(&client, &body.username, &body.password).await {
+        Ok(account_record) => account_record,
+        Err(err) if err.biz_kind() == Some(DataConflict) => {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(DataConflict))
+                    .because(Box::new(err))
+                    .message("Username exists")
+                    .fields(vec![FieldError::new("username", "is taken")])
+                    .done()
+                    .into()
+            );
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let (access_cookie, refresh_cookie) = start_session(&app_state, &client, account_record.id).await?;
+
+    Ok(
+        HttpResponse::Created()
+            .cookie(access_cookie)
+            .cookie(refresh_cookie)
+            .json(
+                SadCourier::brief("Registration success")
+            )
+    )
+}
+
+/// Exchange the refresh cookie for a new access cookie, rotating the refresh token.
+#[post("/refresh")]
+async fn refresh(app_state: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let refresh_token = req.cookie(REFRESH_KEY)
+        .map(|cookie| cookie.value().to_string())
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(BizError(TokenInvalid))
+                .message("Refresh token is missing")
+                .done()
+        })?;
+
+    let old_hash = sha256_hex(&refresh_token);
+
+    let new_refresh_token = random_token(REFRESH_TOKEN_BYTES)?;
+
+    let refresh_expires = Utc::now().add(TimeDelta::days(app_state.token.refresh_days));
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let session = match rotate_session(&pg_client, &old_hash, &sha256_hex(&new_refresh_token), refresh_expires.naive_utc()).await? {
+        Some(session) => session,
+        None => {
+            let revoked = revoke_replayed_session(&pg_client, &old_hash).await?;
+            debug!("refresh token is unknown, revoked {} replayed session", revoked);
+
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(TokenInvalid))
+                    .message("Refresh token is invalid, revoked or expired")
+                    .done()
+                    .into()
+            );
+        }
+    };
+
+    let (access_cookie, refresh_cookie) = generate_session_cookies(
+        &app_state,
+        session.account_id,
+        &session.id,
+        new_refresh_token,
+        refresh_expires.timestamp(),
+    )?;
+
+    Ok(
+        HttpResponse::Ok()
+            .cookie(access_cookie)
+            .cookie(refresh_cookie)
+            .json(
+                SadCourier::brief("Refresh success")
+            )
+    )
+}
+
+#[post("/logout", wrap = "JwtMiddleware")]
+async fn logout(app_state: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let claims = extract_claims(req)?;
+
+    let pg_client = get_pg(&app_state).await?;
+
+    revoke_session(&pg_client, &claims.jti, claims.sub).await?;
+
+    Ok(
+        HttpResponse::Ok()
+            .cookie(removal_cookie(&app_state.cookie, JWT_AUTH_KEY, "/"))
+            .cookie(removal_cookie(&app_state.cookie, REFRESH_KEY, REFRESH_COOKIE_PATH))
+            .json(
+                SadCourier::brief("Logout success")
+            )
+    )
+}
+
+/// Revoke every session of the account, e.g. when a cookie has been stolen.
+#[post("/logout-all", wrap = "JwtMiddleware")]
+async fn logout_all(app_state: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let claims = extract_claims(req)?;
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let revoked = revoke_all_sessions(&pg_client, claims.sub).await?;
+
+    debug!("revoked {} sessions of user {}", revoked, claims.sub);
+
+    Ok(
+        HttpResponse::Ok()
+            .cookie(removal_cookie(&app_state.cookie, JWT_AUTH_KEY, "/"))
+            .cookie(removal_cookie(&app_state.cookie, REFRESH_KEY, REFRESH_COOKIE_PATH))
+            .json(
+                SadCourier::brief("Logout from all sessions success")
+            )
+    )
+}
+
+/// API tokens can not be used to manage API tokens, only a login session can.
+fn ensure_session(claims: &Claims) -> Result<(), ServiceError> {
+    if claims.is_api_token() {
+        return Err(
+            ServiceError::build()
+                .belong(BizError(PermissionDenied))
+                .message("API tokens can not be managed with an API token")
+                .done()
+        );
+    }
+    Ok(())
+}
+
+#[post("/tokens", wrap = "JwtMiddleware")]
+async fn create_api_token(app_state: web::Data<AppState>, req: HttpRequest, body: web::Json<ReqBodyForApiToken>) -> Result<HttpResponse, Error> {
+    let claims = extract_claims(req)?;
+
+    ensure_session(&claims)?;
+
+    let body = body.into_inner();
+
+    if body.name.trim().is_empty() {
+        return Err(
+            ServiceError::build()
+                .belong(BizError(ValidationFailed))
+                .message("name must not be empty")
+                .done()
+                .into()
+        );
+    }
+
+    if let Some(scopes) = &body.scopes {
+        if let Some(unknown) = scopes.iter().find(|scope| Permission::from_scope(scope).is_none()) {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message(&format!("unknown scope: {}", unknown))
+                    .done()
+                    .into()
+            );
+        }
+    }
+
+    let expires_at = match body.expires_in_days {
+        Some(days) if days <= 0 => {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("expires_in_days must be greater than zero")
+                    .done()
+                    .into()
+            );
+        }
+        Some(days) => Some(Utc::now().add(TimeDelta::days(days)).naive_utc()),
+        None => None,
+    };
+
+    let token = format!("{}{}", API_TOKEN_PREFIX, random_token(API_TOKEN_BYTES)?);
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let api_token_record = insert_api_token(
+        &pg_client,
+        claims.sub,
+        body.name.trim(),
+        &sha256_hex(&token),
+        &token[..API_TOKEN_PREFIX_LEN],
+        &body.scopes,
+        expires_at,
+    ).await?;
+
+    Ok(
+        HttpResponse::Created().json(
+            HappyCourier::build()
+                .message("Success to create API token, it will not be shown again")
+                .data(
+                    RespBodyForCreatedApiToken {
+                        token,
+                        detail: api_token_record.into(),
+                    }
+                )
+                .done()
+        )
+    )
+}
+
+#[get("/tokens", wrap = "JwtMiddleware")]
+async fn read_api_tokens(app_state: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let claims = extract_claims(req)?;
+
+    ensure_session(&claims)?;
+
     let pg_client = get_pg(&app_state).await?;
 
-    let queried_account = select(&pg_client, &req.username)
+    let api_tokens = select_api_tokens(&pg_client, claims.sub)
         .await?
-        .pop()
-        .ok_or(
+        .into_iter()
+        .map(Into::into)
+        .collect::<Vec<RespBodyForApiToken>>();
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to get API tokens")
+                .data(api_tokens)
+                .done()
+        )
+    )
+}
+
+#[delete("/tokens/{token_id}", wrap = "JwtMiddleware")]
+async fn revoke_token(app_state: web::Data<AppState>, req: HttpRequest, path: web::Path<i64>) -> Result<HttpResponse, Error> {
+    let claims = extract_claims(req)?;
+
+    ensure_session(&claims)?;
+
+    let token_id = path.into_inner();
+
+    let pg_client = get_pg(&app_state).await?;
+
+    if !revoke_api_token(&pg_client, token_id, claims.sub).await? {
+        return Err(
+            ServiceError::build()
+                .belong(BizError(DataNotFound))
+                .message("The API token does not exist")
+                .done()
+                .into()
+        );
+    }
+
+    Ok(
+        HttpResponse::Ok().json(
+            SadCourier::brief("Success to revoke API token")
+        )
+    )
+}
+
+/// Change the password of the current user, other sessions are logged out.
+#[put("/password", wrap = "JwtMiddleware")]
+async fn change_password(app_state: web::Data<AppState>, req: HttpRequest, body: web::Json<ReqBodyForPasswordChange>) -> Result<HttpResponse, Error> {
+    let claims = extract_claims(req)?;
+
+    ensure_session(&claims)?;
+
+    let body = body.into_inner();
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let account = query_account_by_id(&pg_client, claims.sub).await?;
+
+    field_errors_to_result(app_state.password_policy.check("new_password", &body.new_password, &account.username))?;
+
+    if !verify(&body.current_password, &account.password).map_err(ServiceError::from)? {
+        return Err(
             ServiceError::build()
                 .belong(BizError(AuthorizationFailed))
-                .message("User does not exist")
+                .message("Current password is incorrect")
                 .done()
-        )?;
+                .into()
+        );
+    }
 
+    update_password(&pg_client, account.id, &body.new_password).await?;
 
-    match verify(req.password, &queried_account.password) {
-        Ok(pwd_is_correct) => {
-            if pwd_is_correct {
-                let expires = Local::now().add(TimeDelta::hours(TOKEN_SPAN)).timestamp();
+    let revoked = revoke_other_sessions(&pg_client, account.id, &claims.jti).await?;
 
-                let token = generate_token(queried_account.id, app_state.jwt_secret.as_bytes(), expires)?;
+    debug!("password of user {} changed, revoked {} other sessions", account.id, revoked);
 
-                let cookie = generate_cookie(&token, expires)?;
+    Ok(
+        HttpResponse::Ok().json(
+            SadCourier::brief("Success to change password")
+        )
+    )
+}
 
-                Ok(
-                    HttpResponse::Ok()
-                        .cookie(cookie)
-                        .json(
-                            SadCourier::brief("Login success")
-                        )
-                )
-            } else {
-                Ok(HttpResponse::Unauthorized().finish())
+/// Mail a one-time reset code to the account. The response is the same whether the account
+/// exists or not, so it can not be used to probe usernames.
+#[post("/password/reset-code")]
+async fn request_password_reset(app_state: web::Data<AppState>, body: web::Json<ReqBodyForResetCode>) -> Result<HttpResponse, Error> {
+    let body = body.into_inner();
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let queried_account = select(&pg_client, &body.username)
+        .await?
+        .pop();
+
+    match queried_account {
+        Some(account) => match account.email {
+            Some(email) => {
+                let code = random_code(RESET_CODE_LEN)?;
+
+                let expires_at = Utc::now().add(TimeDelta::minutes(RESET_CODE_SPAN)).naive_utc();
+
+                insert_password_reset(&pg_client, account.id, &sha256_hex(&code), expires_at).await?;
+
+                let mail = Mail {
+                    to: email,
+                    subject: "Reset your password".to_string(),
+                    body: format!(
+                        "Hi {},\n\nYour password reset code is {}. It expires in {} minutes and can only be used once.\n\nIf you did not ask for it, just ignore this mail.",
+                        account.username, code, RESET_CODE_SPAN
+                    ),
+                };
+
+                // sent in the background, waiting for the mail server would tell existing accounts apart by the response time
+                let mailer = app_state.mailer.clone();
+                actix_web::rt::spawn(async move {
+                    if let Err(err) = mailer.send(mail).await {
+                        error!("failed to mail reset code to user {}: {}", account.id, err);
+                    }
+                });
             }
-        }
-        Err(_) => Ok(HttpResponse::InternalServerError().finish())
+            None => debug!("user {} has no email, no reset code is sent", account.id),
+        },
+        None => debug!("reset code requested for unknown user {}", body.username),
     }
+
+    Ok(
+        HttpResponse::Accepted().json(
+            SadCourier::brief("If the account has an email, a reset code has been sent to it")
+        )
+    )
 }
 
+/// Set a new password with a mailed reset code, every session of the account is logged out.
+#[post("/password/reset")]
+async fn reset_password(app_state: web::Data<AppState>, body: web::Json<ReqBodyForPasswordReset>) -> Result<HttpResponse, Error> {
+    let body = body.into_inner();
 
-#[post("/register")]
-async fn register(app_state: web::Data<AppState>, account_json: web::Json<ReqBodyForAuth>) -> Result<HttpResponse, Error> {
-    let body = account_json.into_inner();
-    let client = get_pg(&app_state).await?;
+    let pg_client = get_pg(&app_state).await?;
 
-    let queried_account = select(&client, &body.username)
-        .await?;
+    let invalid_code = || {
+        ServiceError::build()
+            .belong(BizError(TokenInvalid))
+            .message("Reset code is invalid, used or expired")
+            .done()
+    };
 
-    if queried_account.is_empty() {
-        let account_record = add_account(&client, &body.username, &body.password).await?;
+    let account = select(&pg_client, &body.username)
+        .await?
+        .pop()
+        .ok_or_else(invalid_code)?;
+
+    field_errors_to_result(app_state.password_policy.check("new_password", &body.new_password, &account.username))?;
 
-        let expires = Local::now().add(TimeDelta::hours(TOKEN_SPAN)).timestamp();
+    let code_hash = sha256_hex(&body.code.trim().to_uppercase());
 
-        let token = generate_token(account_record.id, app_state.jwt_secret.as_bytes(), expires)?;
+    if !redeem_password_reset(&pg_client, account.id, &code_hash, RESET_CODE_ATTEMPTS).await? {
+        return Err(invalid_code().into());
+    }
 
-        let cookie = generate_cookie(&token, expires)?;
+    update_password(&pg_client, account.id, &body.new_password).await?;
 
-        Ok(
-            HttpResponse::Created()
-                .cookie(cookie)
-                .json(
-                    SadCourier::brief("Registration success")
-                )
+    let revoked = revoke_all_sessions(&pg_client, account.id).await?;
+
+    debug!("password of user {} reset, revoked {} sessions", account.id, revoked);
+
+    Ok(
+        HttpResponse::Ok().json(
+            SadCourier::brief("Success to reset password")
         )
-    } else {
-        Ok(
-            HttpResponse::Conflict().json(
-                SadCourier::brief("Username exists")
+    )
+}
+
+/// Proves the password of `sub` was right, only good for passing the second factor.
+#[derive(Debug, Serialize, Deserialize)]
+struct MfaPendingClaims {
+    sub: i64,
+    exp: i64,
+    aud: String,
+}
+
+fn generate_mfa_token(account_id: i64, jwt_secret: &[u8]) -> Result<String, ServiceError> {
+    let claims = MfaPendingClaims {
+        sub: account_id,
+        exp: Utc::now().add(TimeDelta::minutes(MFA_TOKEN_SPAN)).timestamp(),
+        aud: MFA_AUDIENCE.to_string(),
+    };
+
+    let mfa_token = encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt_secret))?;
+
+    Ok(mfa_token)
+}
+
+fn decode_mfa_token(mfa_token: &str, jwt_secret: &[u8]) -> Result<i64, ServiceError> {
+    let mut validation = Validation::new(Algorithm::default());
+    validation.set_audience(&[MFA_AUDIENCE]);
+
+    decode::<MfaPendingClaims>(mfa_token, &DecodingKey::from_secret(jwt_secret), &validation)
+        .map(|data| data.claims.sub)
+        .map_err(|err| {
+            ServiceError::build()
+                .belong(BizError(TokenInvalid))
+                .because(Box::new(err))
+                .message("Two-factor token is invalid or expired")
+                .done()
+        })
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Accept either a fresh TOTP code or an unused recovery code.
+async fn verify_second_factor(pg_client: &PgClient, mfa: &MfaRecord, code: &str) -> Result<bool, ServiceError> {
+    match totp::verify(&mfa.secret, code, Utc::now().timestamp())? {
+        Some(step) => use_totp_step(pg_client, mfa.account_id, step).await,
+        None => use_recovery_code(pg_client, mfa.account_id, &sha256_hex(&normalize_recovery_code(code))).await,
+    }
+}
+
+fn incorrect_mfa_code() -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(ValidationFailed))
+        .message("Two-factor code is incorrect")
+        .fields(vec![FieldError::new("code", "is incorrect or used already")])
+        .done()
+}
+
+fn mfa_enabled_already() -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(DataConflict))
+        .message("Two-factor authentication is enabled already")
+        .done()
+}
+
+/// Finish a login held for the second factor and issue the session cookies.
+#[post("/login/mfa")]
+async fn login_mfa(app_state: web::Data<AppState>, body: web::Json<ReqBodyForMfaLogin>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let ip = app_state.rate_limiter.client_ip(&req);
+    let body = body.into_inner();
+
+    let account_id = decode_mfa_token(&body.mfa_token, app_state.jwt_secret.as_bytes())?;
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let account = query_account_by_id(&pg_client, account_id).await?;
+
+    let username = account.username.to_lowercase();
+
+    if let Some(held_back) = hold_back_login(&app_state, &pg_client, &username, ip).await? {
+        return Ok(held_back);
+    }
+
+    let mfa = select_mfa(&pg_client, account.id)
+        .await?
+        .filter(MfaRecord::is_enabled)
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(BizError(TokenInvalid))
+                .message("Two-factor authentication is not enabled")
+                .done()
+        })?;
+
+    if !verify_second_factor(&pg_client, &mfa, &body.code).await? {
+        return fail_login(&pg_client, &username, ip, "Two-factor code is incorrect").await;
+    }
+
+    insert_login_attempt(&pg_client, &username, ip, true).await?;
+
+    let (access_cookie, refresh_cookie) = start_session(&app_state, &pg_client, account.id).await?;
+
+    Ok(
+        HttpResponse::Ok()
+            .cookie(access_cookie)
+            .cookie(refresh_cookie)
+            .json(
+                SadCourier::brief("Login success")
             )
+    )
+}
+
+/// Start enrolling a TOTP authenticator, it is not required at login until confirmed.
+#[post("/mfa/enroll", wrap = "JwtMiddleware")]
+async fn enroll_mfa(app_state: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let claims = extract_claims(req)?;
+
+    ensure_session(&claims)?;
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let account = query_account_by_id(&pg_client, claims.sub).await?;
+
+    let secret = totp::generate_secret()?;
+
+    upsert_pending_mfa(&pg_client, account.id, &secret)
+        .await?
+        .ok_or_else(mfa_enabled_already)?;
+
+    let otpauth_uri = totp::otpauth_uri(MFA_ISSUER, &account.username, &secret);
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Add the secret to an authenticator app and confirm with a code")
+                .data(RespBodyForMfaEnrollment { secret, otpauth_uri })
+                .done()
         )
+    )
+}
+
+/// Confirm the enrolled authenticator with its first code, returns the recovery codes.
+#[post("/mfa/confirm", wrap = "JwtMiddleware")]
+async fn confirm_mfa(app_state: web::Data<AppState>, req: HttpRequest, body: web::Json<ReqBodyForMfaCode>) -> Result<HttpResponse, Error> {
+    let claims = extract_claims(req)?;
+
+    ensure_session(&claims)?;
+
+    let mut pg_client = get_pg(&app_state).await?;
+
+    let mfa = select_mfa(&pg_client, claims.sub)
+        .await?
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(BizError(DataNotFound))
+                .message("Two-factor authentication is not enrolled")
+                .done()
+        })?;
+
+    if mfa.is_enabled() {
+        return Err(mfa_enabled_already().into());
     }
+
+    let step = totp::verify(&mfa.secret, &body.code, Utc::now().timestamp())?
+        .ok_or_else(incorrect_mfa_code)?;
+
+    if !use_totp_step(&pg_client, mfa.account_id, step).await? {
+        return Err(incorrect_mfa_code().into());
+    }
+
+    let mut recovery_codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+
+    for _ in 0..RECOVERY_CODE_COUNT {
+        let code = random_code(RECOVERY_CODE_LEN)?;
+        let (head, tail) = code.split_at(RECOVERY_CODE_LEN / 2);
+        recovery_codes.push(format!("{}-{}", head, tail));
+    }
+
+    let code_hashes = recovery_codes
+        .iter()
+        .map(|code| sha256_hex(&normalize_recovery_code(code)))
+        .collect::<Vec<String>>();
+
+    replace_recovery_codes(&mut pg_client, mfa.account_id, &code_hashes).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Two-factor authentication is enabled, keep the recovery codes safe")
+                .data(RespBodyForRecoveryCodes { recovery_codes })
+                .done()
+        )
+    )
+}
+
+/// Turn two-factor authentication off, a current code or a recovery code is required.
+#[delete("/mfa", wrap = "JwtMiddleware")]
+async fn disable_mfa(app_state: web::Data<AppState>, req: HttpRequest, body: web::Json<ReqBodyForMfaCode>) -> Result<HttpResponse, Error> {
+    let claims = extract_claims(req)?;
+
+    ensure_session(&claims)?;
+
+    let mut pg_client = get_pg(&app_state).await?;
+
+    let mfa = select_mfa(&pg_client, claims.sub)
+        .await?
+        .filter(MfaRecord::is_enabled)
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(BizError(DataNotFound))
+                .message("Two-factor authentication is not enabled")
+                .done()
+        })?;
+
+    if !verify_second_factor(&pg_client, &mfa, &body.code).await? {
+        return Err(incorrect_mfa_code().into());
+    }
+
+    delete_mfa(&mut pg_client, mfa.account_id).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            SadCourier::brief("Two-factor authentication is disabled")
+        )
+    )
 }
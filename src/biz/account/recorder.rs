@@ -5,8 +5,12 @@ use deadpool_postgres::{Client as PgClient, GenericClient};
 use tokio_pg_mapper::FromTokioPostgresRow;
 use chrono::{NaiveDate};
 use chrono::NaiveDateTime;
+use std::net::IpAddr;
+use chrono::TimeDelta;
 use log::debug;
+use tracing::instrument;
 use crate::infra::error::error::ServiceError;
+use crate::infra::login_guard::LoginFailures;
 
 #[derive(Deserialize, PostgresMapper, Debug, Serialize)]
 #[pg_mapper(table = "account")]
@@ -27,13 +31,14 @@ pub struct Account {
 }
 
 
+#[instrument(skip_all)]
 pub async fn select(client: &PgClient, username: &str) -> Result<Vec<Account>, ServiceError> {
     let stmt = r#"
         SELECT
             *
         FROM
             account
-        WHERE username = $1;
+        WHERE LOWER(username) = LOWER($1);
     "#;
     // If no qualified user, raise a BizError of DataNotFound
     let rows = client
@@ -41,11 +46,13 @@ pub async fn select(client: &PgClient, username: &str) -> Result<Vec<Account>, S
         .await?;
 
     rows.iter()
-        .map(|row| Account::from_row_ref(&row).map_err(Into::into))
+        .map(|row| Account::from_row_ref(row).map_err(Into::into))
         .collect::<Result<Vec<Account>, ServiceError>>()
 }
 
-pub async fn add_account(pc: &PgClient, username: &str, password: &str) -> Result<Account, ServiceError> {
+/// A username taken already, ignoring case, fails with `DataConflict`.
+#[instrument(skip_all)]
+pub async fn add_account(pc: &impl GenericClient, username: &str, password: &str) -> Result<Account, ServiceError> {
     let hashed_pwd = hash(password, DEFAULT_COST)?;
 
     let stmt = "INSERT INTO account(username, password) VALUES ($1, $2) RETURNING *";
@@ -58,4 +65,574 @@ pub async fn add_account(pc: &PgClient, username: &str, password: &str) -> Resul
         debug!("from row ref: {:?}", e);
         Into::into(e)
     })
-}
\ No newline at end of file
+}
+
+#[instrument(skip_all)]
+pub async fn select_roles(pc: &PgClient, account_id: i64) -> Result<Vec<String>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            role
+        FROM
+            account_role
+        WHERE
+            account_id = $1;
+    "#;
+
+    let rows = pc
+        .query(stmt, &[&account_id])
+        .await?;
+
+    Ok(
+        rows.iter()
+            .map(|row| row.get("role"))
+            .collect()
+    )
+}
+
+#[instrument(skip_all)]
+pub async fn insert_role(pc: &PgClient, account_id: i64, role: &str) -> Result<(), ServiceError> {
+    let stmt = r#"
+        INSERT INTO
+            account_role (account_id, role)
+        VALUES
+            ($1, $2)
+        ON CONFLICT (account_id, role) DO NOTHING;
+    "#;
+
+    pc.execute(stmt, &[&account_id, &role]).await?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, PostgresMapper, Debug, Serialize)]
+#[pg_mapper(table = "session")]
+pub struct SessionRecord {
+    pub id: String,
+    pub account_id: i64,
+    pub refresh_hash: String,
+    pub previous_hash: Option<String>,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[instrument(skip_all)]
+pub async fn insert_session(pc: &PgClient, id: &str, account_id: i64, refresh_hash: &str, expires_at: NaiveDateTime) -> Result<SessionRecord, ServiceError> {
+    let stmt = r#"
+        INSERT INTO
+            session (id, account_id, refresh_hash, expires_at)
+        VALUES
+            ($1, $2, $3, $4)
+        RETURNING *;
+    "#;
+
+    let row = pc
+        .query_one(stmt, &[&id, &account_id, &refresh_hash, &expires_at])
+        .await?;
+
+    let session_record = SessionRecord::from_row_ref(&row)?;
+
+    Ok(session_record)
+}
+
+/// Swap the refresh token of an active session, `None` if no active session holds `old_hash`.
+#[instrument(skip_all)]
+pub async fn rotate_session(pc: &PgClient, old_hash: &str, new_hash: &str, expires_at: NaiveDateTime) -> Result<Option<SessionRecord>, ServiceError> {
+    let stmt = r#"
+        UPDATE session
+        SET
+            previous_hash = refresh_hash,
+            refresh_hash = $2,
+            expires_at = $3,
+            updated_at = NOW()
+        WHERE
+            refresh_hash = $1 AND revoked_at IS NULL AND expires_at > (NOW() AT TIME ZONE 'UTC')
+        RETURNING *;
+    "#;
+
+    let row = pc
+        .query_opt(stmt, &[&old_hash, &new_hash, &expires_at])
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(SessionRecord::from_row_ref(&row)?)),
+        None => Ok(None),
+    }
+}
+
+/// A refresh token rotated out earlier is being replayed, so it may have been stolen:
+/// revoke the whole session it belonged to.
+#[instrument(skip_all)]
+pub async fn revoke_replayed_session(pc: &PgClient, previous_hash: &str) -> Result<u64, ServiceError> {
+    let stmt = r#"
+        UPDATE session
+        SET
+            revoked_at = NOW()
+        WHERE
+            previous_hash = $1 AND revoked_at IS NULL;
+    "#;
+
+    let revoked = pc.execute(stmt, &[&previous_hash]).await?;
+
+    Ok(revoked)
+}
+
+#[instrument(skip_all)]
+pub async fn revoke_session(pc: &PgClient, id: &str, account_id: i64) -> Result<(), ServiceError> {
+    let stmt = r#"
+        UPDATE session
+        SET
+            revoked_at = NOW()
+        WHERE
+            id = $1 AND account_id = $2 AND revoked_at IS NULL;
+    "#;
+
+    pc.execute(stmt, &[&id, &account_id]).await?;
+
+    Ok(())
+}
+
+#[instrument(skip_all)]
+pub async fn revoke_all_sessions(pc: &PgClient, account_id: i64) -> Result<u64, ServiceError> {
+    let stmt = r#"
+        UPDATE session
+        SET
+            revoked_at = NOW()
+        WHERE
+            account_id = $1 AND revoked_at IS NULL;
+    "#;
+
+    let revoked = pc.execute(stmt, &[&account_id]).await?;
+
+    Ok(revoked)
+}
+
+#[instrument(skip_all)]
+pub async fn is_session_active(pc: &PgClient, id: &str, account_id: i64) -> Result<bool, ServiceError> {
+    let stmt = r#"
+        SELECT EXISTS (
+            SELECT
+                1
+            FROM
+                session
+            WHERE
+                id = $1 AND account_id = $2 AND revoked_at IS NULL AND expires_at > (NOW() AT TIME ZONE 'UTC')
+        );
+    "#;
+
+    let active = pc
+        .query_one(stmt, &[&id, &account_id])
+        .await?
+        .get(0);
+
+    Ok(active)
+}
+
+#[derive(Deserialize, PostgresMapper, Debug, Serialize)]
+#[pg_mapper(table = "api_token")]
+pub struct ApiTokenRecord {
+    pub id: i64,
+    pub account_id: i64,
+    pub name: String,
+    pub token_hash: String,
+    pub token_prefix: String,
+    pub scopes: Option<Vec<String>>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[instrument(skip_all)]
+pub async fn insert_api_token(pc: &PgClient, account_id: i64, name: &str, token_hash: &str, token_prefix: &str, scopes: &Option<Vec<String>>, expires_at: Option<NaiveDateTime>) -> Result<ApiTokenRecord, ServiceError> {
+    let stmt = r#"
+        INSERT INTO
+            api_token (
+                account_id,
+                name,
+                token_hash,
+                token_prefix,
+                scopes,
+                expires_at
+            )
+        VALUES
+            ($1, $2, $3, $4, $5, $6)
+        RETURNING *;
+    "#;
+
+    let row = pc
+        .query_one(stmt, &[&account_id, &name, &token_hash, &token_prefix, scopes, &expires_at])
+        .await?;
+
+    let api_token_record = ApiTokenRecord::from_row_ref(&row)?;
+
+    Ok(api_token_record)
+}
+
+#[instrument(skip_all)]
+pub async fn select_api_tokens(pc: &PgClient, account_id: i64) -> Result<Vec<ApiTokenRecord>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            api_token
+        WHERE
+            account_id = $1 AND revoked_at IS NULL
+        ORDER BY
+            created_at DESC;
+    "#;
+
+    let rows = pc
+        .query(stmt, &[&account_id])
+        .await?;
+
+    rows.iter()
+        .map(|row| ApiTokenRecord::from_row_ref(row).map_err(Into::into))
+        .collect::<Result<Vec<ApiTokenRecord>, ServiceError>>()
+}
+
+/// Look up a usable token by its hash and record that it has been used.
+#[instrument(skip_all)]
+pub async fn use_api_token(pc: &PgClient, token_hash: &str) -> Result<Option<ApiTokenRecord>, ServiceError> {
+    let stmt = r#"
+        UPDATE api_token
+        SET
+            last_used_at = NOW()
+        WHERE
+            token_hash = $1
+            AND revoked_at IS NULL
+            AND (expires_at IS NULL OR expires_at > (NOW() AT TIME ZONE 'UTC'))
+        RETURNING *;
+    "#;
+
+    let row = pc
+        .query_opt(stmt, &[&token_hash])
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(ApiTokenRecord::from_row_ref(&row)?)),
+        None => Ok(None),
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn revoke_api_token(pc: &PgClient, id: i64, account_id: i64) -> Result<bool, ServiceError> {
+    let stmt = r#"
+        UPDATE api_token
+        SET
+            revoked_at = NOW()
+        WHERE
+            id = $1 AND account_id = $2 AND revoked_at IS NULL;
+    "#;
+
+    let revoked = pc.execute(stmt, &[&id, &account_id]).await?;
+
+    Ok(revoked > 0)
+}
+
+#[instrument(skip_all)]
+pub async fn update_password(pc: &PgClient, account_id: i64, password: &str) -> Result<(), ServiceError> {
+    let hashed_pwd = hash(password, DEFAULT_COST)?;
+
+    let stmt = r#"
+        UPDATE account
+        SET
+            password = $2,
+            updated_at = NOW()
+        WHERE
+            id = $1;
+    "#;
+
+    pc.execute(stmt, &[&account_id, &hashed_pwd]).await?;
+
+    Ok(())
+}
+
+/// Revoke every session of the account except the one in use.
+#[instrument(skip_all)]
+pub async fn revoke_other_sessions(pc: &PgClient, account_id: i64, kept_id: &str) -> Result<u64, ServiceError> {
+    let stmt = r#"
+        UPDATE session
+        SET
+            revoked_at = NOW()
+        WHERE
+            account_id = $1 AND id <> $2 AND revoked_at IS NULL;
+    "#;
+
+    let revoked = pc.execute(stmt, &[&account_id, &kept_id]).await?;
+
+    Ok(revoked)
+}
+
+/// Store a new reset code, any code issued before for the account stops working.
+#[instrument(skip_all)]
+pub async fn insert_password_reset(pc: &PgClient, account_id: i64, code_hash: &str, expires_at: NaiveDateTime) -> Result<(), ServiceError> {
+    let stmt = r#"
+        WITH superseded AS (
+            UPDATE password_reset
+            SET
+                used_at = NOW()
+            WHERE
+                account_id = $1 AND used_at IS NULL
+        )
+        INSERT INTO
+            password_reset (account_id, code_hash, expires_at)
+        VALUES
+            ($1, $2, $3);
+    "#;
+
+    pc.execute(stmt, &[&account_id, &code_hash, &expires_at]).await?;
+
+    Ok(())
+}
+
+/// Try `code_hash` against the latest pending reset code of the account, every try counts towards `max_attempts`.
+/// Return true if the code matched, it is used up then.
+#[instrument(skip_all)]
+pub async fn redeem_password_reset(pc: &PgClient, account_id: i64, code_hash: &str, max_attempts: i32) -> Result<bool, ServiceError> {
+    // two requests for a code at once may both leave one pending, only the latest counts
+    let stmt = r#"
+        UPDATE password_reset
+        SET
+            attempts = attempts + 1,
+            used_at = CASE WHEN code_hash = $2 THEN NOW() END
+        WHERE
+            id = (
+                SELECT
+                    id
+                FROM
+                    password_reset
+                WHERE
+                    account_id = $1
+                    AND used_at IS NULL
+                    AND attempts < $3
+                    AND expires_at > (NOW() AT TIME ZONE 'UTC')
+                ORDER BY
+                    created_at DESC, id DESC
+                LIMIT 1
+                FOR UPDATE
+            )
+            AND used_at IS NULL
+            AND attempts < $3
+        RETURNING used_at IS NOT NULL AS redeemed;
+    "#;
+
+    let row = pc
+        .query_opt(stmt, &[&account_id, &code_hash, &max_attempts])
+        .await?;
+
+    Ok(row.map(|row| row.get("redeemed")).unwrap_or(false))
+}
+
+/// `username` is expected lowercased.
+#[instrument(skip_all)]
+pub async fn insert_login_attempt(pc: &PgClient, username: &str, ip: Option<IpAddr>, succeeded: bool) -> Result<(), ServiceError> {
+    let stmt = r#"
+        INSERT INTO
+            login_attempt (username, ip, succeeded)
+        VALUES
+            ($1, $2, $3);
+    "#;
+
+    pc.execute(stmt, &[&username, &ip, &succeeded]).await?;
+
+    Ok(())
+}
+
+/// Failed logins within `window`, those of the username only count since its last success.
+#[instrument(skip_all)]
+pub async fn select_login_failures(pc: &PgClient, username: &str, ip: Option<IpAddr>, window: TimeDelta) -> Result<LoginFailures, ServiceError> {
+    let stmt = r#"
+        WITH recent AS (
+            SELECT
+                *
+            FROM
+                login_attempt
+            WHERE
+                created_at > (NOW() AT TIME ZONE 'UTC') - make_interval(secs => $3)
+                AND (username = $1 OR ip = $2)
+        ),
+        last_success AS (
+            SELECT
+                COALESCE(MAX(created_at), '-infinity'::TIMESTAMP) AS succeeded_at
+            FROM
+                recent
+            WHERE
+                username = $1 AND succeeded
+        )
+        SELECT
+            COUNT(*) FILTER (WHERE username = $1 AND created_at > last_success.succeeded_at) AS username_failures,
+            MAX(created_at) FILTER (WHERE username = $1 AND created_at > last_success.succeeded_at) AS username_last_failed_at,
+            COUNT(*) FILTER (WHERE ip = $2) AS ip_failures,
+            MAX(created_at) FILTER (WHERE ip = $2) AS ip_last_failed_at
+        FROM
+            recent, last_success
+        WHERE
+            NOT succeeded;
+    "#;
+
+    let row = pc
+        .query_one(stmt, &[&username, &ip, &(window.num_seconds() as f64)])
+        .await?;
+
+    Ok(
+        LoginFailures {
+            username_failures: row.get("username_failures"),
+            username_last_failed_at: row.get("username_last_failed_at"),
+            ip_failures: row.get("ip_failures"),
+            ip_last_failed_at: row.get("ip_last_failed_at"),
+        }
+    )
+}
+
+#[derive(Deserialize, PostgresMapper, Debug, Serialize)]
+#[pg_mapper(table = "account_mfa")]
+pub struct MfaRecord {
+    pub account_id: i64,
+    pub secret: String,
+    pub confirmed_at: Option<NaiveDateTime>,
+    pub last_used_step: Option<i64>,
+    pub created_at: NaiveDateTime,
+}
+
+impl MfaRecord {
+    pub fn is_enabled(&self) -> bool {
+        self.confirmed_at.is_some()
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn select_mfa(pc: &PgClient, account_id: i64) -> Result<Option<MfaRecord>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            account_mfa
+        WHERE
+            account_id = $1;
+    "#;
+
+    let row = pc
+        .query_opt(stmt, &[&account_id])
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(MfaRecord::from_row_ref(&row)?)),
+        None => Ok(None),
+    }
+}
+
+/// Store a pending secret, replacing an unconfirmed one. `None` if a confirmed secret exists already.
+#[instrument(skip_all)]
+pub async fn upsert_pending_mfa(pc: &PgClient, account_id: i64, secret: &str) -> Result<Option<MfaRecord>, ServiceError> {
+    let stmt = r#"
+        INSERT INTO
+            account_mfa (account_id, secret)
+        VALUES
+            ($1, $2)
+        ON CONFLICT (account_id) DO UPDATE
+        SET
+            secret = EXCLUDED.secret,
+            last_used_step = NULL,
+            created_at = NOW()
+        WHERE
+            account_mfa.confirmed_at IS NULL
+        RETURNING *;
+    "#;
+
+    let row = pc
+        .query_opt(stmt, &[&account_id, &secret])
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(MfaRecord::from_row_ref(&row)?)),
+        None => Ok(None),
+    }
+}
+
+/// Accept a TOTP code of time `step`, false if a code of this or a later step was accepted before.
+/// A pending secret gets confirmed by its first code.
+#[instrument(skip_all)]
+pub async fn use_totp_step(pc: &PgClient, account_id: i64, step: i64) -> Result<bool, ServiceError> {
+    let stmt = r#"
+        UPDATE account_mfa
+        SET
+            last_used_step = $2,
+            confirmed_at = COALESCE(confirmed_at, NOW())
+        WHERE
+            account_id = $1 AND (last_used_step IS NULL OR last_used_step < $2);
+    "#;
+
+    let accepted = pc.execute(stmt, &[&account_id, &step]).await?;
+
+    Ok(accepted > 0)
+}
+
+/// Replace every recovery code of the account.
+#[instrument(skip_all)]
+pub async fn replace_recovery_codes(pg_client: &mut PgClient, account_id: i64, code_hashes: &[String]) -> Result<(), ServiceError> {
+    let tx = pg_client.transaction().await?;
+
+    tx.execute("DELETE FROM mfa_recovery_code WHERE account_id = $1;", &[&account_id]).await?;
+
+    let stmt = r#"
+        INSERT INTO
+            mfa_recovery_code (account_id, code_hash)
+        SELECT
+            $1, UNNEST($2::CHAR(64)[]);
+    "#;
+
+    tx.execute(stmt, &[&account_id, &code_hashes]).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[instrument(skip_all)]
+pub async fn use_recovery_code(pc: &PgClient, account_id: i64, code_hash: &str) -> Result<bool, ServiceError> {
+    let stmt = r#"
+        UPDATE mfa_recovery_code
+        SET
+            used_at = NOW()
+        WHERE
+            account_id = $1 AND code_hash = $2 AND used_at IS NULL;
+    "#;
+
+    let used = pc.execute(stmt, &[&account_id, &code_hash]).await?;
+
+    Ok(used > 0)
+}
+
+#[instrument(skip_all)]
+pub async fn delete_mfa(pg_client: &mut PgClient, account_id: i64) -> Result<(), ServiceError> {
+    let tx = pg_client.transaction().await?;
+
+    tx.execute("DELETE FROM mfa_recovery_code WHERE account_id = $1;", &[&account_id]).await?;
+    tx.execute("DELETE FROM account_mfa WHERE account_id = $1;", &[&account_id]).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[instrument(skip_all)]
+pub async fn select_accounts(pc: &PgClient) -> Result<Vec<Account>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            account
+        ORDER BY
+            id;
+    "#;
+
+    let rows = pc
+        .query(stmt, &[])
+        .await?;
+
+    rows.iter()
+        .map(|row| Account::from_row_ref(row).map_err(Into::into))
+        .collect::<Result<Vec<Account>, ServiceError>>()
+}
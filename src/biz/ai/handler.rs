@@ -1,10 +1,14 @@
 use std::collections::HashMap;
-use actix_web::{HttpResponse, post, Error, web};
+use std::time::Instant;
+use actix_web::{HttpResponse, post, Error, web, HttpRequest};
 use log::debug;
 use serde::{Deserialize, Serialize};
+use tracing::{field, info_span, Instrument};
 use crate::AppState;
 use crate::biz::courier::SadCourier;
+use crate::biz::internal::ensure_scope_covered;
 use crate::infra::error::error::ServiceError;
+use crate::infra::metrics::METRICS;
 
 const KIMI_API_URL: &str = "https://api.moonshot.cn/v1/chat/completions";
 
@@ -23,12 +27,24 @@ struct AiReq {
 }
 
 #[post("")]
-pub async fn get_ai_response(app_state: web::Data<AppState>, req_json: web::Json<AiReq>) -> Result<HttpResponse, Error> {
+pub async fn get_ai_response(http_req: HttpRequest, app_state: web::Data<AppState>, req_json: web::Json<AiReq>) -> Result<HttpResponse, Error> {
+    ensure_scope_covered(&http_req)?;
+
     let req = req_json.into_inner();
     debug!("req: {:?}",req);
 
     let client = reqwest::Client::new();
 
+    let started_at = Instant::now();
+
+    let span = info_span!(
+        "ai_request",
+        otel.kind = "client",
+        http.method = "POST",
+        http.url = KIMI_API_URL,
+        http.status_code = field::Empty,
+    );
+
     let kimi_resp = client.post(KIMI_API_URL)
         .header(
             "Authorization",
@@ -36,8 +52,22 @@ pub async fn get_ai_response(app_state: web::Data<AppState>, req_json: web::Json
         )
         .json(&req)
         .send()
-        .await
-        .map_err(|e| Into::<ServiceError>::into(e))?;
+        .instrument(span.clone())
+        .await;
+
+    if let Ok(resp) = &kimi_resp {
+        span.record("http.status_code", resp.status().as_u16());
+    }
+
+    // until the headers arrived, the streamed answer may take much longer
+    let status = kimi_resp
+        .as_ref()
+        .map_or_else(|_| "error".to_string(), |resp| resp.status().as_u16().to_string());
+    METRICS.ai_request_duration
+        .with_label_values(&[status.as_str()])
+        .observe(started_at.elapsed().as_secs_f64());
+
+    let kimi_resp = kimi_resp.map_err(Into::<ServiceError>::into)?;
 
     if kimi_resp.status().is_success() {
         let stream = kimi_resp.bytes_stream();
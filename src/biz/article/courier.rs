@@ -1,9 +1,50 @@
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use crate::infra::error::biz::BizKind::ValidationFailed;
+use crate::infra::error::error::{FieldError, ServiceError};
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::diff::DiffLine;
 // use crate::biz::courier::PaginateQuery;
 
+/// Only published articles whose `publish_at` has come are visible to readers other than the author.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ArticleStatus {
+    Draft,
+    #[default]
+    Published,
+    Archived,
+}
+
+impl ArticleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArticleStatus::Draft => "draft",
+            ArticleStatus::Published => "published",
+            ArticleStatus::Archived => "archived",
+        }
+    }
+}
+
+fn check_title(title: &str) -> Result<(), ServiceError> {
+    if title.trim().is_empty() {
+        return Err(
+            ServiceError::build()
+                .belong(BizError(ValidationFailed))
+                .message("title must not be empty")
+                .fields(vec![FieldError::new("title", "must not be empty")])
+                .done()
+        );
+    }
+    Ok(())
+}
+
 #[derive(Serialize, Debug, Deserialize)]
 pub struct ArticleCourier {
+    // the level3 name of the category, for clients not sending `category_id` yet
+    #[serde(default)]
     pub kind: String,
+    pub category_id: Option<i32>,
     pub tags: Vec<Option<String>>,
     pub is_trending: Option<bool>,
     pub is_insight: Option<bool>,
@@ -13,8 +54,210 @@ pub struct ArticleCourier {
     pub summary: Option<String>,
     pub text: Option<String>,
     pub text_url: Option<String>,
+    // published right away when left out
+    #[serde(default)]
+    pub status: ArticleStatus,
+    // a later time schedules the publication
+    pub publish_at: Option<NaiveDateTime>,
+}
+
+impl ArticleCourier {
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        check_title(&self.title)
+    }
+
+    /// Whether any of the curated flags is requested.
+    pub fn is_curated(&self) -> bool {
+        [self.is_trending, self.is_insight, self.is_recommend].contains(&Some(true))
+    }
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+pub struct ArticleFlagCourier {
+    pub is_trending: Option<bool>,
+    pub is_insight: Option<bool>,
+    pub is_recommend: Option<bool>,
 }
 
-// pub struct ArticleFilter {
-//     pub paginate: PaginateQuery,
-// }
\ No newline at end of file
+/// The content an author replaces with `PUT`, the curated flags are left to curators.
+#[derive(Serialize, Debug, Deserialize)]
+pub struct ArticleContentCourier {
+    #[serde(default)]
+    pub kind: String,
+    pub category_id: Option<i32>,
+    pub tags: Vec<Option<String>>,
+    pub cover_url: Option<String>,
+    pub title: String,
+    pub summary: Option<String>,
+    pub text: Option<String>,
+    pub text_url: Option<String>,
+}
+
+impl ArticleContentCourier {
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        check_title(&self.title)
+    }
+}
+
+/// The fields an author changes with `PATCH`, a `None` keeps the current value.
+#[derive(Serialize, Debug, Deserialize, Default)]
+pub struct ArticlePatchCourier {
+    pub kind: Option<String>,
+    pub category_id: Option<i32>,
+    pub tags: Option<Vec<Option<String>>>,
+    pub cover_url: Option<String>,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub text: Option<String>,
+    pub text_url: Option<String>,
+    pub status: Option<ArticleStatus>,
+    pub publish_at: Option<NaiveDateTime>,
+}
+
+impl ArticlePatchCourier {
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        match &self.title {
+            Some(title) => check_title(title),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Deserialize, Default)]
+pub struct ArticlePublishCourier {
+    // now when left out
+    pub publish_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+pub struct RevisionDiffQuery {
+    pub from: i64,
+    pub to: i64,
+}
+
+/// Line diffs of each field from one revision to another.
+#[derive(Serialize, Debug, Default)]
+pub struct RevisionDiffCourier {
+    pub from: i64,
+    pub to: i64,
+    pub title: Vec<DiffLine>,
+    pub summary: Vec<DiffLine>,
+    pub text: Vec<DiffLine>,
+}
+
+// more tags than this make for a filter nobody means
+const MAX_FILTER_TAGS: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMode {
+    // articles with any of the tags
+    #[default]
+    Any,
+    // articles with every tag
+    All,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ArticleSort {
+    // latest published first, the order readers get without asking
+    #[default]
+    Published,
+    Created,
+    Updated,
+    Title,
+}
+
+impl ArticleSort {
+    pub fn column(&self) -> &'static str {
+        match self {
+            ArticleSort::Published => "publish_at",
+            ArticleSort::Created => "created_at",
+            ArticleSort::Updated => "updated_at",
+            ArticleSort::Title => "title",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+/// Pagination and the filters of the readable articles, every filter left out matches all.
+///
+/// The paging fields are not a flattened `PaginateQuery`, query strings cannot carry
+/// numbers through `#[serde(flatten)]`.
+#[derive(Serialize, Debug, Deserialize, Default)]
+pub struct ArticleFilter {
+    pub page_number: i64,
+    pub page_size: i64,
+    // comma separated
+    pub tags: Option<String>,
+    #[serde(default)]
+    pub tag_mode: TagMode,
+    pub level1: Option<String>,
+    pub level2: Option<String>,
+    pub level3: Option<String>,
+    pub category_id: Option<i32>,
+    pub author_id: Option<i64>,
+    pub is_trending: Option<bool>,
+    pub is_insight: Option<bool>,
+    pub is_recommend: Option<bool>,
+    pub created_from: Option<NaiveDateTime>,
+    pub created_to: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub sort: ArticleSort,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+impl ArticleFilter {
+    pub fn tags(&self) -> Vec<String> {
+        self.tags
+            .iter()
+            .flat_map(|tags| tags.split(','))
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        if self.tags().len() > MAX_FILTER_TAGS {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("Too many tags to filter by")
+                    .fields(vec![FieldError::new("tags", &format!("must be at most {} tags", MAX_FILTER_TAGS))])
+                    .done()
+            );
+        }
+
+        if let (Some(created_from), Some(created_to)) = (self.created_from, self.created_to) {
+            if created_from > created_to {
+                return Err(
+                    ServiceError::build()
+                        .belong(BizError(ValidationFailed))
+                        .message("created_from is after created_to")
+                        .fields(vec![FieldError::new("created_from", "must not be after created_to")])
+                        .done()
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
\ No newline at end of file
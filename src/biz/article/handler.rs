@@ -1,19 +1,72 @@
-use actix_web::{HttpResponse, post, Error, web, HttpRequest, get};
+use actix_web::{HttpResponse, post, put, patch, delete, Error, web, HttpRequest, get};
+use tokio_postgres::Client;
 use crate::AppState;
-use crate::biz::courier::{Courier, HappyCourier, PaginateQuery, SadCourier};
+use crate::biz::courier::{Courier, HappyCourier, SadCourier};
 use super::{courier, recorder};
+use super::courier::ArticleStatus;
+use super::recorder::ArticleRecord;
+use crate::biz::article_category;
+use crate::biz::article_category::recorder::ArticleCategory;
 use crate::biz::internal;
-use crate::biz::internal::{get_pg, MAX_PAGE_SIZE, MIN_PAGE_SIZE};
+use crate::biz::internal::{ensure_page_number, ensure_page_size, ensure_permission, get_pg};
+use crate::infra::error::biz::BizKind::{DataNotFound, PermissionDenied, ValidationFailed};
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::{FieldError, ServiceError};
+use crate::infra::diff::line_diff;
+use crate::infra::middleware::authorize::{Authorize, Permission};
 
-#[post("")]
+/// The article if the caller wrote it, only authors change their articles.
+async fn ensure_author(client: &Client, article_id: i64, user_id: i64) -> Result<ArticleRecord, ServiceError> {
+    let article_record = recorder::select_by_id(client, article_id).await?;
+
+    if article_record.author_id != user_id {
+        return Err(
+            ServiceError::build()
+                .belong(BizError(PermissionDenied))
+                .message("Only the author may change the article")
+                .done()
+        );
+    }
+
+    Ok(article_record)
+}
+
+/// The category to file the article under, by `category_id`, or by `kind` naming a level3
+/// for clients not sending the id yet.
+async fn resolve_category(client: &Client, category_id: Option<i32>, kind: &str) -> Result<ArticleCategory, ServiceError> {
+    let category = match category_id {
+        Some(category_id) => article_category::recorder::select_category_by_id(client, category_id).await?,
+        None => article_category::recorder::select_category(client, kind).await?,
+    };
+
+    category.ok_or_else(|| {
+        ServiceError::build()
+            .belong(BizError(ValidationFailed))
+            .message("The article is not filed under an existing category")
+            .fields(vec![FieldError::new("category_id", "does not name a category")])
+            .done()
+    })
+}
+
+#[post("", wrap = "Authorize::require(Permission::WriteArticle)")]
 pub async fn create_article(req: HttpRequest, app_state: web::Data<AppState>, req_body: web::Json<courier::ArticleCourier>) -> Result<HttpResponse, Error> {
+    let mut article_courier = req_body.into_inner();
+
+    article_courier.validate()?;
+
+    if article_courier.is_curated() {
+        ensure_permission(&req, Permission::CurateArticle)?;
+    }
+
     let user_id = internal::extract_user_id(req)?;
 
-    let article_courier = req_body.into_inner();
+    let mut client = get_pg(&app_state).await?;
 
-    let client = get_pg(&app_state).await?;
+    let category = resolve_category(&client, article_courier.category_id, &article_courier.kind).await?;
+    article_courier.kind = category.level3;
+    article_courier.category_id = Some(category.id);
 
-    let article_record = recorder::insert(&client, article_courier, user_id).await?;
+    let article_record = recorder::insert(&mut client, article_courier, user_id).await?;
 
     Ok(
         HttpResponse::Created().json(
@@ -25,6 +78,24 @@ pub async fn create_article(req: HttpRequest, app_state: web::Data<AppState>, re
     )
 }
 
+#[put("/{article_id:\\d+}/flags", wrap = "Authorize::require(Permission::CurateArticle)")]
+pub async fn curate_article(app_state: web::Data<AppState>, path: web::Path<i64>, req_body: web::Json<courier::ArticleFlagCourier>) -> Result<HttpResponse, Error> {
+    let article_id = path.into_inner();
+
+    let client = get_pg(&app_state).await?;
+
+    let article_record = recorder::update_flags(&client, article_id, req_body.into_inner()).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to curate article")
+                .data(article_record)
+                .done()
+        )
+    )
+}
+
 #[get("/owned")]
 pub async fn read_article_owned(req: HttpRequest, app_state: web::Data<AppState>) -> Result<HttpResponse, Error> {
     let user_id = internal::extract_user_id(req)?;
@@ -44,39 +115,24 @@ pub async fn read_article_owned(req: HttpRequest, app_state: web::Data<AppState>
 }
 
 #[get("/paginated")]
-pub async fn read_article_paginated(app_state: web::Data<AppState>, paginate_query: web::Query<PaginateQuery>) -> Result<HttpResponse, Error> {
+pub async fn read_article_paginated(req: HttpRequest, app_state: web::Data<AppState>, filter_query: web::Query<courier::ArticleFilter>) -> Result<HttpResponse, Error> {
+    internal::ensure_scope_covered(&req)?;
+
     let client = get_pg(&app_state).await?;
 
-    let paginate = paginate_query.into_inner();
-    
+    let filter = filter_query.into_inner();
+
     // params validation
-    if paginate.page_size < MIN_PAGE_SIZE {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page size is too small")
-        ));
-    }
+    filter.validate()?;
 
-    if paginate.page_size > MAX_PAGE_SIZE {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page size is too big")
-        ));
-    }
+    ensure_page_size(filter.page_size)?;
 
-    let total_record = recorder::count(&client).await?;
+    let total_record = recorder::count(&client, &filter).await?;
 
-    if paginate.page_number > (total_record / paginate.page_size + 1) {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page number is too big")
-        ));
-    }
+    ensure_page_number(filter.page_number, filter.page_size, total_record)?;
 
 
-    let article_records = recorder::select_paginated(
-        &client,
-        paginate.page_number,
-        paginate.page_size,
-    )
-        .await?;
+    let article_records = recorder::select_paginated(&client, &filter).await?;
 
     Ok(
         HttpResponse::Ok().json(
@@ -90,3 +146,255 @@ pub async fn read_article_paginated(app_state: web::Data<AppState>, paginate_que
         )
     )
 }
+
+/// A published article, or an unpublished one to its author.
+#[get("/{article_id:\\d+}")]
+pub async fn read_article(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>) -> Result<HttpResponse, Error> {
+    let user_id = internal::extract_user_id(req)?;
+
+    let client = get_pg(&app_state).await?;
+
+    let article_record = recorder::select_by_id(&client, path.into_inner()).await?;
+
+    // others do not learn that an unpublished article exists
+    if !article_record.is_visible() && article_record.author_id != user_id {
+        return Err(
+            ServiceError::build()
+                .belong(BizError(DataNotFound))
+                .message("The article does not exist")
+                .done()
+                .into()
+        );
+    }
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to get article")
+                .data(article_record)
+                .done()
+        )
+    )
+}
+
+#[put("/{article_id:\\d+}", wrap = "Authorize::require(Permission::WriteArticle)")]
+pub async fn update_article(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>, req_body: web::Json<courier::ArticleContentCourier>) -> Result<HttpResponse, Error> {
+    let mut content = req_body.into_inner();
+
+    content.validate()?;
+
+    let user_id = internal::extract_user_id(req)?;
+    let article_id = path.into_inner();
+
+    let mut client = get_pg(&app_state).await?;
+
+    ensure_author(&client, article_id, user_id).await?;
+
+    let category = resolve_category(&client, content.category_id, &content.kind).await?;
+    content.kind = category.level3;
+    content.category_id = Some(category.id);
+
+    let article_record = recorder::update_content(&mut client, article_id, user_id, &content).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to update article")
+                .data(article_record)
+                .done()
+        )
+    )
+}
+
+#[patch("/{article_id:\\d+}", wrap = "Authorize::require(Permission::WriteArticle)")]
+pub async fn patch_article(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>, req_body: web::Json<courier::ArticlePatchCourier>) -> Result<HttpResponse, Error> {
+    let mut patch = req_body.into_inner();
+
+    patch.validate()?;
+
+    let user_id = internal::extract_user_id(req)?;
+    let article_id = path.into_inner();
+
+    let mut client = get_pg(&app_state).await?;
+
+    ensure_author(&client, article_id, user_id).await?;
+
+    if patch.category_id.is_some() || patch.kind.is_some() {
+        let category = resolve_category(&client, patch.category_id, patch.kind.as_deref().unwrap_or_default()).await?;
+        patch.kind = Some(category.level3);
+        patch.category_id = Some(category.id);
+    }
+
+    let article_record = recorder::update_partially(&mut client, article_id, user_id, &patch).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to update article")
+                .data(article_record)
+                .done()
+        )
+    )
+}
+
+/// Soft delete, only the author may.
+#[delete("/{article_id:\\d+}", wrap = "Authorize::require(Permission::WriteArticle)")]
+pub async fn delete_article(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>) -> Result<HttpResponse, Error> {
+    let user_id = internal::extract_user_id(req)?;
+    let article_id = path.into_inner();
+
+    let client = get_pg(&app_state).await?;
+
+    ensure_author(&client, article_id, user_id).await?;
+
+    recorder::delete(&client, article_id, user_id).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            SadCourier::brief("Success to delete article")
+        )
+    )
+}
+
+/// Publish now, or at `publish_at` when it is later.
+#[post("/{article_id:\\d+}/publish", wrap = "Authorize::require(Permission::WriteArticle)")]
+pub async fn publish_article(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>, req_body: Option<web::Json<courier::ArticlePublishCourier>>) -> Result<HttpResponse, Error> {
+    let publish_at = req_body.and_then(|body| body.into_inner().publish_at);
+
+    let user_id = internal::extract_user_id(req)?;
+    let article_id = path.into_inner();
+
+    let client = get_pg(&app_state).await?;
+
+    ensure_author(&client, article_id, user_id).await?;
+
+    let article_record = recorder::update_status(&client, article_id, user_id, ArticleStatus::Published, publish_at).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to publish article")
+                .data(article_record)
+                .done()
+        )
+    )
+}
+
+/// Back to draft, hidden from readers until published again.
+#[post("/{article_id:\\d+}/unpublish", wrap = "Authorize::require(Permission::WriteArticle)")]
+pub async fn unpublish_article(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>) -> Result<HttpResponse, Error> {
+    let user_id = internal::extract_user_id(req)?;
+    let article_id = path.into_inner();
+
+    let client = get_pg(&app_state).await?;
+
+    ensure_author(&client, article_id, user_id).await?;
+
+    let article_record = recorder::update_status(&client, article_id, user_id, ArticleStatus::Draft, None).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to unpublish article")
+                .data(article_record)
+                .done()
+        )
+    )
+}
+
+/// Revisions of the article, the latest first, only to its author.
+#[get("/{article_id:\\d+}/revision")]
+pub async fn read_revisions(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>) -> Result<HttpResponse, Error> {
+    let user_id = internal::extract_user_id(req)?;
+    let article_id = path.into_inner();
+
+    let client = get_pg(&app_state).await?;
+
+    ensure_author(&client, article_id, user_id).await?;
+
+    let revisions = recorder::select_revisions(&client, article_id).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to get revisions")
+                .data(revisions)
+                .done()
+        )
+    )
+}
+
+#[get("/{article_id:\\d+}/revision/{revision_id:\\d+}")]
+pub async fn read_revision(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<(i64, i64)>) -> Result<HttpResponse, Error> {
+    let user_id = internal::extract_user_id(req)?;
+    let (article_id, revision_id) = path.into_inner();
+
+    let client = get_pg(&app_state).await?;
+
+    ensure_author(&client, article_id, user_id).await?;
+
+    let revision_record = recorder::select_revision(&client, article_id, revision_id).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to get revision")
+                .data(revision_record)
+                .done()
+        )
+    )
+}
+
+/// Line diff from the revision `from` to the revision `to`.
+#[get("/{article_id:\\d+}/revision/diff")]
+pub async fn diff_revisions(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>, query: web::Query<courier::RevisionDiffQuery>) -> Result<HttpResponse, Error> {
+    let user_id = internal::extract_user_id(req)?;
+    let article_id = path.into_inner();
+    let query = query.into_inner();
+
+    let client = get_pg(&app_state).await?;
+
+    ensure_author(&client, article_id, user_id).await?;
+
+    let from = recorder::select_revision(&client, article_id, query.from).await?;
+    let to = recorder::select_revision(&client, article_id, query.to).await?;
+
+    let diff = courier::RevisionDiffCourier {
+        from: from.id,
+        to: to.id,
+        title: line_diff(&from.title, &to.title),
+        summary: line_diff(from.summary.as_deref().unwrap_or_default(), to.summary.as_deref().unwrap_or_default()),
+        text: line_diff(from.text.as_deref().unwrap_or_default(), to.text.as_deref().unwrap_or_default()),
+    };
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to diff revisions")
+                .data(diff)
+                .done()
+        )
+    )
+}
+
+/// Bring back the title, summary and text of an old revision.
+#[post("/{article_id:\\d+}/revision/{revision_id:\\d+}/restore", wrap = "Authorize::require(Permission::WriteArticle)")]
+pub async fn restore_revision(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<(i64, i64)>) -> Result<HttpResponse, Error> {
+    let user_id = internal::extract_user_id(req)?;
+    let (article_id, revision_id) = path.into_inner();
+
+    let mut client = get_pg(&app_state).await?;
+
+    ensure_author(&client, article_id, user_id).await?;
+
+    let article_record = recorder::restore_revision(&mut client, article_id, revision_id, user_id).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to restore revision")
+                .data(article_record)
+                .done()
+        )
+    )
+}
@@ -1,9 +1,11 @@
-use chrono::{NaiveDateTime};
+use chrono::{NaiveDateTime, Utc};
 use log::debug;
 use serde::{Deserialize, Serialize};
 use tokio_pg_mapper::FromTokioPostgresRow;
 use tokio_pg_mapper_derive::PostgresMapper;
-use tokio_postgres::Client;
+use tokio_postgres::{Client, Transaction};
+use tokio_postgres::types::ToSql;
+use tracing::instrument;
 use crate::biz::article::courier;
 use crate::infra::error::biz::BizKind::DataNotFound;
 use crate::infra::error::error::Kind::BizError;
@@ -26,10 +28,36 @@ pub struct ArticleRecord {
     pub author_id: i64,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub status: String, // draft published archived
+    pub publish_at: Option<NaiveDateTime>,
+    pub category_id: Option<i32>,
 }
 
+#[derive(Deserialize, PostgresMapper, Debug, Serialize, Default)]
+#[pg_mapper(table = "ArticleRevision")]
+pub struct RevisionRecord {
+    pub id: i64,
+    pub article_id: i64,
+    pub title: String,
+    pub summary: Option<String>,
+    pub text: Option<String>,
+    pub editor_id: i64,
+    pub created_at: NaiveDateTime,
+}
+
+impl ArticleRecord {
+    /// Published and due, so every reader may see it.
+    pub fn is_visible(&self) -> bool {
+        self.status == courier::ArticleStatus::Published.as_str()
+            && self.publish_at.is_some_and(|publish_at| publish_at <= Utc::now().naive_utc())
+    }
+}
+
+
+#[instrument(skip_all)]
+pub(crate) async fn insert(client: &mut Client, article_courier: courier::ArticleCourier, author_id: i64) -> Result<ArticleRecord, ServiceError> {
+    let tx = client.transaction().await?;
 
-pub(crate) async fn insert(client: &Client, article_courier: courier::ArticleCourier, author_id: i64) -> Result<ArticleRecord, ServiceError> {
     let stmt = r#"
         INSERT INTO
             article (
@@ -43,14 +71,22 @@ pub(crate) async fn insert(client: &Client, article_courier: courier::ArticleCou
                 summary,
                 text,
                 text_url,
-                author_id
+                author_id,
+                status,
+                publish_at,
+                category_id
             )
         VALUES
-            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12,
+                -- publishing without a time means now
+                CASE WHEN $12 = 'published' THEN COALESCE($13, NOW() AT TIME ZONE 'UTC') ELSE $13 END,
+                $14
+            )
         RETURNING *;
     "#;
 
-    let row = client
+    let row = tx
         .query_one(
             stmt,
             &[
@@ -64,16 +100,63 @@ pub(crate) async fn insert(client: &Client, article_courier: courier::ArticleCou
                 &article_courier.summary,
                 &article_courier.text,
                 &article_courier.text_url,
-                &author_id
+                &author_id,
+                &article_courier.status.as_str(),
+                &article_courier.publish_at,
+                &article_courier.category_id,
             ],
         )
         .await?;
 
     let article_record = ArticleRecord::from_row_ref(&row)?;
 
+    record_revision(&tx, article_record.id, author_id).await?;
+
+    tx.commit().await?;
+
     Ok(article_record)
 }
 
+/// Update the curated flags, a `None` keeps the current value.
+#[instrument(skip_all)]
+pub(crate) async fn update_flags(client: &Client, article_id: i64, flags: courier::ArticleFlagCourier) -> Result<ArticleRecord, ServiceError> {
+    let stmt = r#"
+        UPDATE article
+        SET
+            is_trending = COALESCE($2, is_trending),
+            is_insight = COALESCE($3, is_insight),
+            is_recommend = COALESCE($4, is_recommend),
+            updated_at = (NOW() AT TIME ZONE 'UTC')
+        WHERE
+            id = $1
+            AND deleted_at IS NULL
+        RETURNING *;
+    "#;
+
+    let row = client
+        .query_opt(
+            stmt,
+            &[
+                &article_id,
+                &flags.is_trending,
+                &flags.is_insight,
+                &flags.is_recommend,
+            ],
+        )
+        .await?
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(BizError(DataNotFound))
+                .message("The article does not exist")
+                .done()
+        })?;
+
+    let article_record = ArticleRecord::from_row_ref(&row)?;
+
+    Ok(article_record)
+}
+
+#[instrument(skip_all)]
 pub async fn select_by_author_id(client: &Client, user_id: i64) -> Result<Vec<ArticleRecord>, ServiceError> {
     let stmt = r#"
         SELECT
@@ -82,6 +165,7 @@ pub async fn select_by_author_id(client: &Client, user_id: i64) -> Result<Vec<Ar
             article
         WHERE
             author_id = $1
+            AND deleted_at IS NULL
         ORDER BY
             created_at DESC
     "#;
@@ -109,14 +193,19 @@ pub async fn select_by_author_id(client: &Client, user_id: i64) -> Result<Vec<Ar
     };
 }
 
+#[instrument(skip_all)]
 pub async fn select_all(client: &Client) -> Result<Vec<ArticleRecord>, ServiceError> {
     let stmt = r#"
         SELECT
             *
         FROM
             article
+        WHERE
+            status = 'published'
+            AND publish_at <= (NOW() AT TIME ZONE 'UTC')
+            AND deleted_at IS NULL
         ORDER BY
-            created_at DESC
+            publish_at DESC
     "#;
 
 
@@ -144,27 +233,122 @@ pub async fn select_all(client: &Client) -> Result<Vec<ArticleRecord>, ServiceEr
 }
 
 
-pub(crate) async fn select_paginated(client: &Client, page_number: i64, page_size: i64) -> Result<Vec<ArticleRecord>, ServiceError> {
-    debug!("page number: {}, page size: {}",page_number, page_size);
+/// Conditions joined by `AND`, with the parameters they are numbered by.
+struct Conditions<'a> {
+    clauses: Vec<String>,
+    params: Vec<&'a (dyn ToSql + Sync)>,
+}
 
-    let stmt = r#"
-        SELECT
-            *
-        FROM
-            article
-        ORDER BY
-            created_at DESC
-        LIMIT
-            $1
-        OFFSET
-            $2;
-    "#;
+impl<'a> Conditions<'a> {
+    /// The placeholder of `param` as the next parameter.
+    fn param(&mut self, param: &'a (dyn ToSql + Sync)) -> String {
+        self.params.push(param);
+        format!("${}", self.params.len())
+    }
+
+    fn clause(&self) -> String {
+        self.clauses.join(" AND ")
+    }
+}
 
+/// The conditions of the readable articles matching `filter`. `tags` are those of the
+/// filter, split already.
+fn filter_conditions<'a>(filter: &'a courier::ArticleFilter, tags: &'a Vec<String>) -> Conditions<'a> {
+    let mut conditions = Conditions {
+        clauses: vec![
+            "status = 'published'".to_string(),
+            "publish_at <= (NOW() AT TIME ZONE 'UTC')".to_string(),
+            "deleted_at IS NULL".to_string(),
+        ],
+        params: Vec::new(),
+    };
+
+    if !tags.is_empty() {
+        let operator = match filter.tag_mode {
+            courier::TagMode::Any => "&&",
+            courier::TagMode::All => "@>",
+        };
+        let placeholder = conditions.param(tags);
+        conditions.clauses.push(format!("tags {} {}", operator, placeholder));
+    }
 
-    let offset = page_number * page_size;
+    if let Some(category_id) = &filter.category_id {
+        let placeholder = conditions.param(category_id);
+        conditions.clauses.push(format!("category_id = {}", placeholder));
+    }
+
+    let mut category = Vec::new();
+    for (column, level) in [("level1", &filter.level1), ("level2", &filter.level2), ("level3", &filter.level3)] {
+        if let Some(level) = level {
+            category.push(format!("{} = {}", column, conditions.param(level)));
+        }
+    }
+    if !category.is_empty() {
+        conditions.clauses.push(format!("category_id IN (SELECT id FROM article_category WHERE {})", category.join(" AND ")));
+    }
+
+    if let Some(author_id) = &filter.author_id {
+        let placeholder = conditions.param(author_id);
+        conditions.clauses.push(format!("author_id = {}", placeholder));
+    }
+
+    for (column, flag) in [("is_trending", &filter.is_trending), ("is_insight", &filter.is_insight), ("is_recommend", &filter.is_recommend)] {
+        if let Some(flag) = flag {
+            let placeholder = conditions.param(flag);
+            conditions.clauses.push(format!("{} = {}", column, placeholder));
+        }
+    }
+
+    if let Some(created_from) = &filter.created_from {
+        let placeholder = conditions.param(created_from);
+        conditions.clauses.push(format!("created_at >= {}", placeholder));
+    }
+
+    if let Some(created_to) = &filter.created_to {
+        let placeholder = conditions.param(created_to);
+        conditions.clauses.push(format!("created_at <= {}", placeholder));
+    }
+
+    conditions
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn select_paginated(client: &Client, filter: &courier::ArticleFilter) -> Result<Vec<ArticleRecord>, ServiceError> {
+    debug!("page number: {}, page size: {}", filter.page_number, filter.page_size);
+
+    let tags = filter.tags();
+    let offset = filter.page_number * filter.page_size;
+
+    let mut conditions = filter_conditions(filter, &tags);
+    let limit_placeholder = conditions.param(&filter.page_size);
+    let offset_placeholder = conditions.param(&offset);
+
+    // the sort column and order come from enums, never from the query string itself
+    let stmt = format!(
+        r#"
+            SELECT
+                *
+            FROM
+                article
+            WHERE
+                {}
+            ORDER BY
+                {} {}, id {}
+            LIMIT
+                {}
+            OFFSET
+                {};
+        "#,
+        conditions.clause(),
+        filter.sort.column(),
+        filter.order.keyword(),
+        filter.order.keyword(),
+        limit_placeholder,
+        offset_placeholder,
+    );
 
     let rows = client
-        .query(stmt, &[&page_size, &offset])
+        .query(&stmt, &conditions.params)
         .await?;
 
     return if rows.is_empty() {
@@ -185,13 +369,376 @@ pub(crate) async fn select_paginated(client: &Client, page_number: i64, page_siz
     };
 }
 
-pub(crate) async fn count(client: &Client) -> Result<i64, ServiceError> {
-    let stmt = r#"SELECT COUNT(*) FROM article"#;
+/// Count the articles `select_paginated` pages through with the same filter.
+#[instrument(skip_all)]
+pub(crate) async fn count(client: &Client, filter: &courier::ArticleFilter) -> Result<i64, ServiceError> {
+    let tags = filter.tags();
+    let conditions = filter_conditions(filter, &tags);
+
+    let stmt = format!(
+        r#"
+            SELECT
+                COUNT(*)
+            FROM
+                article
+            WHERE
+                {};
+        "#,
+        conditions.clause(),
+    );
 
-    let count = client.query_one(stmt, &[])
+    let count = client.query_one(&stmt, &conditions.params)
         .await?
         .get(0);
 
     Ok(count)
 }
 
+fn article_not_found() -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(DataNotFound))
+        .message("The article does not exist")
+        .done()
+}
+
+/// The article unless deleted, whatever its status. Callers decide who may see it.
+#[instrument(skip_all)]
+pub(crate) async fn select_by_id(client: &Client, article_id: i64) -> Result<ArticleRecord, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            article
+        WHERE
+            id = $1
+            AND deleted_at IS NULL;
+    "#;
+
+    let row = client
+        .query_opt(stmt, &[&article_id])
+        .await?
+        .ok_or_else(article_not_found)?;
+
+    let article_record = ArticleRecord::from_row_ref(&row)?;
+
+    Ok(article_record)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn update_content(client: &mut Client, article_id: i64, author_id: i64, content: &courier::ArticleContentCourier) -> Result<ArticleRecord, ServiceError> {
+    let tx = client.transaction().await?;
+
+    let stmt = r#"
+        UPDATE article
+        SET
+            kind = $2,
+            tags = $3,
+            cover_url = $4,
+            title = $5,
+            summary = $6,
+            text = $7,
+            text_url = $8,
+            category_id = $9,
+            updated_at = (NOW() AT TIME ZONE 'UTC')
+        WHERE
+            id = $1
+            AND author_id = $10
+            AND deleted_at IS NULL
+        RETURNING *;
+    "#;
+
+    let row = tx
+        .query_opt(
+            stmt,
+            &[
+                &article_id,
+                &content.kind,
+                &content.tags,
+                &content.cover_url,
+                &content.title,
+                &content.summary,
+                &content.text,
+                &content.text_url,
+                &content.category_id,
+                &author_id,
+            ],
+        )
+        .await?
+        .ok_or_else(article_not_found)?;
+
+    let article_record = ArticleRecord::from_row_ref(&row)?;
+
+    record_revision(&tx, article_id, author_id).await?;
+
+    tx.commit().await?;
+
+    Ok(article_record)
+}
+
+/// Change the fields given, a `None` keeps the current value.
+#[instrument(skip_all)]
+pub(crate) async fn update_partially(client: &mut Client, article_id: i64, author_id: i64, patch: &courier::ArticlePatchCourier) -> Result<ArticleRecord, ServiceError> {
+    let tx = client.transaction().await?;
+
+    let stmt = r#"
+        UPDATE article
+        SET
+            kind = COALESCE($2, kind),
+            tags = COALESCE($3, tags),
+            cover_url = COALESCE($4, cover_url),
+            title = COALESCE($5, title),
+            summary = COALESCE($6, summary),
+            text = COALESCE($7, text),
+            text_url = COALESCE($8, text_url),
+            status = COALESCE($9, status),
+            publish_at = CASE
+                WHEN $9 = 'published' THEN COALESCE($10, publish_at, NOW() AT TIME ZONE 'UTC')
+                ELSE COALESCE($10, publish_at)
+            END,
+            category_id = COALESCE($11, category_id),
+            updated_at = (NOW() AT TIME ZONE 'UTC')
+        WHERE
+            id = $1
+            AND author_id = $12
+            AND deleted_at IS NULL
+        RETURNING *;
+    "#;
+
+    let row = tx
+        .query_opt(
+            stmt,
+            &[
+                &article_id,
+                &patch.kind,
+                &patch.tags,
+                &patch.cover_url,
+                &patch.title,
+                &patch.summary,
+                &patch.text,
+                &patch.text_url,
+                &patch.status.map(|status| status.as_str()),
+                &patch.publish_at,
+                &patch.category_id,
+                &author_id,
+            ],
+        )
+        .await?
+        .ok_or_else(article_not_found)?;
+
+    let article_record = ArticleRecord::from_row_ref(&row)?;
+
+    record_revision(&tx, article_id, author_id).await?;
+
+    tx.commit().await?;
+
+    Ok(article_record)
+}
+
+/// Set the status, publishing without a time means now, keeping the time when it is left out otherwise.
+#[instrument(skip_all)]
+pub(crate) async fn update_status(client: &Client, article_id: i64, author_id: i64, status: courier::ArticleStatus, publish_at: Option<NaiveDateTime>) -> Result<ArticleRecord, ServiceError> {
+    let stmt = r#"
+        UPDATE article
+        SET
+            status = $2,
+            publish_at = CASE
+                WHEN $2 = 'published' THEN COALESCE($3, NOW() AT TIME ZONE 'UTC')
+                ELSE COALESCE($3, publish_at)
+            END,
+            updated_at = (NOW() AT TIME ZONE 'UTC')
+        WHERE
+            id = $1
+            AND author_id = $4
+            AND deleted_at IS NULL
+        RETURNING *;
+    "#;
+
+    let row = client
+        .query_opt(stmt, &[&article_id, &status.as_str(), &publish_at, &author_id])
+        .await?
+        .ok_or_else(article_not_found)?;
+
+    let article_record = ArticleRecord::from_row_ref(&row)?;
+
+    Ok(article_record)
+}
+
+/// Soft delete, the row stays for the record but is gone for every query.
+#[instrument(skip_all)]
+pub(crate) async fn delete(client: &Client, article_id: i64, author_id: i64) -> Result<(), ServiceError> {
+    let stmt = r#"
+        UPDATE article
+        SET
+            deleted_at = (NOW() AT TIME ZONE 'UTC')
+        WHERE
+            id = $1
+            AND author_id = $2
+            AND deleted_at IS NULL;
+    "#;
+
+    let deleted = client
+        .execute(stmt, &[&article_id, &author_id])
+        .await?;
+
+    if deleted == 0 {
+        return Err(article_not_found());
+    }
+
+    Ok(())
+}
+
+/// Keep the title, summary and text of the article as a revision, unless they are
+/// what the latest revision holds already.
+async fn record_revision(tx: &Transaction<'_>, article_id: i64, editor_id: i64) -> Result<(), ServiceError> {
+    let stmt = r#"
+        INSERT INTO
+            article_revision (article_id, title, summary, text, editor_id)
+        SELECT
+            a.id, a.title, a.summary, a.text, $2
+        FROM
+            article a
+        WHERE
+            a.id = $1
+            AND NOT EXISTS (
+                SELECT
+                    1
+                FROM
+                    (
+                        SELECT title, summary, text
+                        FROM article_revision
+                        WHERE article_id = $1
+                        ORDER BY id DESC
+                        LIMIT 1
+                    ) latest
+                WHERE
+                    latest.title = a.title
+                    AND latest.summary IS NOT DISTINCT FROM a.summary
+                    AND latest.text IS NOT DISTINCT FROM a.text
+            );
+    "#;
+
+    tx.execute(stmt, &[&article_id, &editor_id]).await?;
+
+    Ok(())
+}
+
+fn revision_not_found() -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(DataNotFound))
+        .message("The revision does not exist")
+        .done()
+}
+
+/// Revisions of the article, the latest first.
+#[instrument(skip_all)]
+pub(crate) async fn select_revisions(client: &Client, article_id: i64) -> Result<Vec<RevisionRecord>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            article_revision
+        WHERE
+            article_id = $1
+        ORDER BY
+            id DESC;
+    "#;
+
+    let rows = client
+        .query(stmt, &[&article_id])
+        .await?;
+
+    let mut revisions = Vec::new();
+
+    for row in rows {
+        let revision_record = RevisionRecord::from_row_ref(&row)?;
+        revisions.push(revision_record)
+    }
+
+    Ok(revisions)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn select_revision(client: &Client, article_id: i64, revision_id: i64) -> Result<RevisionRecord, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            article_revision
+        WHERE
+            id = $1
+            AND article_id = $2;
+    "#;
+
+    let row = client
+        .query_opt(stmt, &[&revision_id, &article_id])
+        .await?
+        .ok_or_else(revision_not_found)?;
+
+    let revision_record = RevisionRecord::from_row_ref(&row)?;
+
+    Ok(revision_record)
+}
+
+/// Make an old revision the current version, which is kept as a revision of its own.
+#[instrument(skip_all)]
+pub(crate) async fn restore_revision(client: &mut Client, article_id: i64, revision_id: i64, author_id: i64) -> Result<ArticleRecord, ServiceError> {
+    let tx = client.transaction().await?;
+
+    let stmt = r#"
+        UPDATE article a
+        SET
+            title = r.title,
+            summary = r.summary,
+            text = r.text,
+            updated_at = (NOW() AT TIME ZONE 'UTC')
+        FROM
+            article_revision r
+        WHERE
+            a.id = $1
+            AND a.author_id = $3
+            AND a.deleted_at IS NULL
+            AND r.id = $2
+            AND r.article_id = a.id
+        RETURNING a.*;
+    "#;
+
+    let row = tx
+        .query_opt(stmt, &[&article_id, &revision_id, &author_id])
+        .await?
+        .ok_or_else(revision_not_found)?;
+
+    let article_record = ArticleRecord::from_row_ref(&row)?;
+
+    record_revision(&tx, article_id, author_id).await?;
+
+    tx.commit().await?;
+
+    Ok(article_record)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biz::article::courier::{ArticleFilter, TagMode};
+    use super::filter_conditions;
+
+    #[test]
+    fn filter_conditions_are_numbered_in_order() {
+        let filter = ArticleFilter {
+            tags: Some("rust, sleep,".to_string()),
+            tag_mode: TagMode::All,
+            level1: Some("Coding".to_string()),
+            level3: Some("Rust".to_string()),
+            is_insight: Some(true),
+            ..Default::default()
+        };
+        let tags = filter.tags();
+        assert_eq!(tags, vec!["rust", "sleep"]);
+
+        let conditions = filter_conditions(&filter, &tags);
+
+        assert_eq!(conditions.params.len(), 4);
+        assert!(conditions.clause().ends_with(
+            "tags @> $1 AND category_id IN (SELECT id FROM article_category WHERE level1 = $2 AND level3 = $3) AND is_insight = $4"
+        ), "{}", conditions.clause());
+    }
+}
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use crate::biz::article_category::recorder::CategoryCountRecord;
+use crate::infra::error::biz::BizKind::ValidationFailed;
+use crate::infra::error::error::{FieldError, ServiceError};
+use crate::infra::error::error::Kind::BizError;
+
+#[derive(Serialize, Debug, Deserialize)]
+pub struct CategoryCourier {
+    pub level1: String,
+    pub level2: String,
+    pub level3: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl CategoryCourier {
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        let fields: Vec<FieldError> = [("level1", &self.level1), ("level2", &self.level2), ("level3", &self.level3)]
+            .into_iter()
+            .filter(|(_, level)| level.trim().is_empty())
+            .map(|(field, _)| FieldError::new(field, "must not be empty"))
+            .collect();
+
+        if !fields.is_empty() {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("Every level of the category must be named")
+                    .fields(fields)
+                    .done()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// A level3 category, the one articles are filed under.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CategoryLeaf {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub article_count: i64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CategoryBranch<C> {
+    pub name: String,
+    // the articles of every category below
+    pub article_count: i64,
+    pub children: Vec<C>,
+}
+
+pub type CategoryTree = Vec<CategoryBranch<CategoryBranch<CategoryLeaf>>>;
+
+/// Nest the categories level1 → level2 → level3, `records` sorted by their levels.
+pub fn build_tree(records: Vec<CategoryCountRecord>) -> CategoryTree {
+    let mut tree: CategoryTree = Vec::new();
+
+    for record in records {
+        let leaf = CategoryLeaf {
+            id: record.id,
+            name: record.level3,
+            description: record.description,
+            article_count: record.article_count,
+        };
+
+        if !matches!(tree.last(), Some(level1) if level1.name == record.level1) {
+            tree.push(CategoryBranch { name: record.level1, article_count: 0, children: Vec::new() });
+        }
+        let level1 = tree.last_mut().unwrap();
+        level1.article_count += leaf.article_count;
+
+        if !matches!(level1.children.last(), Some(level2) if level2.name == record.level2) {
+            level1.children.push(CategoryBranch { name: record.level2, article_count: 0, children: Vec::new() });
+        }
+        let level2 = level1.children.last_mut().unwrap();
+        level2.article_count += leaf.article_count;
+        level2.children.push(leaf);
+    }
+
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biz::article_category::recorder::CategoryCountRecord;
+    use super::build_tree;
+
+    fn record(id: i32, level1: &str, level2: &str, level3: &str, article_count: i64) -> CategoryCountRecord {
+        CategoryCountRecord {
+            id,
+            level1: level1.to_string(),
+            level2: level2.to_string(),
+            level3: level3.to_string(),
+            description: String::new(),
+            article_count,
+        }
+    }
+
+    #[test]
+    fn tree_sums_article_counts_up_the_levels() {
+        let tree = build_tree(vec![
+            record(1, "Coding", "Database", "MySQL", 2),
+            record(2, "Coding", "Database", "SQLite", 0),
+            record(3, "Coding", "Languages", "Rust", 5),
+            record(4, "Parenting", "Sleep", "Naps", 1),
+        ]);
+
+        assert_eq!(tree.len(), 2);
+        assert_eq!((tree[0].name.as_str(), tree[0].article_count), ("Coding", 7));
+        assert_eq!(tree[0].children.len(), 2);
+        assert_eq!((tree[0].children[0].name.as_str(), tree[0].children[0].article_count), ("Database", 2));
+        assert_eq!(tree[0].children[0].children.len(), 2);
+        assert_eq!(tree[1].children[0].children[0].id, 4);
+    }
+}
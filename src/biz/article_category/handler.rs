@@ -1,11 +1,16 @@
-use actix_web::{HttpResponse, Error, web, get};
+use actix_web::{HttpResponse, Error, web, get, post, put, delete, HttpRequest};
 use crate::AppState;
+use crate::biz::article_category::courier::{build_tree, CategoryCourier};
+use crate::biz::article_category::recorder;
 use crate::biz::article_category::recorder::{select_all_category, select_distinct_level};
-use crate::biz::courier::{Courier};
-use crate::biz::internal::get_pg;
+use crate::biz::courier::{Courier, HappyCourier, SadCourier};
+use crate::biz::internal::{ensure_scope_covered, get_pg};
+use crate::infra::middleware::authorize::{Authorize, Permission};
 
 #[get("")]
-pub async fn read_all_category(app_state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+pub async fn read_all_category(req: HttpRequest, app_state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    ensure_scope_covered(&req)?;
+
     let client = get_pg(&app_state).await?;
 
     let category_records = select_all_category(&client).await?;
@@ -22,3 +27,76 @@ pub async fn read_all_category(app_state: web::Data<AppState>) -> Result<HttpRes
         )
     )
 }
+
+/// Categories nested level1 → level2 → level3, with the visible articles of each.
+#[get("/tree")]
+pub async fn read_category_tree(req: HttpRequest, app_state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    ensure_scope_covered(&req)?;
+
+    let client = get_pg(&app_state).await?;
+
+    let category_counts = recorder::select_category_counts(&client).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to query category tree")
+                .data(build_tree(category_counts))
+                .done()
+        )
+    )
+}
+
+#[post("", wrap = "Authorize::require(Permission::EditCategory)")]
+pub async fn create_category(app_state: web::Data<AppState>, req_body: web::Json<CategoryCourier>) -> Result<HttpResponse, Error> {
+    let category_courier = req_body.into_inner();
+
+    category_courier.validate()?;
+
+    let client = get_pg(&app_state).await?;
+
+    let category_record = recorder::insert(&client, &category_courier).await?;
+
+    Ok(
+        HttpResponse::Created().json(
+            HappyCourier::build()
+                .message("Success to create category")
+                .data(category_record)
+                .done()
+        )
+    )
+}
+
+#[put("/{category_id:\\d+}", wrap = "Authorize::require(Permission::EditCategory)")]
+pub async fn update_category(app_state: web::Data<AppState>, path: web::Path<i32>, req_body: web::Json<CategoryCourier>) -> Result<HttpResponse, Error> {
+    let category_courier = req_body.into_inner();
+
+    category_courier.validate()?;
+
+    let mut client = get_pg(&app_state).await?;
+
+    let category_record = recorder::update(&mut client, path.into_inner(), &category_courier).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to update category")
+                .data(category_record)
+                .done()
+        )
+    )
+}
+
+/// Only a category no article is filed under may go.
+#[delete("/{category_id:\\d+}", wrap = "Authorize::require(Permission::EditCategory)")]
+pub async fn delete_category(app_state: web::Data<AppState>, path: web::Path<i32>) -> Result<HttpResponse, Error> {
+    let client = get_pg(&app_state).await?;
+
+    recorder::delete(&client, path.into_inner()).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            SadCourier::brief("Success to delete category")
+        )
+    )
+}
@@ -1,2 +1,3 @@
 pub mod recorder;
-pub mod handler;
\ No newline at end of file
+pub mod handler;
+pub mod courier;
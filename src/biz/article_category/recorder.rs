@@ -3,7 +3,12 @@ use serde::{Deserialize, Serialize};
 use tokio_pg_mapper::FromTokioPostgresRow;
 use tokio_pg_mapper_derive::PostgresMapper;
 use tokio_postgres::Client;
-use crate::infra::error::error::ServiceError;
+use tokio_postgres::error::SqlState;
+use tracing::instrument;
+use crate::biz::article_category::courier::CategoryCourier;
+use crate::infra::error::biz::BizKind::{DataConflict, DataNotFound, ValidationFailed};
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::{FieldError, ServiceError};
 
 #[derive(Deserialize, PostgresMapper, Debug, Serialize, Default)]
 #[pg_mapper(table = "ArticleCategory")]
@@ -15,26 +20,79 @@ pub struct ArticleCategory {
     pub description: String,
 }
 
-pub async fn select_category(client: &Client, level3: String) -> Result<ArticleCategory, ServiceError> {
+/// A category with the visible articles filed under it.
+#[derive(Deserialize, PostgresMapper, Debug, Serialize)]
+#[pg_mapper(table = "ArticleCategory")]
+pub struct CategoryCountRecord {
+    pub id: i32,
+    pub level1: String,
+    pub level2: String,
+    pub level3: String,
+    pub description: String,
+    pub article_count: i64,
+}
+
+fn category_not_found() -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(DataNotFound))
+        .message("The category does not exist")
+        .done()
+}
+
+/// The category named `level3`, which must be unique among all the categories to tell it by name.
+#[instrument(skip_all)]
+pub async fn select_category(client: &Client, level3: &str) -> Result<Option<ArticleCategory>, ServiceError> {
     let stmt = r#"
         SELECT
             *
         FROM
             article_category
         WHERE
-            level3 = $1;
+            level3 = $1
+        LIMIT 2;
     "#;
 
 
+    let rows = client
+        .query(stmt, &[&level3])
+        .await?;
+
+    if rows.len() > 1 {
+        return Err(
+            ServiceError::build()
+                .belong(BizError(ValidationFailed))
+                .message("Several categories share the name, the article must be filed by category_id")
+                .fields(vec![FieldError::new("kind", "names more than one category")])
+                .done()
+        );
+    }
+
+    let category = rows.first().map(ArticleCategory::from_row_ref).transpose()?;
+
+    Ok(category)
+}
+
+#[instrument(skip_all)]
+pub async fn select_category_by_id(client: &Client, category_id: i32) -> Result<Option<ArticleCategory>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            article_category
+        WHERE
+            id = $1;
+    "#;
+
     let row = client
-        .query_one(stmt, &[&level3])
+        .query_opt(stmt, &[&category_id])
         .await?;
 
-    let category = ArticleCategory::from_row_ref(&row)?;
+    let category = row.map(|row| ArticleCategory::from_row_ref(&row)).transpose()?;
 
     Ok(category)
 }
 
+#[instrument(skip_all)]
 pub async fn select_all_category(client: &Client) -> Result<Vec<ArticleCategory>, ServiceError> {
     let stmt = r#"
         SELECT
@@ -58,6 +116,7 @@ pub async fn select_all_category(client: &Client) -> Result<Vec<ArticleCategory>
     Ok(categories)
 }
 
+#[instrument(skip_all)]
 pub async fn select_distinct_level(client: &Client) -> Result<HashMap<String, i64>, ServiceError> {
     let stmt = r#"
         SELECT
@@ -84,3 +143,130 @@ pub async fn select_distinct_level(client: &Client) -> Result<HashMap<String, i6
     Ok(counts)
 }
 
+/// Every category by its levels, counting the articles readers may see in each.
+#[instrument(skip_all)]
+pub async fn select_category_counts(client: &Client) -> Result<Vec<CategoryCountRecord>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            c.id,
+            c.level1,
+            c.level2,
+            c.level3,
+            COALESCE(c.description, '') AS description,
+            COUNT(a.id) AS article_count
+        FROM
+            article_category c
+            LEFT JOIN article a ON
+                a.category_id = c.id
+                AND a.status = 'published'
+                AND a.publish_at <= (NOW() AT TIME ZONE 'UTC')
+                AND a.deleted_at IS NULL
+        GROUP BY
+            c.id
+        ORDER BY
+            c.level1, c.level2, c.level3;
+    "#;
+
+    let rows = client
+        .query(stmt, &[])
+        .await?;
+
+    let mut categories = Vec::new();
+
+    for row in rows {
+        let category = CategoryCountRecord::from_row_ref(&row)?;
+        categories.push(category)
+    }
+
+    Ok(categories)
+}
+
+#[instrument(skip_all)]
+pub async fn insert(client: &Client, category: &CategoryCourier) -> Result<ArticleCategory, ServiceError> {
+    let stmt = r#"
+        INSERT INTO
+            article_category (level1, level2, level3, description)
+        VALUES
+            ($1, $2, $3, $4)
+        RETURNING *;
+    "#;
+
+    let row = client
+        .query_one(stmt, &[&category.level1.trim(), &category.level2.trim(), &category.level3.trim(), &category.description])
+        .await?;
+
+    let category_record = ArticleCategory::from_row_ref(&row)?;
+
+    Ok(category_record)
+}
+
+/// Rename the category, along with the `kind` of the articles filed under it.
+#[instrument(skip_all)]
+pub async fn update(client: &mut Client, category_id: i32, category: &CategoryCourier) -> Result<ArticleCategory, ServiceError> {
+    let tx = client.transaction().await?;
+
+    let stmt = r#"
+        UPDATE article_category
+        SET
+            level1 = $2,
+            level2 = $3,
+            level3 = $4,
+            description = $5
+        WHERE
+            id = $1
+        RETURNING *;
+    "#;
+
+    let row = tx
+        .query_opt(stmt, &[&category_id, &category.level1.trim(), &category.level2.trim(), &category.level3.trim(), &category.description])
+        .await?
+        .ok_or_else(category_not_found)?;
+
+    let category_record = ArticleCategory::from_row_ref(&row)?;
+
+    // deleted articles too, they keep matching their category should they come back
+    let stmt = r#"
+        UPDATE article
+        SET
+            kind = $2
+        WHERE
+            category_id = $1;
+    "#;
+
+    tx.execute(stmt, &[&category_id, &category_record.level3]).await?;
+
+    tx.commit().await?;
+
+    Ok(category_record)
+}
+
+/// Remove a category no article is filed under, deleted ones included.
+#[instrument(skip_all)]
+pub async fn delete(client: &Client, category_id: i32) -> Result<(), ServiceError> {
+    let stmt = r#"
+        DELETE FROM article_category
+        WHERE
+            id = $1;
+    "#;
+
+    let deleted = client
+        .execute(stmt, &[&category_id])
+        .await
+        .map_err(|err| {
+            if err.code() == Some(&SqlState::FOREIGN_KEY_VIOLATION) {
+                ServiceError::build()
+                    .belong(BizError(DataConflict))
+                    .because(Box::new(err))
+                    .message("Articles are still filed under the category")
+                    .done()
+            } else {
+                err.into()
+            }
+        })?;
+
+    if deleted == 0 {
+        return Err(category_not_found());
+    }
+
+    Ok(())
+}
@@ -2,10 +2,11 @@ use chrono::{NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
 use crate::infra::error::biz::BizKind::ValidationFailed;
 use crate::infra::error::error::Kind::BizError;
-use crate::infra::error::error::ServiceError;
+use crate::infra::error::error::{FieldError, ServiceError};
 
 #[derive(Serialize, Debug, Deserialize)]
 pub struct Behavior {
+    pub child_id: i64,
     pub wake_up_time: NaiveTime,
     pub sleep_time: NaiveTime,
     pub diaper_changes: i32,
@@ -22,6 +23,7 @@ impl Behavior {
                 ServiceError::build()
                     .belong(BizError(ValidationFailed))
                     .message("diaper_changes must be greater than zero")
+                    .fields(vec![FieldError::new("diaper_changes", "must be greater than zero")])
                     .done()
             );
         }
@@ -30,6 +32,7 @@ impl Behavior {
                 ServiceError::build()
                     .belong(BizError(ValidationFailed))
                     .message("naps must be greater than zero")
+                    .fields(vec![FieldError::new("naps", "must be greater than zero")])
                     .done()
             );
         }
@@ -38,6 +41,7 @@ impl Behavior {
                 ServiceError::build()
                     .belong(BizError(ValidationFailed))
                     .message("crying_episodes must be greater than zero")
+                    .fields(vec![FieldError::new("crying_episodes", "must be greater than zero")])
                     .done()
             );
         }
@@ -46,6 +50,7 @@ impl Behavior {
                 ServiceError::build()
                     .belong(BizError(ValidationFailed))
                     .message("outdoor_time must be greater than zero")
+                    .fields(vec![FieldError::new("duration_outdoor", "must be greater than zero")])
                     .done()
             );
         }
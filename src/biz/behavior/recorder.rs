@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use tokio_pg_mapper::FromTokioPostgresRow;
 use tokio_pg_mapper_derive::PostgresMapper;
 use tokio_postgres::Client;
+use tracing::instrument;
 use crate::biz::behavior::courier::Behavior;
 use crate::infra::error::biz::BizKind::DataNotFound;
 use crate::infra::error::error::ServiceError;
@@ -14,6 +15,7 @@ use crate::infra::error::error::Kind::BizError;
 #[pg_mapper(table = "Behavior")]
 pub struct BehaviorRecord {
     pub id: i64,
+    pub child_id: i64,
     pub wake_up_time: NaiveTime,
     pub sleep_time: NaiveTime,
     pub diaper_changes: i32,
@@ -23,10 +25,12 @@ pub struct BehaviorRecord {
     pub record_date: NaiveDate,
 }
 
+#[instrument(skip_all)]
 pub(crate) async fn insert(pg_client: &PgClient, behavior_json: &Behavior) -> Result<BehaviorRecord, ServiceError> {
     let stmt = r#"
         INSERT INTO
             behavior (
+               child_id,
                wake_up_time,
                sleep_time,
                diaper_changes,
@@ -36,7 +40,7 @@ pub(crate) async fn insert(pg_client: &PgClient, behavior_json: &Behavior) -> Re
                record_date
             )
         VALUES
-            ($1, $2, $3, $4, $5, $6, $7)
+            ($1, $2, $3, $4, $5, $6, $7, $8)
         RETURNING *;
     "#;
 
@@ -44,6 +48,7 @@ pub(crate) async fn insert(pg_client: &PgClient, behavior_json: &Behavior) -> Re
         .query_one(
             stmt,
             &[
+                &behavior_json.child_id,
                 &behavior_json.wake_up_time,
                 &behavior_json.sleep_time,
                 &behavior_json.diaper_changes,
@@ -61,12 +66,15 @@ pub(crate) async fn insert(pg_client: &PgClient, behavior_json: &Behavior) -> Re
 }
 
 
-pub async fn select_all(client: &Client) -> Result<Vec<BehaviorRecord>, ServiceError> {
+#[instrument(skip_all)]
+pub async fn select_all(client: &Client, child_id: i64) -> Result<Vec<BehaviorRecord>, ServiceError> {
     let stmt = r#"
         SELECT
             *
         FROM
             behavior
+        WHERE
+            child_id = $1
         ORDER BY
             record_date DESC
     "#;
@@ -74,7 +82,7 @@ pub async fn select_all(client: &Client) -> Result<Vec<BehaviorRecord>, ServiceE
 
 
     let rows = client
-        .query(stmt, &[])
+        .query(stmt, &[&child_id])
         .await?;
 
     return if rows.is_empty() {
@@ -96,7 +104,8 @@ pub async fn select_all(client: &Client) -> Result<Vec<BehaviorRecord>, ServiceE
 }
 
 
-pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64) -> Result<Vec<BehaviorRecord>, ServiceError> {
+#[instrument(skip_all)]
+pub(crate) async fn select_many(pc: &PgClient, child_id: i64, page_number: i64, page_size: i64) -> Result<Vec<BehaviorRecord>, ServiceError> {
     debug!("page number: {}, page size: {}",page_number, page_size);
 
     let stmt = r#"
@@ -104,6 +113,8 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
             *
         FROM
             behavior
+        WHERE
+            child_id = $3
         ORDER BY
             created_at DESC
         LIMIT
@@ -116,7 +127,7 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
     let offset = page_number * page_size;
 
     let rows = pc
-        .query(stmt, &[&offset, &page_size])
+        .query(stmt, &[&offset, &page_size, &child_id])
         .await?;
 
     return if rows.is_empty() {
@@ -137,10 +148,11 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
     };
 }
 
-pub(crate) async fn count(pc: &PgClient) -> Result<i64, ServiceError> {
-    let stmt = r#"SELECT COUNT(*) FROM behavior"#;
+#[instrument(skip_all)]
+pub(crate) async fn count(pc: &PgClient, child_id: i64) -> Result<i64, ServiceError> {
+    let stmt = r#"SELECT COUNT(*) FROM behavior WHERE child_id = $1"#;
 
-    let count = pc.query_one(stmt, &[])
+    let count = pc.query_one(stmt, &[&child_id])
         .await?
         .get(0);
 
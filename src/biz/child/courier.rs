@@ -0,0 +1,46 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::infra::error::biz::BizKind::ValidationFailed;
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::{FieldError, ServiceError};
+
+#[derive(Serialize, Debug, Deserialize)]
+pub struct ChildJson {
+    pub name: String,
+    pub birthday: Option<NaiveDate>,
+    pub sex: Option<String>, // male female
+    pub avatar_url: Option<String>,
+}
+
+impl ChildJson {
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        if self.name.trim().is_empty() {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("name must not be empty")
+                    .fields(vec![FieldError::new("name", "must not be empty")])
+                    .done()
+            );
+        }
+        if let Some(sex) = &self.sex {
+            if sex != "male" && sex != "female" {
+                return Err(
+                    ServiceError::build()
+                        .belong(BizError(ValidationFailed))
+                        .message("sex must be either male or female")
+                        .fields(vec![FieldError::new("sex", "must be either male or female")])
+                        .done()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Query selector shared by the health, diet and behavior scopes
+/// to choose which child the records belong to.
+#[derive(Serialize, Debug, Deserialize)]
+pub struct ChildSelector {
+    pub child_id: i64,
+}
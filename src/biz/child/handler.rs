@@ -0,0 +1,97 @@
+use actix_web::{Error, get, HttpRequest, HttpResponse, post, put, web};
+use crate::AppState;
+use crate::biz::child::courier::ChildJson;
+use crate::biz::courier::HappyCourier;
+use crate::biz::internal::{extract_membership, get_pg};
+use crate::infra::middleware::authorize::{Authorize, Permission};
+use super::recorder;
+
+#[post("", wrap = "Authorize::require(Permission::ManageChild)")]
+pub async fn create_child(req: HttpRequest, app_state: web::Data<AppState>, body: web::Json<ChildJson>) -> Result<HttpResponse, Error> {
+    let family_id = extract_membership(req)?.family_id;
+
+    let child_json = body.into_inner();
+
+    // validate
+    child_json.validate()?;
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let child_record = recorder::insert(&pg_client, family_id, &child_json).await?;
+
+    Ok(
+        HttpResponse::Created()
+            .json(
+                HappyCourier::build()
+                    .message("Success to create child")
+                    .data(child_record)
+                    .done()
+            )
+    )
+}
+
+#[get("")]
+pub async fn read_children(req: HttpRequest, app_state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    let family_id = extract_membership(req)?.family_id;
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let child_records = recorder::select_by_family(&pg_client, family_id).await?;
+
+    Ok(
+        HttpResponse::Ok()
+            .json(
+                HappyCourier::build()
+                    .message("Success to get children of the family")
+                    .data(child_records)
+                    .done()
+            )
+    )
+}
+
+#[get("/{child_id}")]
+pub async fn read_child(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>) -> Result<HttpResponse, Error> {
+    let family_id = extract_membership(req)?.family_id;
+
+    let child_id = path.into_inner();
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let child_record = recorder::select_one(&pg_client, child_id, family_id).await?;
+
+    Ok(
+        HttpResponse::Ok()
+            .json(
+                HappyCourier::build()
+                    .message("Success to get child")
+                    .data(child_record)
+                    .done()
+            )
+    )
+}
+
+#[put("/{child_id}", wrap = "Authorize::require(Permission::ManageChild)")]
+pub async fn update_child(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>, body: web::Json<ChildJson>) -> Result<HttpResponse, Error> {
+    let family_id = extract_membership(req)?.family_id;
+
+    let child_id = path.into_inner();
+
+    let child_json = body.into_inner();
+
+    // validate
+    child_json.validate()?;
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let child_record = recorder::update(&pg_client, child_id, family_id, &child_json).await?;
+
+    Ok(
+        HttpResponse::Ok()
+            .json(
+                HappyCourier::build()
+                    .message("Success to update child")
+                    .data(child_record)
+                    .done()
+            )
+    )
+}
@@ -0,0 +1,171 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use deadpool_postgres::{Client as PgClient, GenericClient};
+use serde::{Deserialize, Serialize};
+use tokio_pg_mapper::FromTokioPostgresRow;
+use tokio_pg_mapper_derive::PostgresMapper;
+use tracing::instrument;
+use crate::biz::child::courier::ChildJson;
+use crate::infra::error::biz::BizKind::DataNotFound;
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::ServiceError;
+
+#[derive(Deserialize, PostgresMapper, Debug, Serialize, Default)]
+#[pg_mapper(table = "child")]
+pub struct ChildRecord {
+    pub id: i64,
+    pub family_id: i64,
+    pub name: String,
+    pub birthday: Option<NaiveDate>,
+    pub sex: Option<String>,
+    pub avatar_url: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn insert(pg_client: &impl GenericClient, family_id: i64, child_json: &ChildJson) -> Result<ChildRecord, ServiceError> {
+    let stmt = r#"
+        INSERT INTO
+            child (
+                family_id,
+                name,
+                birthday,
+                sex,
+                avatar_url
+            )
+        VALUES
+            ($1, $2, $3, $4, $5)
+        RETURNING *;
+    "#;
+
+    let row = pg_client
+        .query_one(
+            stmt,
+            &[
+                &family_id,
+                &child_json.name.trim(),
+                &child_json.birthday,
+                &child_json.sex,
+                &child_json.avatar_url,
+            ],
+        )
+        .await?;
+
+    let child_record = ChildRecord::from_row_ref(&row)?;
+
+    Ok(child_record)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn update(pg_client: &PgClient, child_id: i64, family_id: i64, child_json: &ChildJson) -> Result<ChildRecord, ServiceError> {
+    let stmt = r#"
+        UPDATE child
+        SET
+            name = $3,
+            birthday = $4,
+            sex = $5,
+            avatar_url = $6,
+            updated_at = NOW()
+        WHERE
+            id = $1 AND family_id = $2
+        RETURNING *;
+    "#;
+
+    let row = pg_client
+        .query_opt(
+            stmt,
+            &[
+                &child_id,
+                &family_id,
+                &child_json.name.trim(),
+                &child_json.birthday,
+                &child_json.sex,
+                &child_json.avatar_url,
+            ],
+        )
+        .await?
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(BizError(DataNotFound))
+                .message("The child does not exist in the family")
+                .done()
+        })?;
+
+    let child_record = ChildRecord::from_row_ref(&row)?;
+
+    Ok(child_record)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn select_one(pg_client: &PgClient, child_id: i64, family_id: i64) -> Result<ChildRecord, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            child
+        WHERE
+            id = $1 AND family_id = $2;
+    "#;
+
+    let row = pg_client
+        .query_opt(stmt, &[&child_id, &family_id])
+        .await?
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(BizError(DataNotFound))
+                .message("The child does not exist in the family")
+                .done()
+        })?;
+
+    let child_record = ChildRecord::from_row_ref(&row)?;
+
+    Ok(child_record)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn select_by_family(pg_client: &PgClient, family_id: i64) -> Result<Vec<ChildRecord>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            child
+        WHERE
+            family_id = $1
+        ORDER BY
+            birthday ASC NULLS LAST, id ASC;
+    "#;
+
+    let rows = pg_client
+        .query(stmt, &[&family_id])
+        .await?;
+
+    let mut children = Vec::new();
+
+    for row in rows {
+        let child_record = ChildRecord::from_row_ref(&row)?;
+        children.push(child_record)
+    }
+
+    Ok(children)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn belongs_to_family(pg_client: &PgClient, child_id: i64, family_id: i64) -> Result<bool, ServiceError> {
+    let stmt = r#"
+        SELECT EXISTS (
+            SELECT
+                1
+            FROM
+                child
+            WHERE
+                id = $1 AND family_id = $2
+        );
+    "#;
+
+    let belongs = pg_client
+        .query_one(stmt, &[&child_id, &family_id])
+        .await?
+        .get(0);
+
+    Ok(belongs)
+}
@@ -30,12 +30,6 @@ impl SadCourier {
             .message(message)
             .done()
     }
-
-    pub fn sorry() -> Courier<String, String> {
-        Courier::build()
-            .message("Internal server error due to an unknown reason")
-            .done()
-    }
 }
 
 
@@ -89,17 +83,19 @@ pub struct PaginateQuery {
     pub page_size:i64,
 }
 
+#[cfg(test)]
 mod tests {
     #[test]
     fn new_courier() {
         use crate::biz::courier::Courier;
 
+        #[derive(Default)]
         struct TestData<'a> {
             name: &'a str,
             age: u8,
         }
 
-        let courier = Courier::build()
+        let courier: Courier<TestData, ()> = Courier::build()
             .message("Bad request")
             .data(
                 TestData {
@@ -110,7 +106,7 @@ mod tests {
             .done();
 
         assert_eq!(courier.message, "Bad request");
-        assert_eq!(courier.data.age, "Demon");
-        assert_eq!(courier.data.name, 18);
+        assert_eq!(courier.data.name, "Demon");
+        assert_eq!(courier.data.age, 18);
     }
 }
\ No newline at end of file
@@ -2,10 +2,11 @@ use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use crate::infra::error::biz::BizKind::ValidationFailed;
 use crate::infra::error::error::Kind::BizError;
-use crate::infra::error::error::ServiceError;
+use crate::infra::error::error::{FieldError, ServiceError};
 
 #[derive(Serialize, Debug, Deserialize)]
 pub struct DietJson {
+    pub child_id: i64,
     pub milk: i32,
     pub meat: i32,
     pub egg: i32,
@@ -22,6 +23,7 @@ impl DietJson {
                 ServiceError::build()
                     .belong(BizError(ValidationFailed))
                     .message("milk must be greater than zero")
+                    .fields(vec![FieldError::new("milk", "must be greater than zero")])
                     .done()
             );
         }
@@ -30,6 +32,7 @@ impl DietJson {
                 ServiceError::build()
                     .belong(BizError(ValidationFailed))
                     .message("meat must be greater than zero")
+                    .fields(vec![FieldError::new("meat", "must be greater than zero")])
                     .done()
             );
         }
@@ -38,6 +41,7 @@ impl DietJson {
                 ServiceError::build()
                     .belong(BizError(ValidationFailed))
                     .message("egg must be greater than zero")
+                    .fields(vec![FieldError::new("egg", "must be greater than zero")])
                     .done()
             );
         }
@@ -46,6 +50,7 @@ impl DietJson {
                 ServiceError::build()
                     .belong(BizError(ValidationFailed))
                     .message("vegetable must be greater than zero")
+                    .fields(vec![FieldError::new("vegetable", "must be greater than zero")])
                     .done()
             );
         }
@@ -54,6 +59,7 @@ impl DietJson {
                 ServiceError::build()
                     .belong(BizError(ValidationFailed))
                     .message("fruit must be greater than zero")
+                    .fields(vec![FieldError::new("fruit", "must be greater than zero")])
                     .done()
             );
         }
@@ -62,6 +68,7 @@ impl DietJson {
                 ServiceError::build()
                     .belong(BizError(ValidationFailed))
                     .message("grain must be greater than zero")
+                    .fields(vec![FieldError::new("grain", "must be greater than zero")])
                     .done()
             );
         }
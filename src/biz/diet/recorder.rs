@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use tokio_pg_mapper::FromTokioPostgresRow;
 use tokio_pg_mapper_derive::PostgresMapper;
 use tokio_postgres::Client;
+use tracing::instrument;
 use crate::biz::diet::courier::DietJson;
 use crate::infra::error::biz::BizKind::DataNotFound;
 use crate::infra::error::error::ServiceError;
@@ -14,6 +15,7 @@ use crate::infra::error::error::Kind::BizError;
 #[pg_mapper(table = "Diet")]
 pub struct DietRecord {
     pub id: i64,
+    pub child_id: i64,
     pub milk: i32,
     pub meat: i32,
     pub egg: i32,
@@ -24,10 +26,12 @@ pub struct DietRecord {
 }
 
 
+#[instrument(skip_all)]
 pub(crate) async fn insert(pg_client: &PgClient, diet_body: &DietJson) -> Result<DietRecord, ServiceError> {
     let stmt = r#"
         INSERT INTO
             diet (
+                child_id,
                 milk,
                 meat,
                 egg,
@@ -37,7 +41,7 @@ pub(crate) async fn insert(pg_client: &PgClient, diet_body: &DietJson) -> Result
                 record_date
             )
         VALUES
-            ($1, $2, $3, $4, $5, $6, $7)
+            ($1, $2, $3, $4, $5, $6, $7, $8)
         RETURNING *;
     "#;
 
@@ -45,6 +49,7 @@ pub(crate) async fn insert(pg_client: &PgClient, diet_body: &DietJson) -> Result
         .query_one(
             stmt,
             &[
+                &diet_body.child_id,
                 &diet_body.milk,
                 &diet_body.meat,
                 &diet_body.egg,
@@ -61,12 +66,15 @@ pub(crate) async fn insert(pg_client: &PgClient, diet_body: &DietJson) -> Result
     Ok(diet_record)
 }
 
-pub async fn select_all(client: &Client) -> Result<Vec<DietRecord>, ServiceError> {
+#[instrument(skip_all)]
+pub async fn select_all(client: &Client, child_id: i64) -> Result<Vec<DietRecord>, ServiceError> {
     let stmt = r#"
         SELECT
             *
         FROM
             diet
+        WHERE
+            child_id = $1
         ORDER BY
             record_date DESC
     "#;
@@ -74,7 +82,7 @@ pub async fn select_all(client: &Client) -> Result<Vec<DietRecord>, ServiceError
 
 
     let rows = client
-        .query(stmt, &[])
+        .query(stmt, &[&child_id])
         .await?;
 
     return if rows.is_empty() {
@@ -95,7 +103,8 @@ pub async fn select_all(client: &Client) -> Result<Vec<DietRecord>, ServiceError
     };
 }
 
-pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64) -> Result<Vec<DietRecord>, ServiceError> {
+#[instrument(skip_all)]
+pub(crate) async fn select_many(pc: &PgClient, child_id: i64, page_number: i64, page_size: i64) -> Result<Vec<DietRecord>, ServiceError> {
     debug!("page number: {}, page size: {}",page_number, page_size);
 
     let stmt = r#"
@@ -103,6 +112,8 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
             *
         FROM
             diet
+        WHERE
+            child_id = $3
         ORDER BY
             created_at DESC
         LIMIT
@@ -115,7 +126,7 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
     let offset = page_number * page_size;
 
     let rows = pc
-        .query(stmt, &[&offset, &page_size])
+        .query(stmt, &[&offset, &page_size, &child_id])
         .await?;
 
     return if rows.is_empty() {
@@ -136,10 +147,11 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
     };
 }
 
-pub(crate) async fn count(pc: &PgClient) -> Result<i64, ServiceError> {
-    let stmt = r#"SELECT COUNT(*) FROM diet"#;
+#[instrument(skip_all)]
+pub(crate) async fn count(pc: &PgClient, child_id: i64) -> Result<i64, ServiceError> {
+    let stmt = r#"SELECT COUNT(*) FROM diet WHERE child_id = $1"#;
 
-    let count = pc.query_one(stmt, &[])
+    let count = pc.query_one(stmt, &[&child_id])
         .await?
         .get(0);
 
@@ -4,6 +4,9 @@ use super::{courier, recorder};
 use crate::biz::courier::{HappyCourier, SadCourier};
 use crate::biz::internal;
 use crate::biz::internal::get_pg;
+use crate::infra::error::biz::BizKind::DataNotFound;
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::ServiceError;
 
 #[post("")]
 pub async fn create_draft(req: HttpRequest, app_state: web::Data<AppState>, req_body: web::Json<courier::DraftCourier>) -> Result<HttpResponse, Error> {
@@ -31,17 +34,19 @@ pub async fn read_draft_owned(req: HttpRequest, app_state: web::Data<AppState>)
     let res = recorder::select(&client, user_id).await?;
 
     if res.is_empty() {
-        Ok(
-            HttpResponse::NotFound().json(
-                SadCourier::brief("The user has no draft")
-            )
+        Err(
+            ServiceError::build()
+                .belong(BizError(DataNotFound))
+                .message("The user has no draft")
+                .done()
+                .into()
         )
     } else {
         Ok(
             HttpResponse::Ok().json(
                 HappyCourier::build()
                     .message("Success to find draft")
-                    .data(res.get(0))
+                    .data(res.first())
                     .done()
             )
         )
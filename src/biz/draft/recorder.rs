@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 use tokio_pg_mapper::FromTokioPostgresRow;
 use tokio_pg_mapper_derive::PostgresMapper;
 use tokio_postgres::{Client};
+use tracing::instrument;
 use crate::infra::error::error::ServiceError;
 
 #[derive(Deserialize, PostgresMapper, Debug, Serialize, Default)]
@@ -13,6 +14,7 @@ pub struct DraftRecord {
     pub updated_at: NaiveDateTime,
 }
 
+#[instrument(skip_all)]
 pub async fn insert(client: &Client, user_id: i64, draft: String) -> Result<(), ServiceError> {
     let stmt = r#"
         INSERT INTO
@@ -40,6 +42,7 @@ pub async fn insert(client: &Client, user_id: i64, draft: String) -> Result<(),
     Ok(())
 }
 
+#[instrument(skip_all)]
 pub async fn select(client: &Client, user_id: i64) -> Result<Vec<DraftRecord>, ServiceError> {
     let stmt = r#"
         SELECT
@@ -0,0 +1,76 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use tokio_pg_mapper_derive::PostgresMapper;
+use crate::biz::family::recorder::FamilyRecord;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FamilyRole {
+    Owner,
+    Parent,
+    Caregiver,
+    #[default]
+    Viewer,
+}
+
+impl FamilyRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FamilyRole::Owner => "owner",
+            FamilyRole::Parent => "parent",
+            FamilyRole::Caregiver => "caregiver",
+            FamilyRole::Viewer => "viewer",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "owner" => Some(FamilyRole::Owner),
+            "parent" => Some(FamilyRole::Parent),
+            "caregiver" => Some(FamilyRole::Caregiver),
+            "viewer" => Some(FamilyRole::Viewer),
+            _ => None,
+        }
+    }
+
+    /// Owners and parents may invite new members into the family.
+    pub fn can_invite(&self) -> bool {
+        matches!(self, FamilyRole::Owner | FamilyRole::Parent)
+    }
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+pub struct FamilyJson {
+    pub name: String,
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+pub struct InvitationJson {
+    pub role: FamilyRole,
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+pub struct InvitationCodeJson {
+    pub code: String,
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+pub struct RoleJson {
+    pub role: FamilyRole,
+}
+
+#[derive(Deserialize, PostgresMapper, Debug, Serialize, Default)]
+#[pg_mapper(table = "family_member")]
+pub struct MemberResp {
+    pub account_id: i64,
+    pub username: String,
+    pub avatar_url: Option<String>,
+    pub role: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct FamilyResp {
+    pub family: FamilyRecord,
+    pub members: Vec<MemberResp>,
+}
@@ -0,0 +1,250 @@
+use std::ops::Add;
+use actix_web::{delete, Error, get, HttpRequest, HttpResponse, post, put, web};
+use chrono::{TimeDelta, Utc};
+use crate::AppState;
+use crate::biz::courier::{HappyCourier, SadCourier};
+use crate::biz::family::courier::{FamilyJson, FamilyResp, FamilyRole, InvitationCodeJson, InvitationJson, RoleJson};
+use crate::biz::internal::{extract_membership, extract_user_id, get_pg};
+use crate::infra::crypto::random_code;
+use crate::infra::error::biz::BizKind::{DataConflict, PermissionDenied, ValidationFailed};
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::{FieldError, ServiceError};
+use crate::infra::middleware::family::FamilyMiddleware;
+use super::recorder;
+
+const INVITATION_SPAN: i64 = 72;
+const INVITATION_CODE_LEN: usize = 8;
+
+#[post("")]
+pub async fn create_family(req: HttpRequest, app_state: web::Data<AppState>, body: web::Json<FamilyJson>) -> Result<HttpResponse, Error> {
+    let user_id = extract_user_id(req)?;
+
+    let family_json = body.into_inner();
+
+    if family_json.name.trim().is_empty() {
+        return Err(invalid("name", "must not be empty", "Family name is empty").into());
+    }
+
+    let mut pg_client = get_pg(&app_state).await?;
+
+    if recorder::select_by_member(&pg_client, user_id).await?.is_some() {
+        return Err(already_in_family().into());
+    }
+
+    let family_record = recorder::insert(&mut pg_client, family_json.name.trim(), user_id).await?;
+
+    Ok(
+        HttpResponse::Created().json(
+            HappyCourier::build()
+                .message("Success to create family")
+                .data(family_record)
+                .done()
+        )
+    )
+}
+
+#[get("", wrap = "FamilyMiddleware")]
+pub async fn read_family_owned(req: HttpRequest, app_state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    let membership = extract_membership(req)?;
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let family = recorder::select_family(&pg_client, membership.family_id).await?;
+
+    let members = recorder::select_members(&pg_client, membership.family_id).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to get family")
+                .data(FamilyResp { family, members })
+                .done()
+        )
+    )
+}
+
+#[post("/invitation", wrap = "FamilyMiddleware")]
+pub async fn create_invitation(req: HttpRequest, app_state: web::Data<AppState>, body: web::Json<InvitationJson>) -> Result<HttpResponse, Error> {
+    let membership = extract_membership(req)?;
+
+    let invitation_json = body.into_inner();
+
+    if !membership.role.can_invite() {
+        return Err(permission_denied("Only owners and parents can invite members").into());
+    }
+
+    if invitation_json.role == FamilyRole::Owner {
+        return Err(invalid("role", "must not be owner", "A family can not have another owner by invitation").into());
+    }
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let code = random_code(INVITATION_CODE_LEN)?;
+
+    let expires_at = Utc::now().naive_utc().add(TimeDelta::hours(INVITATION_SPAN));
+
+    let invitation_record = recorder::insert_invitation(
+        &pg_client,
+        membership.family_id,
+        membership.account_id,
+        invitation_json.role,
+        &code,
+        expires_at,
+    ).await?;
+
+    Ok(
+        HttpResponse::Created().json(
+            HappyCourier::build()
+                .message("Success to create invitation")
+                .data(invitation_record)
+                .done()
+        )
+    )
+}
+
+#[get("/invitation", wrap = "FamilyMiddleware")]
+pub async fn read_pending_invitation(req: HttpRequest, app_state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    let membership = extract_membership(req)?;
+
+    if !membership.role.can_invite() {
+        return Err(permission_denied("Only owners and parents can view invitations").into());
+    }
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let invitation_records = recorder::select_pending_invitations(&pg_client, membership.family_id).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to get pending invitations")
+                .data(invitation_records)
+                .done()
+        )
+    )
+}
+
+#[post("/join")]
+pub async fn accept_invitation(req: HttpRequest, app_state: web::Data<AppState>, body: web::Json<InvitationCodeJson>) -> Result<HttpResponse, Error> {
+    let user_id = extract_user_id(req)?;
+
+    let code = body.into_inner().code;
+
+    let mut pg_client = get_pg(&app_state).await?;
+
+    if recorder::select_by_member(&pg_client, user_id).await?.is_some() {
+        return Err(already_in_family().into());
+    }
+
+    let member_record = recorder::accept_invitation(&mut pg_client, code.trim(), user_id).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to join the family")
+                .data(member_record)
+                .done()
+        )
+    )
+}
+
+#[post("/decline")]
+pub async fn decline_invitation(req: HttpRequest, app_state: web::Data<AppState>, body: web::Json<InvitationCodeJson>) -> Result<HttpResponse, Error> {
+    let user_id = extract_user_id(req)?;
+
+    let code = body.into_inner().code;
+
+    let pg_client = get_pg(&app_state).await?;
+
+    recorder::decline_invitation(&pg_client, code.trim(), user_id).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            SadCourier::brief("Success to decline the invitation")
+        )
+    )
+}
+
+#[put("/member/{account_id}", wrap = "FamilyMiddleware")]
+pub async fn update_member_role(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>, body: web::Json<RoleJson>) -> Result<HttpResponse, Error> {
+    let membership = extract_membership(req)?;
+
+    let account_id = path.into_inner();
+
+    let role = body.into_inner().role;
+
+    if membership.role != FamilyRole::Owner {
+        return Err(permission_denied("Only owners can assign roles").into());
+    }
+
+    if account_id == membership.account_id {
+        return Err(invalid("account_id", "must not be the owner", "Owners can not change their own role").into());
+    }
+
+    if role == FamilyRole::Owner {
+        return Err(invalid("role", "must not be owner", "A family can not have another owner by role change").into());
+    }
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let member_record = recorder::update_role(&pg_client, membership.family_id, account_id, role).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to update member role")
+                .data(member_record)
+                .done()
+        )
+    )
+}
+
+/// Owners remove other members, while any member can remove themselves to leave.
+#[delete("/member/{account_id}", wrap = "FamilyMiddleware")]
+pub async fn remove_member(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>) -> Result<HttpResponse, Error> {
+    let membership = extract_membership(req)?;
+
+    let account_id = path.into_inner();
+
+    let is_leaving = account_id == membership.account_id;
+
+    if !is_leaving && membership.role != FamilyRole::Owner {
+        return Err(permission_denied("Only owners can remove other members").into());
+    }
+
+    if is_leaving && membership.role == FamilyRole::Owner {
+        return Err(invalid("account_id", "must not be the owner", "Owners can not leave their own family").into());
+    }
+
+    let pg_client = get_pg(&app_state).await?;
+
+    recorder::delete_member(&pg_client, membership.family_id, account_id).await?;
+
+    Ok(
+        HttpResponse::Ok().json(
+            SadCourier::brief("Success to remove member")
+        )
+    )
+}
+
+fn permission_denied(message: &str) -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(PermissionDenied))
+        .message(message)
+        .done()
+}
+
+fn invalid(field: &str, reason: &str, message: &str) -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(ValidationFailed))
+        .message(message)
+        .fields(vec![FieldError::new(field, reason)])
+        .done()
+}
+
+fn already_in_family() -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(DataConflict))
+        .message("The user already belongs to a family")
+        .done()
+}
@@ -0,0 +1,347 @@
+use chrono::NaiveDateTime;
+use deadpool_postgres::{Client as PgClient, GenericClient};
+use serde::{Deserialize, Serialize};
+use tokio_pg_mapper::FromTokioPostgresRow;
+use tokio_pg_mapper_derive::PostgresMapper;
+use tracing::instrument;
+use crate::biz::family::courier::{FamilyRole, MemberResp};
+use crate::infra::error::biz::BizKind::DataNotFound;
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::ServiceError;
+
+#[derive(Deserialize, PostgresMapper, Debug, Serialize, Default)]
+#[pg_mapper(table = "family")]
+pub struct FamilyRecord {
+    pub id: i64,
+    pub name: String,
+    pub created_by: i64,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, PostgresMapper, Debug, Serialize, Default)]
+#[pg_mapper(table = "family_member")]
+pub struct MemberRecord {
+    pub family_id: i64,
+    pub account_id: i64,
+    pub role: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, PostgresMapper, Debug, Serialize, Default)]
+#[pg_mapper(table = "family_invitation")]
+pub struct InvitationRecord {
+    pub id: i64,
+    pub family_id: i64,
+    pub code: String,
+    pub role: String,
+    pub invited_by: i64,
+    pub invitee_id: Option<i64>,
+    pub status: String,
+    pub expires_at: NaiveDateTime,
+    pub responded_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Create a family and make the creator its owner.
+#[instrument(skip_all)]
+pub(crate) async fn insert(pg_client: &mut PgClient, name: &str, created_by: i64) -> Result<FamilyRecord, ServiceError> {
+    let tx = pg_client.transaction().await?;
+
+    let family_record = insert_in(&tx, name, created_by).await?;
+
+    tx.commit().await?;
+
+    Ok(family_record)
+}
+
+/// `insert` within a transaction of the caller.
+pub(crate) async fn insert_in(tx: &impl GenericClient, name: &str, created_by: i64) -> Result<FamilyRecord, ServiceError> {
+    let stmt = r#"
+        INSERT INTO
+            family (name, created_by)
+        VALUES
+            ($1, $2)
+        RETURNING *;
+    "#;
+
+    let row = tx
+        .query_one(stmt, &[&name, &created_by])
+        .await?;
+
+    let family_record = FamilyRecord::from_row_ref(&row)?;
+
+    let stmt = r#"
+        INSERT INTO
+            family_member (family_id, account_id, role)
+        VALUES
+            ($1, $2, $3);
+    "#;
+
+    tx.execute(stmt, &[&family_record.id, &created_by, &FamilyRole::Owner.as_str()]).await?;
+
+    Ok(family_record)
+}
+
+/// Return the family the account belongs to, if any.
+#[instrument(skip_all)]
+pub(crate) async fn select_by_member(pg_client: &PgClient, account_id: i64) -> Result<Option<FamilyRecord>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            f.*
+        FROM
+            family f
+        JOIN
+            family_member m ON m.family_id = f.id
+        WHERE
+            m.account_id = $1;
+    "#;
+
+    let row = pg_client
+        .query_opt(stmt, &[&account_id])
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(FamilyRecord::from_row_ref(&row)?)),
+        None => Ok(None),
+    }
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn select_membership(pg_client: &PgClient, account_id: i64) -> Result<Option<MemberRecord>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            family_member
+        WHERE
+            account_id = $1;
+    "#;
+
+    let row = pg_client
+        .query_opt(stmt, &[&account_id])
+        .await?;
+
+    match row {
+        Some(row) => Ok(Some(MemberRecord::from_row_ref(&row)?)),
+        None => Ok(None),
+    }
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn select_family(pg_client: &PgClient, family_id: i64) -> Result<FamilyRecord, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            family
+        WHERE
+            id = $1;
+    "#;
+
+    let row = pg_client
+        .query_one(stmt, &[&family_id])
+        .await?;
+
+    let family_record = FamilyRecord::from_row_ref(&row)?;
+
+    Ok(family_record)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn select_members(pg_client: &PgClient, family_id: i64) -> Result<Vec<MemberResp>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            m.account_id,
+            a.username,
+            a.avatar_url,
+            m.role,
+            m.created_at
+        FROM
+            family_member m
+        JOIN
+            account a ON a.id = m.account_id
+        WHERE
+            m.family_id = $1
+        ORDER BY
+            m.created_at ASC;
+    "#;
+
+    let rows = pg_client
+        .query(stmt, &[&family_id])
+        .await?;
+
+    let mut members = Vec::new();
+
+    for row in rows {
+        let member = MemberResp::from_row_ref(&row)?;
+        members.push(member)
+    }
+
+    Ok(members)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn update_role(pg_client: &PgClient, family_id: i64, account_id: i64, role: FamilyRole) -> Result<MemberRecord, ServiceError> {
+    let stmt = r#"
+        UPDATE family_member
+        SET
+            role = $3
+        WHERE
+            family_id = $1 AND account_id = $2
+        RETURNING *;
+    "#;
+
+    let row = pg_client
+        .query_opt(stmt, &[&family_id, &account_id, &role.as_str()])
+        .await?
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(BizError(DataNotFound))
+                .message("The account is not a member of the family")
+                .done()
+        })?;
+
+    let member_record = MemberRecord::from_row_ref(&row)?;
+
+    Ok(member_record)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn delete_member(pg_client: &PgClient, family_id: i64, account_id: i64) -> Result<(), ServiceError> {
+    let stmt = r#"
+        DELETE FROM
+            family_member
+        WHERE
+            family_id = $1 AND account_id = $2;
+    "#;
+
+    let deleted = pg_client
+        .execute(stmt, &[&family_id, &account_id])
+        .await?;
+
+    if deleted == 0 {
+        return Err(
+            ServiceError::build()
+                .belong(BizError(DataNotFound))
+                .message("The account is not a member of the family")
+                .done()
+        );
+    }
+
+    Ok(())
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn insert_invitation(pg_client: &PgClient, family_id: i64, invited_by: i64, role: FamilyRole, code: &str, expires_at: NaiveDateTime) -> Result<InvitationRecord, ServiceError> {
+    let stmt = r#"
+        INSERT INTO
+            family_invitation (
+                family_id,
+                invited_by,
+                role,
+                code,
+                expires_at
+            )
+        VALUES
+            ($1, $2, $3, $4, $5)
+        RETURNING *;
+    "#;
+
+    let row = pg_client
+        .query_one(stmt, &[&family_id, &invited_by, &role.as_str(), &code, &expires_at])
+        .await?;
+
+    let invitation_record = InvitationRecord::from_row_ref(&row)?;
+
+    Ok(invitation_record)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn select_pending_invitations(pg_client: &PgClient, family_id: i64) -> Result<Vec<InvitationRecord>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            *
+        FROM
+            family_invitation
+        WHERE
+            family_id = $1 AND status = 'pending' AND expires_at > (NOW() AT TIME ZONE 'UTC')
+        ORDER BY
+            created_at DESC;
+    "#;
+
+    let rows = pg_client
+        .query(stmt, &[&family_id])
+        .await?;
+
+    let mut invitations = Vec::new();
+
+    for row in rows {
+        let invitation_record = InvitationRecord::from_row_ref(&row)?;
+        invitations.push(invitation_record)
+    }
+
+    Ok(invitations)
+}
+
+/// Consume a pending invitation by accepting it, which makes the invitee a member
+/// of the inviting family. An invite code can only be used once.
+#[instrument(skip_all)]
+pub(crate) async fn accept_invitation(pg_client: &mut PgClient, code: &str, invitee_id: i64) -> Result<MemberRecord, ServiceError> {
+    let tx = pg_client.transaction().await?;
+
+    let row = tx
+        .query_opt(CONSUME_INVITATION_STMT, &[&code, &"accepted", &invitee_id])
+        .await?
+        .ok_or_else(invitation_not_found)?;
+
+    let invitation_record = InvitationRecord::from_row_ref(&row)?;
+
+    let stmt = r#"
+        INSERT INTO
+            family_member (family_id, account_id, role)
+        VALUES
+            ($1, $2, $3)
+        RETURNING *;
+    "#;
+
+    let row = tx
+        .query_one(stmt, &[&invitation_record.family_id, &invitee_id, &invitation_record.role])
+        .await?;
+
+    let member_record = MemberRecord::from_row_ref(&row)?;
+
+    tx.commit().await?;
+
+    Ok(member_record)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn decline_invitation(pg_client: &PgClient, code: &str, invitee_id: i64) -> Result<InvitationRecord, ServiceError> {
+    let row = pg_client
+        .query_opt(CONSUME_INVITATION_STMT, &[&code, &"declined", &invitee_id])
+        .await?
+        .ok_or_else(invitation_not_found)?;
+
+    let invitation_record = InvitationRecord::from_row_ref(&row)?;
+
+    Ok(invitation_record)
+}
+
+const CONSUME_INVITATION_STMT: &str = r#"
+    UPDATE family_invitation
+    SET
+        status = $2,
+        invitee_id = $3,
+        responded_at = NOW()
+    WHERE
+        code = $1 AND status = 'pending' AND expires_at > (NOW() AT TIME ZONE 'UTC')
+    RETURNING *;
+"#;
+
+fn invitation_not_found() -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(DataNotFound))
+        .message("The invitation does not exist, has expired or has been used")
+        .done()
+}
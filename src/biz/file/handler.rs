@@ -1,20 +1,28 @@
-use actix_web::{web, Error, HttpResponse, post};
+use actix_web::{web, Error, HttpRequest, HttpResponse, get, post};
+use actix_files::NamedFile;
 use futures::StreamExt;
-use std::io::Write;
+use std::io::{ErrorKind, Write};
 use actix_multipart::Multipart;
 use futures_util::TryStreamExt;
 use log::debug;
 use crate::AppState;
 use crate::biz::courier::SadCourier;
-use crate::infra::error::error::Kind::InfraError;
-use crate::infra::error::error::ServiceError;
+use crate::biz::internal::extract_membership;
+use crate::infra::error::biz::BizKind::DataNotFound;
+use crate::infra::error::error::Kind::{BizError, InfraError};
+use crate::infra::error::error::{FieldError, ServiceError};
+use crate::infra::metrics::METRICS;
+use crate::infra::middleware::authorize::{Authorize, Permission};
 
 /// 上传头像处理函数
 #[post("/image")]
 pub async fn save_image(
+    req: HttpRequest,
     app_state: web::Data<AppState>,
     payload: Multipart) -> Result<HttpResponse, Error> {
-    handle_file_upload(payload, &app_state.image_static_dir, "image").await?;
+    let family_id = extract_membership(req)?.family_id;
+
+    handle_file_upload(payload, &family_dir(&app_state.image_static_dir, family_id), "image").await?;
 
     Ok(HttpResponse::Ok().json(
         SadCourier::brief("Success to upload image")
@@ -23,15 +31,117 @@ pub async fn save_image(
 
 #[post("/document")]
 pub async fn save_document(
+    req: HttpRequest,
     app_state: web::Data<AppState>,
     payload: Multipart) -> Result<HttpResponse, Error> {
-    handle_file_upload(payload, &app_state.document_static_dir, "document").await?;
+    let family_id = extract_membership(req)?.family_id;
+
+    handle_file_upload(payload, &family_dir(&app_state.document_static_dir, family_id), "document").await?;
 
     Ok(HttpResponse::Ok().json(
         SadCourier::brief("Success to upload document")
     ))
 }
 
+/// 上传文章配图，存放在公开目录中，所有人可经 /static/image 访问
+#[post("/image", wrap = "Authorize::require(Permission::WriteArticle)")]
+pub async fn save_article_image(
+    app_state: web::Data<AppState>,
+    payload: Multipart) -> Result<HttpResponse, Error> {
+    handle_file_upload(payload, &app_state.image_static_dir, "image").await?;
+
+    Ok(HttpResponse::Ok().json(
+        SadCourier::brief("Success to upload image")
+    ))
+}
+
+/// 读取本家庭上传的文件，其他家庭的文件不可见
+#[get("/{kind}/{filename}")]
+pub async fn read_file(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let family_id = extract_membership(req.clone())?.family_id;
+
+    let (kind, filename) = path.into_inner();
+
+    let Some(upload_dir) = upload_dir(&app_state, &kind) else {
+        return Err(unknown_kind().into());
+    };
+
+    let filepath = format!("{}/{}", family_dir(upload_dir, family_id), sanitize_filename::sanitize(filename));
+
+    Ok(open_file(filepath).await?.into_response(&req))
+}
+
+/// 读取公开目录中的文件：文章配图，以及按家庭分目录存放之前上传的文件
+#[get("/{kind}/{filename}")]
+pub async fn read_public_file(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (kind, filename) = path.into_inner();
+
+    let Some(upload_dir) = upload_dir(&app_state, &kind) else {
+        return Err(unknown_kind().into());
+    };
+
+    // 文件名中不含路径分隔符，家庭子目录里的文件无法经此读取
+    let filepath = format!("{}/{}", upload_dir, sanitize_filename::sanitize(filename));
+
+    Ok(open_file(filepath).await?.into_response(&req))
+}
+
+fn upload_dir<'a>(app_state: &'a AppState, kind: &str) -> Option<&'a str> {
+    match kind {
+        "image" => Some(&app_state.image_static_dir),
+        "document" => Some(&app_state.document_static_dir),
+        _ => None,
+    }
+}
+
+fn unknown_kind() -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(DataNotFound))
+        .message("Unknown file kind")
+        .fields(vec![FieldError::new("kind", "must be image or document")])
+        .done()
+}
+
+fn file_not_found(err: std::io::Error) -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(DataNotFound))
+        .because(Box::new(err))
+        .message("File is not found")
+        .done()
+}
+
+/// 打开普通文件，目录（例如家庭子目录）视为不存在
+async fn open_file(filepath: String) -> Result<NamedFile, ServiceError> {
+    let file = NamedFile::open_async(filepath)
+        .await
+        .map_err(|err| {
+            if err.kind() == ErrorKind::NotFound {
+                file_not_found(err)
+            } else {
+                err.into()
+            }
+        })?;
+
+    if !file.metadata().is_file() {
+        return Err(file_not_found(std::io::Error::from(ErrorKind::NotFound)));
+    }
+
+    Ok(
+        file.use_etag(true)
+            .use_last_modified(true)
+    )
+}
+
+/// 每个家庭的文件存放在各自的子目录中
+fn family_dir(upload_dir: &str, family_id: i64) -> String {
+    format!("{}/{}", upload_dir, family_id)
+}
 
 /// 通用文件上传处理函数
 async fn handle_file_upload(
@@ -39,6 +149,9 @@ async fn handle_file_upload(
     upload_dir: &str,
     field_name: &str,
 ) -> Result<(), ServiceError> {
+    let dir = upload_dir.to_string();
+    web::block(move || std::fs::create_dir_all(dir)).await??;
+
     while let Ok(Some(mut field)) = payload.try_next().await {
         debug!("field: {:?}", field);
         debug!("field name: {:?}", field.name());
@@ -62,9 +175,11 @@ async fn handle_file_upload(
             let mut f = web::block(|| std::fs::File::create(filepath)).await??;
             while let Some(chunk) = field.next().await {
                 let data = chunk?;
+                let len = data.len() as u64;
                 f = web::block(move || f.write_all(&data).map(|_| f)).await??;
+                METRICS.upload_bytes.with_label_values(&[field_name]).inc_by(len);
             }
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
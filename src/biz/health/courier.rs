@@ -1,11 +1,57 @@
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use crate::infra::error::biz::BizKind::ValidationFailed;
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::{FieldError, ServiceError};
 
 #[derive(Serialize, Debug, Deserialize)]
 pub struct HealthJson {
+    pub child_id: i64,
     pub height: f64,
     pub weight: f64,
     pub teeth: i32,
     pub head_circumference: f64,
     pub record_date: NaiveDate,
-}
\ No newline at end of file
+}
+
+impl HealthJson {
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        if self.height < 0.0 {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("height must not be negative")
+                    .fields(vec![FieldError::new("height", "must not be negative")])
+                    .done()
+            );
+        }
+        if self.weight < 0.0 {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("weight must not be negative")
+                    .fields(vec![FieldError::new("weight", "must not be negative")])
+                    .done()
+            );
+        }
+        if self.teeth < 0 {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("teeth must not be negative")
+                    .fields(vec![FieldError::new("teeth", "must not be negative")])
+                    .done()
+            );
+        }
+        if self.head_circumference < 0.0 {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("head_circumference must not be negative")
+                    .fields(vec![FieldError::new("head_circumference", "must not be negative")])
+                    .done()
+            );
+        }
+        Ok(())
+    }
+}
@@ -1,29 +1,30 @@
-use actix_web::{Error, get, HttpResponse, post, web};
+use actix_web::{Error, get, HttpRequest, HttpResponse, post, web};
 use crate::AppState;
-use crate::biz::courier::{Courier, HappyCourier, PaginateQuery, SadCourier};
+use crate::biz::courier::{Courier, HappyCourier, PaginateQuery};
 use crate::biz::health::courier::HealthJson;
-use crate::biz::internal::{get_pg, MAX_PAGE_SIZE, MIN_PAGE_SIZE};
+use crate::biz::child::courier::ChildSelector;
+use crate::biz::internal::{ensure_child_access, ensure_page_number, ensure_page_size, extract_membership, get_pg};
+use crate::infra::middleware::authorize::{Authorize, Permission};
 use super::recorder;
 
-#[post("")]
-pub async fn create_health_record(app_state: web::Data<AppState>, body: web::Json<HealthJson>) -> Result<HttpResponse, Error> {
+#[post("", wrap = "Authorize::require(Permission::WriteChildRecord)")]
+pub async fn create_health_record(req: HttpRequest, app_state: web::Data<AppState>, body: web::Json<HealthJson>) -> Result<HttpResponse, Error> {
+    let family_id = extract_membership(req)?.family_id;
+
     let pg_client = get_pg(&app_state).await?;
 
     let health_body = body.into_inner();
 
+    ensure_child_access(&pg_client, family_id, health_body.child_id).await?;
+
     // validate
-    if health_body.height < 0.0 || health_body.weight < 0.0 || health_body.teeth < 0|| health_body.head_circumference < 0.0 {
-        return Ok(
-            HttpResponse::BadRequest().json(
-                SadCourier::brief("Journal content or title is empty")
-            )
-        );
-    }
+    health_body.validate()?;
 
     // validate date todo
 
     let health_record = recorder::insert(
         &pg_client,
+        health_body.child_id,
         health_body.height,
         health_body.weight,
         health_body.teeth,
@@ -42,12 +43,18 @@ pub async fn create_health_record(app_state: web::Data<AppState>, body: web::Jso
     )
 }
 #[get("/all")]
-pub async fn read_all_health_record(app_state: web::Data<AppState>)-> Result<HttpResponse, Error> {
+pub async fn read_all_health_record(req: HttpRequest, app_state: web::Data<AppState>, child_query: web::Query<ChildSelector>)-> Result<HttpResponse, Error> {
+    let family_id = extract_membership(req)?.family_id;
+
     let client = get_pg(&app_state).await?;
 
-    let health_records = recorder::select_all(&client).await?;
+    let child_id = child_query.into_inner().child_id;
+
+    ensure_child_access(&client, family_id, child_id).await?;
 
-    let total_record = recorder::count(&client).await?;
+    let health_records = recorder::select_all(&client, child_id).await?;
+
+    let total_record = recorder::count(&client, child_id).await?;
 
     Ok(
         HttpResponse::Ok().json(
@@ -65,35 +72,28 @@ pub async fn read_all_health_record(app_state: web::Data<AppState>)-> Result<Htt
 
 
 #[get("/paginated")]
-pub async fn read_health_record_paginated(app_state: web::Data<AppState>, paginate_query: web::Query<PaginateQuery>) -> Result<HttpResponse, Error> {
+pub async fn read_health_record_paginated(req: HttpRequest, app_state: web::Data<AppState>, paginate_query: web::Query<PaginateQuery>, child_query: web::Query<ChildSelector>) -> Result<HttpResponse, Error> {
+    let family_id = extract_membership(req)?.family_id;
+
     let client = get_pg(&app_state).await?;
 
+    let child_id = child_query.into_inner().child_id;
+
+    ensure_child_access(&client, family_id, child_id).await?;
+
     // params validation
     let paginate = paginate_query.into_inner();
 
-    if paginate.page_size < MIN_PAGE_SIZE {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page size is too small")
-        ));
-    }
-
-    if paginate.page_size > MAX_PAGE_SIZE {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page size is too big")
-        ));
-    }
+    ensure_page_size(paginate.page_size)?;
 
-    let total_record = recorder::count(&client).await?;
+    let total_record = recorder::count(&client, child_id).await?;
 
-    if paginate.page_number > (total_record / paginate.page_size + 1) {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page number is too big")
-        ));
-    }
+    ensure_page_number(paginate.page_number, paginate.page_size, total_record)?;
 
 
     let health_records = recorder::select_many(
         &client,
+        child_id,
         paginate.page_number,
         paginate.page_size,
     )
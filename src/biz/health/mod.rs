@@ -1,3 +1,3 @@
 mod courier;
 pub mod handler;
-mod recorder;
\ No newline at end of file
+pub(crate) mod recorder;
\ No newline at end of file
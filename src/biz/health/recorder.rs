@@ -1,10 +1,11 @@
 use chrono::{NaiveDate, NaiveDateTime};
-use deadpool_postgres::Client as PgClient;
+use deadpool_postgres::{Client as PgClient, GenericClient};
 use log::debug;
 use serde::{Deserialize, Serialize};
 use tokio_pg_mapper::FromTokioPostgresRow;
 use tokio_pg_mapper_derive::PostgresMapper;
 use tokio_postgres::Client;
+use tracing::instrument;
 use crate::infra::error::biz::BizKind::DataNotFound;
 use crate::infra::error::error::ServiceError;
 use crate::infra::error::error::Kind::BizError;
@@ -13,6 +14,7 @@ use crate::infra::error::error::Kind::BizError;
 #[pg_mapper(table = "Health")]
 pub struct HealthRecord {
     pub id: i64,
+    pub child_id: i64,
     pub height: f64,
     pub weight: f64,
     pub teeth: i32,
@@ -22,10 +24,12 @@ pub struct HealthRecord {
     pub updated_at: NaiveDateTime,
 }
 
-pub(crate) async fn insert(pg_client: &PgClient, height: f64, weight: f64, teeth: i32, head_circumference: f64, record_date: NaiveDate) -> Result<HealthRecord, ServiceError> {
+#[instrument(skip_all)]
+pub(crate) async fn insert(pg_client: &impl GenericClient, child_id: i64, height: f64, weight: f64, teeth: i32, head_circumference: f64, record_date: NaiveDate) -> Result<HealthRecord, ServiceError> {
     let stmt = r#"
         INSERT INTO
             health (
+                child_id,
                 height,
                 weight,
                 teeth,
@@ -33,12 +37,12 @@ pub(crate) async fn insert(pg_client: &PgClient, height: f64, weight: f64, teeth
                 record_date
             )
         VALUES
-            ($1, $2, $3, $4, $5)
+            ($1, $2, $3, $4, $5, $6)
         RETURNING *;
     "#;
 
     let row = pg_client
-        .query_one(stmt, &[&height, &weight, &teeth, &head_circumference, &record_date])
+        .query_one(stmt, &[&child_id, &height, &weight, &teeth, &head_circumference, &record_date])
         .await?;
 
     let health_record = HealthRecord::from_row_ref(&row)?;
@@ -46,17 +50,20 @@ pub(crate) async fn insert(pg_client: &PgClient, height: f64, weight: f64, teeth
     Ok(health_record)
 }
 
-pub(crate) async fn select_all(client: &Client) -> Result<Vec<HealthRecord>, ServiceError> {
+#[instrument(skip_all)]
+pub(crate) async fn select_all(client: &Client, child_id: i64) -> Result<Vec<HealthRecord>, ServiceError> {
     let stmt = r#"
         SELECT
             *
         FROM
             health
+        WHERE
+            child_id = $1
         ORDER BY
             record_date DESC
     "#;
 
-    let rows = client.query(stmt, &[]).await?;
+    let rows = client.query(stmt, &[&child_id]).await?;
 
     return if rows.is_empty() {
         Err(
@@ -77,7 +84,8 @@ pub(crate) async fn select_all(client: &Client) -> Result<Vec<HealthRecord>, Ser
     }
 }
 
-pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64) -> Result<Vec<HealthRecord>, ServiceError> {
+#[instrument(skip_all)]
+pub(crate) async fn select_many(pc: &PgClient, child_id: i64, page_number: i64, page_size: i64) -> Result<Vec<HealthRecord>, ServiceError> {
     debug!("page number: {}, page size: {}",page_number, page_size);
 
     let stmt = r#"
@@ -85,6 +93,8 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
             *
         FROM
             health
+        WHERE
+            child_id = $3
         ORDER BY
             record_date DESC
         LIMIT
@@ -97,7 +107,7 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
     let offset = page_number * page_size;
 
     let rows = pc
-        .query(stmt, &[&offset, &page_size])
+        .query(stmt, &[&offset, &page_size, &child_id])
         .await?;
 
     return if rows.is_empty() {
@@ -119,10 +129,11 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
     };
 }
 
-pub(crate) async fn count(pc: &PgClient) -> Result<i64, ServiceError> {
-    let stmt = r#"SELECT COUNT(*) FROM health"#;
+#[instrument(skip_all)]
+pub(crate) async fn count(pc: &PgClient, child_id: i64) -> Result<i64, ServiceError> {
+    let stmt = r#"SELECT COUNT(*) FROM health WHERE child_id = $1"#;
 
-    let count = pc.query_one(stmt, &[])
+    let count = pc.query_one(stmt, &[&child_id])
         .await?
         .get(0);
 
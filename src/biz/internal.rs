@@ -1,8 +1,11 @@
 use actix_web::{HttpMessage, HttpRequest, web};
 use crate::AppState;
-use crate::infra::error::biz::BizKind::ClaimsNotFound;
+use crate::biz::child;
+use crate::infra::error::biz::BizKind::{ClaimsNotFound, PermissionDenied, ValidationFailed};
 use crate::infra::error::error::Kind::{BizError, InfraError};
-use crate::infra::error::error::ServiceError;
+use crate::infra::error::error::{FieldError, ServiceError};
+use crate::infra::middleware::authorize::{Grants, Permission};
+use crate::infra::middleware::family::Membership;
 use crate::infra::middleware::jwt::Claims;
 use deadpool_postgres::{Client as PgClient};
 
@@ -10,6 +13,8 @@ pub const MAX_PAGE_SIZE: i64 = 20;
 pub const MIN_PAGE_SIZE: i64 = 10;
 
 pub fn extract_user_id(req: HttpRequest) -> Result<i64, ServiceError> {
+    ensure_scope_covered(&req)?;
+
     let user_id = req.extensions()
         .get::<Claims>()
         .ok_or_else(|| {
@@ -22,6 +27,21 @@ pub fn extract_user_id(req: HttpRequest) -> Result<i64, ServiceError> {
     Ok(user_id)
 }
 
+pub fn extract_claims(req: HttpRequest) -> Result<Claims, ServiceError> {
+    ensure_scope_covered(&req)?;
+
+    let claims = req.extensions()
+        .get::<Claims>()
+        .cloned()
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(BizError(ClaimsNotFound))
+                .done()
+        })?;
+
+    Ok(claims)
+}
+
 pub async fn get_pg(app_state: &web::Data<AppState>) -> Result<PgClient, ServiceError> {
     app_state.pool
         .get()
@@ -33,3 +53,155 @@ pub async fn get_pg(app_state: &web::Data<AppState>) -> Result<PgClient, Service
                 .done()
         })
 }
+
+pub fn extract_membership(req: HttpRequest) -> Result<Membership, ServiceError> {
+    ensure_scope_covered(&req)?;
+
+    let membership = req.extensions()
+        .get::<Membership>()
+        .copied()
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(InfraError)
+                .message("Membership is not resolved, the scope is not wrapped by FamilyMiddleware")
+                .done()
+        })?;
+
+    Ok(membership)
+}
+
+/// Reject an API token with scopes unless an `Authorize` wrapping the route granted one of them.
+/// Routes not declaring a permission are off limits for such tokens.
+pub fn ensure_scope_covered(req: &HttpRequest) -> Result<(), ServiceError> {
+    let extensions = req.extensions();
+
+    let scoped = extensions
+        .get::<Claims>()
+        .is_some_and(|claims| claims.scopes.is_some());
+
+    if scoped && extensions.get::<Grants>().is_none() {
+        return Err(
+            ServiceError::build()
+                .belong(BizError(PermissionDenied))
+                .message("The scopes of the API token do not cover this route")
+                .done()
+        );
+    }
+
+    Ok(())
+}
+
+/// Check a permission beyond the one required by the `Authorize` wrapping the route.
+pub fn ensure_permission(req: &HttpRequest, permission: Permission) -> Result<(), ServiceError> {
+    let allowed = req.extensions()
+        .get::<Grants>()
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(InfraError)
+                .message("Grants are not resolved, the route is not wrapped by Authorize")
+                .done()
+        })?
+        .allows(permission);
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(
+            ServiceError::build()
+                .belong(BizError(PermissionDenied))
+                .message(&format!("{:?} permission is required", permission))
+                .done()
+        )
+    }
+}
+
+/// Reject access to a child outside the family.
+pub async fn ensure_child_access(client: &PgClient, family_id: i64, child_id: i64) -> Result<(), ServiceError> {
+    if child::recorder::belongs_to_family(client, child_id, family_id).await? {
+        Ok(())
+    } else {
+        Err(
+            ServiceError::build()
+                .belong(BizError(PermissionDenied))
+                .message("The child is outside the family of the user")
+                .done()
+        )
+    }
+}
+
+fn invalid(field: &str, reason: &str, message: &str) -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(ValidationFailed))
+        .message(message)
+        .fields(vec![FieldError::new(field, reason)])
+        .done()
+}
+
+/// Reject a page size outside `MIN_PAGE_SIZE..=MAX_PAGE_SIZE`.
+pub fn ensure_page_size(page_size: i64) -> Result<(), ServiceError> {
+    if page_size < MIN_PAGE_SIZE {
+        return Err(invalid("page_size", &format!("must be at least {}", MIN_PAGE_SIZE), "Page size is too small"));
+    }
+
+    if page_size > MAX_PAGE_SIZE {
+        return Err(invalid("page_size", &format!("must be at most {}", MAX_PAGE_SIZE), "Page size is too big"));
+    }
+
+    Ok(())
+}
+
+/// Reject a page number past the page after the last of `total_record` records.
+pub fn ensure_page_number(page_number: i64, page_size: i64, total_record: i64) -> Result<(), ServiceError> {
+    let last = total_record / page_size + 1;
+
+    if page_number > last {
+        return Err(invalid("page_number", &format!("must be at most {}", last), "Page number is too big"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::HttpMessage;
+    use actix_web::test::TestRequest;
+    use crate::infra::middleware::authorize::Grants;
+    use crate::infra::middleware::jwt::Claims;
+    use crate::infra::error::biz::BizKind::ValidationFailed;
+    use super::{ensure_page_number, ensure_page_size, ensure_scope_covered, MAX_PAGE_SIZE, MIN_PAGE_SIZE};
+
+    fn claims(scopes: Option<Vec<String>>) -> Claims {
+        Claims { sub: 1, exp: i64::MAX, jti: "api:1".to_string(), scopes }
+    }
+
+    #[test]
+    fn scoped_token_is_rejected_unless_authorized() {
+        let req = TestRequest::default().to_http_request();
+        req.extensions_mut().insert(claims(Some(vec!["article:write".to_string()])));
+        assert!(ensure_scope_covered(&req).is_err());
+
+        req.extensions_mut().insert(Grants::default());
+        assert!(ensure_scope_covered(&req).is_ok());
+    }
+
+    #[test]
+    fn unscoped_token_is_always_covered() {
+        let req = TestRequest::default().to_http_request();
+        req.extensions_mut().insert(claims(None));
+
+        assert!(ensure_scope_covered(&req).is_ok());
+    }
+
+    #[test]
+    fn page_out_of_range_names_the_field() {
+        assert!(ensure_page_size(MIN_PAGE_SIZE).is_ok());
+        assert!(ensure_page_size(MAX_PAGE_SIZE).is_ok());
+
+        let err = ensure_page_size(MAX_PAGE_SIZE + 1).unwrap_err();
+        assert_eq!(err.biz_kind(), Some(ValidationFailed));
+        assert_eq!(err.fields()[0].field, "page_size");
+
+        assert!(ensure_page_number(3, 10, 25).is_ok());
+        assert_eq!(ensure_page_number(4, 10, 25).unwrap_err().fields()[0].field, "page_number");
+    }
+}
@@ -1,8 +1,35 @@
 use serde::{Deserialize, Serialize};
+use crate::infra::error::biz::BizKind::ValidationFailed;
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::{FieldError, ServiceError};
 
 #[derive(Serialize, Debug, Deserialize)]
 pub struct JournalJson {
     pub title: String,
     pub content: String,
     pub images: Vec<String>,
-}
\ No newline at end of file
+}
+
+impl JournalJson {
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        if self.title.is_empty() {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("title must not be empty")
+                    .fields(vec![FieldError::new("title", "must not be empty")])
+                    .done()
+            );
+        }
+        if self.content.is_empty() {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("content must not be empty")
+                    .fields(vec![FieldError::new("content", "must not be empty")])
+                    .done()
+            );
+        }
+        Ok(())
+    }
+}
@@ -1,26 +1,24 @@
-use actix_web::{Error, get, HttpResponse, post, web};
+use actix_web::{Error, get, HttpRequest, HttpResponse, post, web};
 use crate::AppState;
-use crate::biz::courier::{Courier, HappyCourier, PaginateQuery, SadCourier};
-use crate::biz::internal::{get_pg, MAX_PAGE_SIZE, MIN_PAGE_SIZE};
+use crate::biz::courier::{Courier, HappyCourier, PaginateQuery};
+use crate::biz::internal::{ensure_page_number, ensure_page_size, extract_membership, get_pg};
 use crate::biz::journal::courier::{JournalJson};
 use super::recorder;
 
 #[post("")]
-pub async fn create_journal(app_state: web::Data<AppState>, body: web::Json<JournalJson>) -> Result<HttpResponse, Error> {
+pub async fn create_journal(req: HttpRequest, app_state: web::Data<AppState>, body: web::Json<JournalJson>) -> Result<HttpResponse, Error> {
+    let family_id = extract_membership(req)?.family_id;
+
     let pg_client = get_pg(&app_state).await?;
 
     let journal_body = body.into_inner();
 
-    if journal_body.content.is_empty() || journal_body.title.is_empty() {
-        return Ok(
-            HttpResponse::BadRequest().json(
-                SadCourier::brief("Journal content or title is empty")
-            )
-        );
-    }
+    // validate
+    journal_body.validate()?;
 
     let journal_record = recorder::insert(
         &pg_client,
+        family_id,
         &journal_body.title,
         &journal_body.content,
         &journal_body.images.iter().map(|image_url| image_url.as_str()).collect::<Vec<&str>>(),
@@ -38,35 +36,24 @@ pub async fn create_journal(app_state: web::Data<AppState>, body: web::Json<Jour
 }
 
 #[get("/paginated")]
-pub async fn read_paginated_journal(app_state: web::Data<AppState>, paginate_query: web::Query<PaginateQuery>) -> Result<HttpResponse, Error> {
+pub async fn read_paginated_journal(req: HttpRequest, app_state: web::Data<AppState>, paginate_query: web::Query<PaginateQuery>) -> Result<HttpResponse, Error> {
+    let family_id = extract_membership(req)?.family_id;
+
     let client = get_pg(&app_state).await?;
 
     // params validation
     let paginate = paginate_query.into_inner();
 
-    if paginate.page_size < MIN_PAGE_SIZE {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page size is too small")
-        ));
-    }
-
-    if paginate.page_size > MAX_PAGE_SIZE {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page size is too big")
-        ));
-    }
+    ensure_page_size(paginate.page_size)?;
 
-    let total_record = recorder::count(&client).await?;
+    let total_record = recorder::count(&client, family_id).await?;
 
-    if paginate.page_number > (total_record / paginate.page_size + 1) {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page number is too big")
-        ));
-    }
+    ensure_page_number(paginate.page_number, paginate.page_size, total_record)?;
 
 
     let journal_records = recorder::select_many(
         &client,
+        family_id,
         paginate.page_number,
         paginate.page_size,
     )
@@ -1,3 +1,3 @@
 pub mod handler;
 mod courier;
-mod recorder;
\ No newline at end of file
+pub(crate) mod recorder;
\ No newline at end of file
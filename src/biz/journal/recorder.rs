@@ -1,9 +1,10 @@
 use chrono::NaiveDateTime;
-use deadpool_postgres::Client as PgClient;
+use deadpool_postgres::{Client as PgClient, GenericClient};
 use log::debug;
 use serde::{Deserialize, Serialize};
 use tokio_pg_mapper::FromTokioPostgresRow;
 use tokio_pg_mapper_derive::PostgresMapper;
+use tracing::instrument;
 use crate::infra::error::biz::BizKind::DataNotFound;
 use crate::infra::error::error::ServiceError;
 use crate::infra::error::error::Kind::BizError;
@@ -12,6 +13,7 @@ use crate::infra::error::error::Kind::BizError;
 #[pg_mapper(table = "Journal")]
 pub struct JournalRecord {
     pub id: i64,
+    pub family_id: i64,
     pub title: String,
     pub content: String,
     pub images: Vec<String>,
@@ -19,17 +21,18 @@ pub struct JournalRecord {
     pub updated_at: NaiveDateTime,
 }
 
-pub(crate) async fn insert(pg_client: &PgClient, title: &str, content: &str, images: &[&str]) -> Result<JournalRecord, ServiceError> {
+#[instrument(skip_all)]
+pub(crate) async fn insert(pg_client: &impl GenericClient, family_id: i64, title: &str, content: &str, images: &[&str]) -> Result<JournalRecord, ServiceError> {
     let stmt = r#"
         INSERT INTO
-            journal(title, content, images)
+            journal(family_id, title, content, images)
         VALUES
-            ($1, $2, $3)
+            ($1, $2, $3, $4)
         RETURNING *;
     "#;
 
     let row = pg_client
-        .query_one(stmt, &[&title, &content, &images])
+        .query_one(stmt, &[&family_id, &title, &content, &images])
         .await?;
 
     let journal_record = JournalRecord::from_row_ref(&row)?;
@@ -38,7 +41,8 @@ pub(crate) async fn insert(pg_client: &PgClient, title: &str, content: &str, ima
 }
 
 
-pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64) -> Result<Vec<JournalRecord>, ServiceError> {
+#[instrument(skip_all)]
+pub(crate) async fn select_many(pc: &PgClient, family_id: i64, page_number: i64, page_size: i64) -> Result<Vec<JournalRecord>, ServiceError> {
     debug!("page number: {}, page size: {}",page_number, page_size);
 
     let offset = page_number * page_size;
@@ -46,6 +50,7 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
     let stmt = r#"
         SELECT
             id,
+            family_id,
             title,
             content,
             images,
@@ -53,6 +58,8 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
             updated_at
         FROM
             journal
+        WHERE
+            family_id = $3
         ORDER BY
             created_at DESC
         LIMIT
@@ -62,7 +69,7 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
     "#;
 
     let rows = pc
-        .query(stmt, &[&offset, &page_size])
+        .query(stmt, &[&offset, &page_size, &family_id])
         .await?;
 
     return if rows.is_empty() {
@@ -83,10 +90,11 @@ pub(crate) async fn select_many(pc: &PgClient, page_number: i64, page_size: i64)
     };
 }
 
-pub(crate) async fn count(pc: &PgClient) -> Result<i64, ServiceError> {
-    let stmt = r#"SELECT COUNT(*) FROM journal"#;
+#[instrument(skip_all)]
+pub(crate) async fn count(pc: &PgClient, family_id: i64) -> Result<i64, ServiceError> {
+    let stmt = r#"SELECT COUNT(*) FROM journal WHERE family_id = $1"#;
 
-    let count = pc.query_one(stmt, &[])
+    let count = pc.query_one(stmt, &[&family_id])
         .await?
         .get(0);
 
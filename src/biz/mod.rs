@@ -1,5 +1,5 @@
 pub(crate) mod courier;
-mod internal;
+pub(crate) mod internal;
 pub mod account;
 pub mod file;
 pub mod user;
@@ -13,4 +13,8 @@ pub mod article;
 pub mod article_category;
 pub mod draft;
 pub mod remark;
+pub mod family;
+pub mod child;
+pub mod probe;
+pub mod search;
 
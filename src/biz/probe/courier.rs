@@ -0,0 +1,37 @@
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Default)]
+pub struct CheckResp {
+    pub ok: bool,
+    // why the check failed, empty if ok
+    pub detail: String,
+}
+
+impl CheckResp {
+    pub fn passed() -> Self {
+        CheckResp {
+            ok: true,
+            detail: "".to_string(),
+        }
+    }
+
+    pub fn failed(detail: String) -> Self {
+        CheckResp {
+            ok: false,
+            detail,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ReadinessResp {
+    pub database: CheckResp,
+    pub image_dir: CheckResp,
+    pub document_dir: CheckResp,
+}
+
+impl ReadinessResp {
+    pub fn is_ready(&self) -> bool {
+        self.database.ok && self.image_dir.ok && self.document_dir.ok
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+use actix_web::{Error, get, HttpRequest, HttpResponse, web};
+use actix_web::http::header::AUTHORIZATION;
+use log::error;
+use openssl::memcmp;
+use prometheus::TEXT_FORMAT;
+use crate::AppState;
+use crate::biz::courier::{EmptyData, HappyCourier};
+use crate::biz::probe::courier::{CheckResp, ReadinessResp};
+use crate::biz::probe::recorder::ping;
+use crate::infra::error::biz::BizKind::TokenInvalid;
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::ServiceError;
+use crate::infra::metrics::METRICS;
+
+// the probes are unauthenticated, the causes of failures only go to the log
+async fn check_database(app_state: &AppState) -> CheckResp {
+    match ping(&app_state.pool).await {
+        Ok(()) => CheckResp::passed(),
+        Err(err) => {
+            error!("Readiness check of the database failed: {}", err);
+            CheckResp::failed("unavailable".to_string())
+        }
+    }
+}
+
+/// Write and remove a probe file, the directory may exist but be read-only.
+async fn check_writable(dir: &str) -> CheckResp {
+    let probe = Path::new(dir).join(format!(".readyz-{}", std::process::id()));
+
+    match tokio::fs::write(&probe, b"ok").await {
+        Ok(()) => {
+            let _ = tokio::fs::remove_file(&probe).await;
+            CheckResp::passed()
+        }
+        Err(err) => {
+            error!("Readiness check of {} failed: {}", dir, err);
+            CheckResp::failed("not writable".to_string())
+        }
+    }
+}
+
+/// Liveness, the process is up and serving.
+#[get("/healthz")]
+async fn healthz() -> Result<HttpResponse, Error> {
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::<EmptyData>::build()
+                .message("OK")
+                .done()
+        )
+    )
+}
+
+/// Readiness, the dependencies needed to serve requests are usable.
+#[get("/readyz")]
+async fn readyz(app_state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    let readiness = ReadinessResp {
+        database: check_database(&app_state).await,
+        image_dir: check_writable(&app_state.image_static_dir).await,
+        document_dir: check_writable(&app_state.document_static_dir).await,
+    };
+
+    let (mut response, message) = if readiness.is_ready() {
+        (HttpResponse::Ok(), "Ready")
+    } else {
+        (HttpResponse::ServiceUnavailable(), "Not ready")
+    };
+
+    Ok(
+        response.json(
+            HappyCourier::build()
+                .message(message)
+                .data(readiness)
+                .done()
+        )
+    )
+}
+
+/// Metrics in the Prometheus text format, behind `metrics.token`.
+#[get("/metrics")]
+async fn metrics(req: HttpRequest, app_state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    let expected = &app_state.metrics.token;
+
+    let given = req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or("");
+
+    // an empty token never matches, whatever got past the settings validation
+    if expected.is_empty() || given.len() != expected.len() || !memcmp::eq(given.as_bytes(), expected.as_bytes()) {
+        return Err(
+            ServiceError::build()
+                .belong(BizError(TokenInvalid))
+                .message("Metrics token is invalid")
+                .done()
+                .into()
+        );
+    }
+
+    let text = METRICS.render(&app_state.pool)?;
+
+    Ok(
+        HttpResponse::Ok()
+            .content_type(TEXT_FORMAT)
+            .body(text)
+    )
+}
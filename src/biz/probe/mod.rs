@@ -0,0 +1,3 @@
+pub mod handler;
+pub mod courier;
+pub mod recorder;
\ No newline at end of file
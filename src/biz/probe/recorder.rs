@@ -0,0 +1,38 @@
+use std::time::Duration;
+use deadpool_postgres::Pool;
+use tracing::instrument;
+use crate::infra::error::error::Kind::InfraError;
+use crate::infra::error::error::ServiceError;
+
+// a database that does not answer in time is as good as down
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Check out a connection and run a trivial query, proving the database is reachable.
+#[instrument(skip_all)]
+pub async fn ping(pool: &Pool) -> Result<(), ServiceError> {
+    let query = async {
+        let pc = pool
+            .get()
+            .await
+            .map_err(|err| {
+                ServiceError::build()
+                    .belong(InfraError)
+                    .because(Box::new(err))
+                    .message("Failed to check out a connection")
+                    .done()
+            })?;
+
+        pc.simple_query("SELECT 1;").await?;
+
+        Ok(())
+    };
+
+    tokio::time::timeout(PING_TIMEOUT, query)
+        .await
+        .map_err(|_| {
+            ServiceError::build()
+                .belong(InfraError)
+                .message(&format!("No answer within {}s", PING_TIMEOUT.as_secs()))
+                .done()
+        })?
+}
@@ -1,9 +1,9 @@
 use actix_web::{HttpResponse, post, Error, web, HttpRequest, get};
 use crate::AppState;
-use crate::biz::courier::{Courier, HappyCourier, PaginateQuery, SadCourier};
+use crate::biz::courier::{Courier, HappyCourier, PaginateQuery};
 use super::{courier, recorder};
 use crate::biz::internal;
-use crate::biz::internal::{get_pg, MAX_PAGE_SIZE, MIN_PAGE_SIZE};
+use crate::biz::internal::{ensure_page_number, ensure_page_size, get_pg};
 
 #[post("")]
 pub async fn create_remark(req: HttpRequest, app_state: web::Data<AppState>, req_body: web::Json<courier::RemarkCourier>) -> Result<HttpResponse, Error> {
@@ -27,32 +27,20 @@ pub async fn create_remark(req: HttpRequest, app_state: web::Data<AppState>, req
 
 
 #[get("/paginated/{parent_id}")]
-pub async fn read_remark_paginated(app_state: web::Data<AppState>, path: web::Path<i64>, paginate_query: web::Query<PaginateQuery>) -> Result<HttpResponse, Error> {
+pub async fn read_remark_paginated(req: HttpRequest, app_state: web::Data<AppState>, path: web::Path<i64>, paginate_query: web::Query<PaginateQuery>) -> Result<HttpResponse, Error> {
+    internal::ensure_scope_covered(&req)?;
+
     let client = get_pg(&app_state).await?;
 
     let paginate = paginate_query.into_inner();
     let parent_id = path.into_inner();
 
     // params validation
-    if paginate.page_size < MIN_PAGE_SIZE {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page size is too small")
-        ));
-    }
-
-    if paginate.page_size > MAX_PAGE_SIZE {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page size is too big")
-        ));
-    }
+    ensure_page_size(paginate.page_size)?;
 
     let total_record = recorder::count(&client, parent_id).await?;
 
-    if paginate.page_number > (total_record / paginate.page_size + 1) {
-        return Ok(HttpResponse::BadRequest().json(
-            SadCourier::brief("Page number is too big")
-        ));
-    }
+    ensure_page_number(paginate.page_number, paginate.page_size, total_record)?;
 
 
     let remark_records = recorder::select_paginated(
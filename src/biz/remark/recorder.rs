@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use tokio_pg_mapper::FromTokioPostgresRow;
 use tokio_pg_mapper_derive::PostgresMapper;
 use tokio_postgres::Client;
+use tracing::instrument;
 use crate::biz::remark::courier;
 use crate::infra::error::biz::BizKind::DataNotFound;
 use crate::infra::error::error::Kind::BizError;
@@ -22,6 +23,7 @@ pub struct RemarkRecorder {
 }
 
 
+#[instrument(skip_all)]
 pub(crate) async fn insert(client: &Client, remark_courier: courier::RemarkCourier, user_id: i64) -> Result<RemarkRecorder, ServiceError> {
     let stmt = r#"
         INSERT INTO
@@ -51,6 +53,7 @@ pub(crate) async fn insert(client: &Client, remark_courier: courier::RemarkCouri
     Ok(remark_record)
 }
 
+#[instrument(skip_all)]
 pub async fn select_all(client: &Client) -> Result<Vec<RemarkRecorder>, ServiceError> {
     let stmt = r#"
         SELECT
@@ -86,6 +89,7 @@ pub async fn select_all(client: &Client) -> Result<Vec<RemarkRecorder>, ServiceE
 }
 
 
+#[instrument(skip_all)]
 pub(crate) async fn select_paginated(client: &Client, parent: i64, page_number: i64, page_size: i64) -> Result<Vec<RemarkRecorder>, ServiceError> {
     debug!("page number: {}, page size: {}, parent id: {}",page_number, page_size, parent);
 
@@ -130,6 +134,7 @@ pub(crate) async fn select_paginated(client: &Client, parent: i64, page_number:
     };
 }
 
+#[instrument(skip_all)]
 pub(crate) async fn count(client: &Client, parent: i64) -> Result<i64, ServiceError> {
     let stmt = r#"SELECT COUNT(*) FROM remark WHERE parent = $1"#;
 
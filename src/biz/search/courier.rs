@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use crate::biz::search::recorder::SearchHit;
+use crate::infra::error::biz::BizKind::ValidationFailed;
+use crate::infra::error::error::{FieldError, ServiceError};
+use crate::infra::error::error::Kind::BizError;
+
+const MAX_QUERY_LENGTH: usize = 200;
+const DEFAULT_LIMIT: i64 = 10;
+const MAX_LIMIT: i64 = 20;
+
+fn default_limit() -> i64 {
+    DEFAULT_LIMIT
+}
+
+/// `q` follows the web search syntax: quoted phrases, `or`, and `-` to exclude a word.
+#[derive(Serialize, Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    // hits of each type
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+}
+
+impl SearchQuery {
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        let q = self.q.trim();
+
+        if q.is_empty() || q.chars().count() > MAX_QUERY_LENGTH {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("The search query is empty or too long")
+                    .fields(vec![FieldError::new("q", &format!("must have 1 to {} characters", MAX_QUERY_LENGTH))])
+                    .done()
+            );
+        }
+
+        if !(1..=MAX_LIMIT).contains(&self.limit) {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("The limit is out of range")
+                    .fields(vec![FieldError::new("limit", &format!("must be between 1 and {}", MAX_LIMIT))])
+                    .done()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Hits grouped by type, each group ranked best first.
+#[derive(Serialize, Debug, Default)]
+pub struct SearchResp {
+    pub articles: Vec<SearchHit>,
+    pub journals: Vec<SearchHit>,
+    pub wishes: Vec<SearchHit>,
+}
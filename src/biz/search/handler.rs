@@ -0,0 +1,35 @@
+use actix_web::{Error, get, HttpRequest, HttpResponse, web};
+use crate::AppState;
+use crate::biz::courier::HappyCourier;
+use crate::biz::internal::{extract_user_id, get_pg};
+use super::courier::{SearchQuery, SearchResp};
+use super::recorder;
+
+/// Articles, journals and wishes matching `q`, each limited to what the caller may read.
+#[get("")]
+pub async fn search(req: HttpRequest, app_state: web::Data<AppState>, query: web::Query<SearchQuery>) -> Result<HttpResponse, Error> {
+    let query = query.into_inner();
+
+    query.validate()?;
+
+    let user_id = extract_user_id(req)?;
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let q = query.q.trim();
+
+    let search_resp = SearchResp {
+        articles: recorder::search_articles(&pg_client, q, user_id, query.limit).await?,
+        journals: recorder::search_journals(&pg_client, q, user_id, query.limit).await?,
+        wishes: recorder::search_wishes(&pg_client, q, user_id, query.limit).await?,
+    };
+
+    Ok(
+        HttpResponse::Ok().json(
+            HappyCourier::build()
+                .message("Success to search")
+                .data(search_resp)
+                .done()
+        )
+    )
+}
@@ -0,0 +1,3 @@
+pub mod handler;
+pub mod courier;
+pub mod recorder;
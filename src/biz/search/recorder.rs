@@ -0,0 +1,140 @@
+use chrono::NaiveDateTime;
+use deadpool_postgres::Client as PgClient;
+use serde::{Deserialize, Serialize};
+use tokio_pg_mapper::FromTokioPostgresRow;
+use tokio_pg_mapper_derive::PostgresMapper;
+use tracing::instrument;
+use crate::infra::error::error::ServiceError;
+
+/// A matching article, journal or wish with the matched words marked in `snippet`.
+///
+/// The snippet is the stored text as is apart from the `<mark>` tags, clients escape it
+/// before rendering it as html.
+#[derive(Deserialize, PostgresMapper, Debug, Serialize)]
+#[pg_mapper(table = "SearchHit")]
+pub struct SearchHit {
+    pub id: i64,
+    pub title: Option<String>,
+    pub snippet: String,
+    pub rank: f32,
+    pub created_at: NaiveDateTime,
+}
+
+// ts_headline reads the whole text, so it is only run on the hits that are returned
+const HEADLINE_OPTIONS: &str = "StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=30, MinWords=10";
+
+async fn select_hits(pc: &PgClient, stmt: &str, q: &str, user_id: i64, limit: i64) -> Result<Vec<SearchHit>, ServiceError> {
+    let rows = pc
+        .query(stmt, &[&q, &user_id, &limit, &HEADLINE_OPTIONS])
+        .await?;
+
+    let mut hits = Vec::new();
+
+    for row in rows {
+        let hit = SearchHit::from_row_ref(&row)?;
+        hits.push(hit)
+    }
+
+    Ok(hits)
+}
+
+/// Articles every reader may see, and the unpublished ones of the caller.
+#[instrument(skip_all)]
+pub async fn search_articles(pc: &PgClient, q: &str, user_id: i64, limit: i64) -> Result<Vec<SearchHit>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            id,
+            title,
+            ts_headline('simple', CONCAT_WS(' ', summary, text), query, $4) AS snippet,
+            rank,
+            created_at
+        FROM
+            (
+                SELECT
+                    a.id, a.title, a.summary, a.text, a.created_at, query,
+                    ts_rank(a.search_vector, query) AS rank
+                FROM
+                    article a, websearch_to_tsquery('simple', $1) query
+                WHERE
+                    a.search_vector @@ query
+                    AND a.deleted_at IS NULL
+                    AND (
+                        (a.status = 'published' AND a.publish_at <= (NOW() AT TIME ZONE 'UTC'))
+                        OR a.author_id = $2
+                    )
+                ORDER BY
+                    rank DESC, a.id DESC
+                LIMIT $3
+            ) hits
+        ORDER BY
+            rank DESC, id DESC;
+    "#;
+
+    select_hits(pc, stmt, q, user_id, limit).await
+}
+
+/// Journals of the family of the caller, none without a family.
+#[instrument(skip_all)]
+pub async fn search_journals(pc: &PgClient, q: &str, user_id: i64, limit: i64) -> Result<Vec<SearchHit>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            id,
+            title,
+            ts_headline('simple', content, query, $4) AS snippet,
+            rank,
+            created_at
+        FROM
+            (
+                SELECT
+                    j.id, j.title, j.content, j.created_at, query,
+                    ts_rank(j.search_vector, query) AS rank
+                FROM
+                    journal j
+                    JOIN family_member m ON m.family_id = j.family_id,
+                    websearch_to_tsquery('simple', $1) query
+                WHERE
+                    j.search_vector @@ query
+                    AND m.account_id = $2
+                ORDER BY
+                    rank DESC, j.id DESC
+                LIMIT $3
+            ) hits
+        ORDER BY
+            rank DESC, id DESC;
+    "#;
+
+    select_hits(pc, stmt, q, user_id, limit).await
+}
+
+/// Wishes of the family of the caller, none without a family.
+#[instrument(skip_all)]
+pub async fn search_wishes(pc: &PgClient, q: &str, user_id: i64, limit: i64) -> Result<Vec<SearchHit>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            id,
+            NULL::VARCHAR AS title,
+            ts_headline('simple', content, query, $4) AS snippet,
+            rank,
+            created_at
+        FROM
+            (
+                SELECT
+                    w.id, w.content, w.created_at, query,
+                    ts_rank(w.search_vector, query) AS rank
+                FROM
+                    wish w
+                    JOIN family_member m ON m.family_id = w.family_id,
+                    websearch_to_tsquery('simple', $1) query
+                WHERE
+                    w.search_vector @@ query
+                    AND m.account_id = $2
+                ORDER BY
+                    rank DESC, w.id DESC
+                LIMIT $3
+            ) hits
+        ORDER BY
+            rank DESC, id DESC;
+    "#;
+
+    select_hits(pc, stmt, q, user_id, limit).await
+}
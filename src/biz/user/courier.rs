@@ -55,20 +55,20 @@ impl From<UserRecorder> for UserResp {
     }
 }
 
-impl Into<UserRecorder> for UserJson {
-    fn into(self) -> UserRecorder {
+impl From<UserJson> for UserRecorder {
+    fn from(value: UserJson) -> Self {
         UserRecorder {
             id: 0,
-            username: self.username,
+            username: value.username,
             password: "".to_string(),
-            mobile: self.mobile,
-            email: self.email,
-            avatar_url: self.avatar_url,
-            pronouns: self.pronouns,
-            birthday: self.birthday,
-            industry: self.industry,
-            location: self.location,
-            social_account: self.social_account,
+            mobile: value.mobile,
+            email: value.email,
+            avatar_url: value.avatar_url,
+            pronouns: value.pronouns,
+            birthday: value.birthday,
+            industry: value.industry,
+            location: value.location,
+            social_account: value.social_account,
             created_at: NaiveDateTime::default(),
             updated_at: NaiveDateTime::default(),
         }
@@ -4,7 +4,7 @@ use log::debug;
 use crate::biz::courier::{HappyCourier};
 use crate::biz::user::courier::{UserQuery, UserJson, UserResp, UserPublicCourier};
 use crate::biz::user::recorder::{query_account_by_id, select_many, update_account};
-use crate::biz::internal::{extract_user_id, get_pg};
+use crate::biz::internal::{ensure_scope_covered, extract_user_id, get_pg};
 use serde_querystring_actix;
 use serde_querystring_actix::QueryString;
 
@@ -27,7 +27,9 @@ async fn get_current_user(req: HttpRequest, app_state: web::Data<AppState>) -> R
 }
 
 #[get("/{user_id}")]
-async fn use_public_info(user_id: web::Path<i64>, app_state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+async fn use_public_info(req: HttpRequest, user_id: web::Path<i64>, app_state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    ensure_scope_covered(&req)?;
+
     let pg_client = get_pg(&app_state).await?;
 
     let user_id = user_id.into_inner();
@@ -48,7 +50,9 @@ async fn use_public_info(user_id: web::Path<i64>, app_state: web::Data<AppState>
 }
 
 #[get("/batch")]
-async fn get_user_info_in_batches(app_state: web::Data<AppState>, QueryString(user_query): QueryString<UserQuery>) -> Result<HttpResponse, Error> {
+async fn get_user_info_in_batches(req: HttpRequest, app_state: web::Data<AppState>, QueryString(user_query): QueryString<UserQuery>) -> Result<HttpResponse, Error> {
+    ensure_scope_covered(&req)?;
+
     let client = get_pg(&app_state).await?;
 
     let user_records = select_many(&client, user_query.user_ids.as_slice()).await?;
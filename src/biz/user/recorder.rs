@@ -1,10 +1,12 @@
 use deadpool_postgres::{Client as PgClient, Client, GenericClient};
 use tokio_pg_mapper::{Error, FromTokioPostgresRow};
+use tracing::instrument;
 use crate::biz::account::recorder::Account;
 use crate::infra::error::error::ServiceError;
 
 pub type UserRecorder = Account;
 
+#[instrument(skip_all)]
 pub async fn update_account<T: Into<UserRecorder>>(pc: &PgClient, user_id: i64, t: T) -> Result<UserRecorder, ServiceError> {
     let user = t.into();
 
@@ -44,6 +46,7 @@ pub async fn update_account<T: Into<UserRecorder>>(pc: &PgClient, user_id: i64,
     Ok(account_record)
 }
 
+#[instrument(skip_all)]
 pub async fn query_account_by_id(pc: &PgClient, user_id: i64) -> Result<UserRecorder, ServiceError> {
     let stmt = r#"
         SELECT
@@ -62,6 +65,7 @@ pub async fn query_account_by_id(pc: &PgClient, user_id: i64) -> Result<UserReco
     Ok(account_record)
 }
 
+#[instrument(skip_all)]
 pub async fn select_many(client: &Client, user_ids: &[i64]) -> Result<Vec<UserRecorder>, ServiceError> {
     let stmt = r#"
         SELECT
@@ -1,12 +1,30 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use crate::biz::wish::recorder::WishRecord;
+use crate::infra::error::biz::BizKind::ValidationFailed;
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::{FieldError, ServiceError};
 
 #[derive(Serialize, Debug, Deserialize)]
 pub struct WishJson {
     pub content: String,
 }
 
+impl WishJson {
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        if self.content.is_empty() {
+            return Err(
+                ServiceError::build()
+                    .belong(BizError(ValidationFailed))
+                    .message("content must not be empty")
+                    .fields(vec![FieldError::new("content", "must not be empty")])
+                    .done()
+            );
+        }
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Debug, Deserialize, Default)]
 pub struct WishResp {
     pub id: i64,
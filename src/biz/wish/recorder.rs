@@ -4,6 +4,7 @@ use log::debug;
 use serde::{Deserialize, Serialize};
 use tokio_pg_mapper::FromTokioPostgresRow;
 use tokio_pg_mapper_derive::PostgresMapper;
+use tracing::instrument;
 use crate::infra::error::biz::BizKind::DataNotFound;
 use crate::infra::error::error::ServiceError;
 use crate::infra::error::error::Kind::BizError;
@@ -12,20 +13,22 @@ use crate::infra::error::error::Kind::BizError;
 #[pg_mapper(table = "wish")]
 pub struct WishRecord {
     pub id: i64,
+    pub family_id: i64,
     pub user_id: i64,
     pub content: String,
     pub created_at: NaiveDateTime,
 }
 
-pub async fn insert(pg_client: &PgClient, user_id: i64, content: &str) -> Result<WishRecord, ServiceError> {
+#[instrument(skip_all)]
+pub async fn insert(pg_client: &PgClient, family_id: i64, user_id: i64, content: &str) -> Result<WishRecord, ServiceError> {
     let stmt = r#"
-        INSERT INTO wish(user_id, content)
-        VALUES ($1, $2)
+        INSERT INTO wish(family_id, user_id, content)
+        VALUES ($1, $2, $3)
         RETURNING *;
     "#;
 
     let row = pg_client
-        .query_one(stmt, &[&user_id, &content])
+        .query_one(stmt, &[&family_id, &user_id, &content])
         .await?;
 
     let wish = WishRecord::from_row_ref(&row)?;
@@ -34,7 +37,8 @@ pub async fn insert(pg_client: &PgClient, user_id: i64, content: &str) -> Result
 }
 
 
-pub async fn select_many(pc: &PgClient, page_number: i64, page_size: i64) -> Result<Vec<WishRecord>, ServiceError> {
+#[instrument(skip_all)]
+pub async fn select_many(pc: &PgClient, family_id: i64, page_number: i64, page_size: i64) -> Result<Vec<WishRecord>, ServiceError> {
     debug!("page number: {}, page size: {}",page_number, page_size);
 
     let offset = page_number * page_size;
@@ -42,11 +46,14 @@ pub async fn select_many(pc: &PgClient, page_number: i64, page_size: i64) -> Res
     let stmt = r#"
         SELECT
             id,
+            family_id,
             user_id,
             content,
             created_at
         FROM
             wish
+        WHERE
+            family_id = $3
         ORDER BY
             created_at DESC
         LIMIT
@@ -56,7 +63,7 @@ pub async fn select_many(pc: &PgClient, page_number: i64, page_size: i64) -> Res
     "#;
 
     let rows = pc
-        .query(stmt, &[&offset, &page_size])
+        .query(stmt, &[&offset, &page_size, &family_id])
         .await?;
 
     return if rows.is_empty() {
@@ -77,10 +84,11 @@ pub async fn select_many(pc: &PgClient, page_number: i64, page_size: i64) -> Res
     };
 }
 
-pub async fn count(pc: &PgClient) -> Result<i64, ServiceError> {
-    let stmt = r#"SELECT COUNT(*) FROM wish"#;
+#[instrument(skip_all)]
+pub async fn count(pc: &PgClient, family_id: i64) -> Result<i64, ServiceError> {
+    let stmt = r#"SELECT COUNT(*) FROM wish WHERE family_id = $1"#;
 
-    let count = pc.query_one(stmt, &[])
+    let count = pc.query_one(stmt, &[&family_id])
         .await?
         .get(0);
 
@@ -0,0 +1,387 @@
+use std::io;
+use std::io::BufRead;
+use chrono::{Days, NaiveDate, Utc};
+use deadpool_postgres::{Client as PgClient, Pool};
+use tokio_postgres::NoTls;
+use crate::biz::account::courier::{check_username, field_errors_to_result};
+use crate::biz::account::recorder::{add_account, insert_role, revoke_all_sessions, select, select_accounts, select_roles, update_password};
+use crate::biz::child::courier::ChildJson;
+use crate::biz::probe::recorder::ping;
+use crate::biz::user::recorder::update_account;
+use crate::biz::{child, family, health, journal};
+use crate::infra::config::Settings;
+use crate::infra::error::biz::BizKind::{DataNotFound, PermissionDenied};
+use crate::infra::error::error::Kind::BizError;
+use crate::infra::error::error::ServiceError;
+use crate::infra::init::project_env;
+use crate::infra::mail::build_mailer;
+use crate::infra::middleware::authorize::SiteRole;
+use crate::infra::migrate;
+use crate::infra::password::PasswordPolicy;
+
+const DEMO_USERNAME: &str = "demo";
+const DEMO_PASSWORD: &str = "Demo-Password-1";
+
+pub const USAGE: &str = r#"Usage: hammer-server [--config <file>] [command]
+
+Commands:
+    serve                   Run the server, the default
+    migrate up              Apply every pending migration
+    migrate down [steps]    Revert the latest migrations, 1 by default
+    migrate status          List migrations and whether they are applied
+    migrate baseline        Record the migrations of the schema from the old sql files as applied,
+                            for a database created before migrations were tracked
+    create-user <username> [--email <email>]
+                            Create an account, the password is read from stdin
+    reset-password <username>
+                            Set a new password read from stdin, logging out every session
+    grant-role <username> <admin|author>
+                            Grant a site wide role
+    list-users              List accounts with their roles
+    check-config            Validate the settings and check the database and mailer
+    seed-demo-data [--force]
+                            Create a demo account with a family, a child and some records,
+                            only in DEV or TEST unless forced
+    help                    Show this message"#;
+
+#[derive(Debug, PartialEq)]
+pub enum MigrateAction {
+    Up,
+    Down(usize),
+    Status,
+    Baseline,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Serve,
+    Migrate(MigrateAction),
+    CreateUser { username: String, email: Option<String> },
+    ResetPassword { username: String },
+    GrantRole { username: String, role: String },
+    ListUsers,
+    CheckConfig,
+    SeedDemoData { force: bool },
+    Help,
+}
+
+/// Parse the command line, without the program name. `--config` is left to the `Initializer`.
+pub fn parse(args: impl IntoIterator<Item=String>) -> Result<Command, String> {
+    let mut words = vec![];
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            args.next();
+        } else if !arg.starts_with("--config=") {
+            words.push(arg);
+        }
+    }
+
+    let words = words.iter().map(String::as_str).collect::<Vec<&str>>();
+
+    match words.as_slice() {
+        [] | ["serve"] => Ok(Command::Serve),
+        ["help"] | ["--help"] | ["-h"] => Ok(Command::Help),
+        ["migrate", "up"] => Ok(Command::Migrate(MigrateAction::Up)),
+        ["migrate", "down"] => Ok(Command::Migrate(MigrateAction::Down(1))),
+        ["migrate", "down", steps] => steps
+            .parse::<usize>()
+            .map(|steps| Command::Migrate(MigrateAction::Down(steps)))
+            .map_err(|_| format!("steps must be a number, got {}", steps)),
+        ["migrate", "status"] => Ok(Command::Migrate(MigrateAction::Status)),
+        ["migrate", "baseline"] => Ok(Command::Migrate(MigrateAction::Baseline)),
+        ["create-user", username] => Ok(Command::CreateUser { username: username.to_string(), email: None }),
+        ["create-user", username, "--email", email] => Ok(Command::CreateUser { username: username.to_string(), email: Some(email.to_string()) }),
+        ["reset-password", username] => Ok(Command::ResetPassword { username: username.to_string() }),
+        ["grant-role", username, role] => match SiteRole::parse(role) {
+            Some(_) => Ok(Command::GrantRole { username: username.to_string(), role: role.to_string() }),
+            None => Err(format!("role must be admin or author, got {}", role)),
+        },
+        ["list-users"] => Ok(Command::ListUsers),
+        ["check-config"] => Ok(Command::CheckConfig),
+        ["seed-demo-data"] => Ok(Command::SeedDemoData { force: false }),
+        ["seed-demo-data", "--force"] => Ok(Command::SeedDemoData { force: true }),
+        _ => Err(format!("Unknown command: {}", words.join(" "))),
+    }
+}
+
+fn to_io_error(err: ServiceError) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+async fn run_migrate(pg_client: &mut PgClient, action: MigrateAction) -> Result<(), ServiceError> {
+    match action {
+        MigrateAction::Up => {
+            let applied = migrate::up(pg_client).await?;
+
+            if applied.is_empty() {
+                println!("Database is up to date");
+            }
+            for migration in applied {
+                println!("Applied  {}", migration.name);
+            }
+        }
+        MigrateAction::Down(steps) => {
+            let reverted = migrate::down(pg_client, steps).await?;
+
+            if reverted.is_empty() {
+                println!("No migration to revert");
+            }
+            for migration in reverted {
+                println!("Reverted {}", migration.name);
+            }
+        }
+        MigrateAction::Baseline => {
+            let recorded = migrate::baseline(pg_client).await?;
+
+            if recorded.is_empty() {
+                println!("No migration to record");
+            }
+            for migration in recorded {
+                println!("Recorded {}", migration.name);
+            }
+        }
+        MigrateAction::Status => {
+            for (migration, applied_at) in migrate::status(pg_client).await? {
+                match applied_at {
+                    Some(applied_at) => println!("{:<32} applied at {}", migration.name, applied_at),
+                    None => println!("{:<32} pending", migration.name),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the password from the first line of stdin, so it does not show up in the shell history.
+fn read_password() -> Result<String, ServiceError> {
+    let mut password = String::new();
+
+    io::stdin().lock().read_line(&mut password)?;
+
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn user_not_found(username: &str) -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(DataNotFound))
+        .message(&format!("User {} does not exist", username))
+        .done()
+}
+
+/// Validation errors are printed field by field, they are what the operator has to fix.
+fn explain(err: ServiceError) -> ServiceError {
+    for field in err.fields() {
+        eprintln!("{} {}", field.field, field.reason);
+    }
+    err
+}
+
+async fn create_user(pg_client: &PgClient, policy: &PasswordPolicy, username: &str, email: Option<String>) -> Result<(), ServiceError> {
+    let password = read_password()?;
+
+    let mut errors = check_username(username);
+    errors.extend(policy.check("password", &password, username));
+    field_errors_to_result(errors).map_err(explain)?;
+
+    let mut account = add_account(pg_client, username, &password).await?;
+
+    if email.is_some() {
+        account.email = email;
+        account = update_account(pg_client, account.id, account).await?;
+    }
+
+    println!("Created user {} with id {}", account.username, account.id);
+
+    Ok(())
+}
+
+async fn reset_password(pg_client: &PgClient, policy: &PasswordPolicy, username: &str) -> Result<(), ServiceError> {
+    let account = select(pg_client, username)
+        .await?
+        .pop()
+        .ok_or_else(|| user_not_found(username))?;
+
+    let password = read_password()?;
+
+    field_errors_to_result(policy.check("password", &password, &account.username)).map_err(explain)?;
+
+    update_password(pg_client, account.id, &password).await?;
+
+    let revoked = revoke_all_sessions(pg_client, account.id).await?;
+
+    println!("Reset password of {}, revoked {} sessions", account.username, revoked);
+
+    Ok(())
+}
+
+async fn grant_role(pg_client: &PgClient, username: &str, role: &str) -> Result<(), ServiceError> {
+    let account = select(pg_client, username)
+        .await?
+        .pop()
+        .ok_or_else(|| user_not_found(username))?;
+
+    insert_role(pg_client, account.id, role).await?;
+
+    println!("Granted {} to {}", role, account.username);
+
+    Ok(())
+}
+
+async fn list_users(pg_client: &PgClient) -> Result<(), ServiceError> {
+    println!("{:<8} {:<32} {:<32} {:<16} created at", "id", "username", "email", "roles");
+
+    for account in select_accounts(pg_client).await? {
+        let roles = select_roles(pg_client, account.id).await?;
+
+        println!(
+            "{:<8} {:<32} {:<32} {:<16} {}",
+            account.id,
+            account.username,
+            account.email.as_deref().unwrap_or("-"),
+            if roles.is_empty() { "-".to_string() } else { roles.join(",") },
+            account.created_at
+        );
+    }
+
+    Ok(())
+}
+
+/// A demo account owning a family with a child, two weeks of health records and a journal entry.
+/// All or nothing, a failed seed leaves no half of it behind to block the next run.
+async fn seed_demo_data(pg_client: &mut PgClient, force: bool) -> Result<(), ServiceError> {
+    // the password is known to anyone reading this, keep it out of production
+    let env = project_env();
+    if !force && env != "DEV" && env != "TEST" {
+        return Err(
+            ServiceError::build()
+                .belong(BizError(PermissionDenied))
+                .message(&format!("Refusing to seed demo data in {}, pass --force to seed anyway", env))
+                .done()
+        );
+    }
+
+    if !select(pg_client, DEMO_USERNAME).await?.is_empty() {
+        println!("Demo data exists already");
+        return Ok(());
+    }
+
+    let tx = pg_client.transaction().await?;
+    let pg_client = &tx;
+
+    let account = add_account(pg_client, DEMO_USERNAME, DEMO_PASSWORD).await?;
+
+    let family = family::recorder::insert_in(pg_client, "Demo family", account.id).await?;
+
+    let today = Utc::now().date_naive();
+    let birthday = today.checked_sub_days(Days::new(300)).unwrap_or(NaiveDate::MIN);
+
+    let child = child::recorder::insert(pg_client, family.id, &ChildJson {
+        name: "Lily".to_string(),
+        birthday: Some(birthday),
+        sex: Some("female".to_string()),
+        avatar_url: None,
+    }).await?;
+
+    for day in 0..14u32 {
+        let record_date = today.checked_sub_days(Days::new((13 - day) as u64)).unwrap_or(today);
+        let growth = day as f64;
+
+        health::recorder::insert(
+            pg_client,
+            child.id,
+            72.0 + growth * 0.05,
+            8.9 + growth * 0.01,
+            4,
+            44.5 + growth * 0.01,
+            record_date,
+        ).await?;
+    }
+
+    journal::recorder::insert(pg_client, family.id, "First steps", "Lily took her first steps today!", &[]).await?;
+
+    tx.commit().await?;
+
+    println!("Created demo user {} with password {}", DEMO_USERNAME, DEMO_PASSWORD);
+
+    Ok(())
+}
+
+/// Validate what the server would only find out when serving: settings, the breached password list,
+/// the mailer and the database.
+pub async fn check_config(settings: &Settings) -> io::Result<()> {
+    println!("{}", settings.redacted());
+
+    if let Err(err) = settings.validate() {
+        eprintln!("Settings are invalid: {}", err);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, err.to_string()));
+    }
+    println!("Settings are valid");
+
+    PasswordPolicy::load(&settings.password)?;
+    println!("Password policy is loaded");
+
+    build_mailer(&settings.mail).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    println!("Mailer is configured");
+
+    let pool = settings.pg
+        .create_pool(None, NoTls)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+
+    ping(&pool).await.map_err(to_io_error)?;
+    println!("Postgres is reachable");
+
+    println!("Config is valid");
+
+    Ok(())
+}
+
+/// Run any command but `serve`, `help` and `check-config`.
+pub async fn run(pool: &Pool, settings: &Settings, command: Command) -> io::Result<()> {
+    let mut pg_client = pool
+        .get()
+        .await
+        .map_err(|err| io::Error::other(err.to_string()))?;
+
+    let policy = PasswordPolicy::load(&settings.password)?;
+
+    let result = match command {
+        Command::Migrate(action) => run_migrate(&mut pg_client, action).await,
+        Command::CreateUser { username, email } => create_user(&pg_client, &policy, &username, email).await,
+        Command::ResetPassword { username } => reset_password(&pg_client, &policy, &username).await,
+        Command::GrantRole { username, role } => grant_role(&pg_client, &username, &role).await,
+        Command::ListUsers => list_users(&pg_client).await,
+        Command::SeedDemoData { force } => seed_demo_data(&mut pg_client, force).await,
+        Command::Serve | Command::CheckConfig | Command::Help => Ok(()),
+    };
+
+    result.map_err(to_io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, MigrateAction, parse};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse(args(&[])), Ok(Command::Serve));
+        assert_eq!(parse(args(&["--config", "prod.yaml"])), Ok(Command::Serve));
+        assert_eq!(parse(args(&["migrate", "up", "--config=prod.yaml"])), Ok(Command::Migrate(MigrateAction::Up)));
+        assert_eq!(parse(args(&["migrate", "down", "3"])), Ok(Command::Migrate(MigrateAction::Down(3))));
+        assert!(parse(args(&["migrate", "down", "x"])).is_err());
+        assert!(parse(args(&["migrate"])).is_err());
+        assert_eq!(parse(args(&["migrate", "baseline"])), Ok(Command::Migrate(MigrateAction::Baseline)));
+        assert_eq!(
+            parse(args(&["create-user", "alice", "--email", "alice@example.com"])),
+            Ok(Command::CreateUser { username: "alice".to_string(), email: Some("alice@example.com".to_string()) })
+        );
+        assert!(parse(args(&["grant-role", "alice", "root"])).is_err());
+        assert_eq!(parse(args(&["seed-demo-data", "--force"])), Ok(Command::SeedDemoData { force: true }));
+    }
+}
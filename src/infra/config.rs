@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use actix_web::cookie::SameSite;
+use config::ConfigError;
 use deadpool_postgres::{Config as PgConfig};
 use serde::Deserialize;
 
@@ -12,14 +15,424 @@ pub struct Settings {
     pub log: LogConfig,
     pub path_to_cert_key: String,
     pub path_to_cert_file: String,
-    pub kimi_secret: String
+    pub kimi_secret: String,
+    #[serde(default)]
+    pub mail: MailConfig,
+    #[serde(default)]
+    pub password: PasswordConfig,
+    #[serde(default)]
+    pub login_guard: LoginGuardConfig,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub cookie: CookieConfig,
+    #[serde(default)]
+    pub token: TokenConfig,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub tracing: TracingConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    // seconds in-flight requests get to finish on shutdown
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: u64,
+    // apply pending migrations before serving
+    #[serde(default)]
+    pub auto_migrate: bool,
+}
+
+fn default_shutdown_timeout() -> u64 {
+    30
 }
 
 #[derive(Debug, Deserialize, Default, Clone)]
 pub struct LogConfig {
     pub level: String,
     pub color_mode: String, // always auto never
+    #[serde(default)]
+    pub format: String, // text json, text by default
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct MailConfig {
+    pub backend: String, // smtp file log
+    pub from: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: String,
+    pub smtp_password: String,
+    pub file_dir: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PasswordConfig {
+    pub min_length: usize,
+    // how many of lowercase, uppercase, digit and symbol a password must mix
+    pub min_classes: usize,
+    // optional file of known breached passwords, one per line
+    pub breached_list: String,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        PasswordConfig {
+            min_length: 8,
+            min_classes: 2,
+            breached_list: "".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LoginGuardConfig {
+    // failed logins older than this are forgotten
+    pub window_minutes: i64,
+    // failures of a username let through before backoff kicks in
+    pub free_attempts: i64,
+    // the delay doubles on every further failure, up to max_delay_seconds
+    pub base_delay_seconds: i64,
+    pub max_delay_seconds: i64,
+    // failures of a username that lock it for lockout_minutes
+    pub lockout_threshold: i64,
+    pub lockout_minutes: i64,
+    // failures from one ip, on any username, that lock the ip for lockout_minutes
+    pub ip_lockout_threshold: i64,
+}
+
+impl Default for LoginGuardConfig {
+    fn default() -> Self {
+        LoginGuardConfig {
+            window_minutes: 60,
+            free_attempts: 3,
+            base_delay_seconds: 1,
+            max_delay_seconds: 300,
+            lockout_threshold: 10,
+            lockout_minutes: 30,
+            ip_lockout_threshold: 50,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CorsConfig {
+    // no cross origin request is allowed if empty
+    pub allowed_origins: Vec<String>,
+}
+
+// the local frontend dev server, allowed before the origins were configurable
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: vec![
+                "https://localhost:5173".to_string(),
+                "https://127.0.0.1:5173".to_string(),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CookieConfig {
+    // empty for a host-only cookie
+    pub domain: String,
+    pub secure: bool,
+    pub same_site: String, // strict lax none
 }
 
+// the cookie set before it was configurable, the default CORS origins of the local frontend
+// dev server are another site than the server, so the cookie has to be sent cross site
+impl Default for CookieConfig {
+    fn default() -> Self {
+        CookieConfig {
+            domain: "127.0.0.1".to_string(),
+            secure: true,
+            same_site: "none".to_string(),
+        }
+    }
+}
+
+impl CookieConfig {
+    /// `None` if `same_site` is none of strict, lax and none.
+    pub fn same_site(&self) -> Option<SameSite> {
+        match self.same_site.to_lowercase().as_str() {
+            "strict" => Some(SameSite::Strict),
+            "lax" => Some(SameSite::Lax),
+            "none" => Some(SameSite::None),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct TokenConfig {
+    pub access_minutes: i64,
+    pub refresh_days: i64,
+}
 
-impl Settings {}
+impl Default for TokenConfig {
+    fn default() -> Self {
+        TokenConfig {
+            access_minutes: 15,
+            refresh_days: 30,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct TlsConfig {
+    // false to serve plain http, e.g. behind a tls terminating reverse proxy
+    pub enabled: bool,
+    // when set, plain http on this port is redirected to https
+    pub redirect_port: String,
+    // how often the certificate files are checked for changes
+    pub reload_interval_seconds: u64,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            enabled: true,
+            redirect_port: "".to_string(),
+            reload_interval_seconds: 60,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    // scrapers must send it as a bearer token
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct TracingConfig {
+    pub enabled: bool,
+    // OTLP over http, the full url of the traces resource
+    pub endpoint: String,
+    pub service_name: String,
+    // share of traces kept, from 0 to 1, a caller sampled per its traceparent header is always followed
+    pub sample_ratio: f64,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        TracingConfig {
+            enabled: false,
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            service_name: "hammer-server".to_string(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RateLimitPolicy {
+    // requests a client may burst
+    pub capacity: u32,
+    // requests a client is granted back per minute
+    pub refill_per_minute: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub backend: String, // memory postgres, postgres is shared by every instance
+    // by scope name, e.g. ai or file, scopes without a policy are not limited
+    pub policies: HashMap<String, RateLimitPolicy>,
+    // ips of the reverse proxies whose Forwarded or X-Forwarded-For header tells the client ip,
+    // for the login lockout as well
+    pub trusted_proxies: Vec<String>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let policies = [
+            // every request spends Kimi credit
+            ("ai", RateLimitPolicy { capacity: 10, refill_per_minute: 10 }),
+            ("file", RateLimitPolicy { capacity: 30, refill_per_minute: 30 }),
+            // three full-text queries a request
+            ("search", RateLimitPolicy { capacity: 30, refill_per_minute: 60 }),
+        ];
+
+        RateLimitConfig {
+            enabled: true,
+            backend: "memory".to_string(),
+            policies: policies
+                .into_iter()
+                .map(|(scope, policy)| (scope.to_string(), policy))
+                .collect(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+fn invalid(message: String) -> ConfigError {
+    ConfigError::Message(message)
+}
+
+const REDACTED: &str = "******";
+
+fn redact(secret: &mut String) {
+    if !secret.is_empty() {
+        *secret = REDACTED.to_string();
+    }
+}
+
+impl Settings {
+    /// Pretty debug output with every secret masked, safe to log.
+    pub fn redacted(&self) -> String {
+        let mut settings = self.clone();
+
+        redact(&mut settings.jwt_secret);
+        redact(&mut settings.kimi_secret);
+        redact(&mut settings.mail.smtp_password);
+        redact(&mut settings.metrics.token);
+        if let Some(password) = settings.pg.password.as_mut() {
+            redact(password);
+        }
+
+        format!("{:#?}", settings)
+    }
+
+    /// Catch settings that would only break at request time.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for origin in &self.cors.allowed_origins {
+            let rest = origin
+                .strip_prefix("https://")
+                .or_else(|| origin.strip_prefix("http://"))
+                .ok_or_else(|| invalid(format!("cors.allowed_origins: {} must start with http:// or https://", origin)))?;
+
+            if rest.is_empty() || rest.contains('/') {
+                return Err(invalid(format!("cors.allowed_origins: {} must be a bare origin without path", origin)));
+            }
+        }
+
+        if self.cookie.domain.contains("://") || self.cookie.domain.contains('/') {
+            return Err(invalid(format!("cookie.domain: {} must be a bare domain", self.cookie.domain)));
+        }
+
+        match self.cookie.same_site() {
+            None => {
+                return Err(invalid(format!("cookie.same_site: {} is none of strict, lax and none", self.cookie.same_site)));
+            }
+            // browsers drop SameSite=None cookies without the secure flag
+            Some(SameSite::None) if !self.cookie.secure => {
+                return Err(invalid("cookie.same_site: none requires cookie.secure".to_string()));
+            }
+            _ => {}
+        }
+
+        if self.tls.enabled && (self.path_to_cert_key.is_empty() || self.path_to_cert_file.is_empty()) {
+            return Err(invalid("tls.enabled requires path_to_cert_key and path_to_cert_file".to_string()));
+        }
+        if !self.tls.redirect_port.is_empty() && !self.tls.enabled {
+            return Err(invalid("tls.redirect_port requires tls.enabled".to_string()));
+        }
+        if self.tls.reload_interval_seconds == 0 {
+            return Err(invalid("tls.reload_interval_seconds must be greater than zero".to_string()));
+        }
+
+        if self.token.access_minutes <= 0 || self.token.refresh_days <= 0 {
+            return Err(invalid("token.access_minutes and token.refresh_days must be greater than zero".to_string()));
+        }
+        if self.token.access_minutes >= self.token.refresh_days * 24 * 60 {
+            return Err(invalid("token.access_minutes must be shorter than token.refresh_days".to_string()));
+        }
+
+        if self.metrics.enabled && self.metrics.token.is_empty() {
+            return Err(invalid("metrics.enabled requires metrics.token".to_string()));
+        }
+
+        if self.tracing.enabled && !self.tracing.endpoint.starts_with("http://") && !self.tracing.endpoint.starts_with("https://") {
+            return Err(invalid(format!("tracing.endpoint: {} must start with http:// or https://", self.tracing.endpoint)));
+        }
+        if !(0.0..=1.0).contains(&self.tracing.sample_ratio) {
+            return Err(invalid("tracing.sample_ratio must be between 0 and 1".to_string()));
+        }
+
+        if !["", "memory", "postgres"].contains(&self.rate_limit.backend.as_str()) {
+            return Err(invalid(format!("rate_limit.backend: {} is neither memory nor postgres", self.rate_limit.backend)));
+        }
+        for proxy in &self.rate_limit.trusted_proxies {
+            if proxy.parse::<std::net::IpAddr>().is_err() {
+                return Err(invalid(format!("rate_limit.trusted_proxies: {} is not an ip", proxy)));
+            }
+        }
+        for (scope, policy) in &self.rate_limit.policies {
+            if policy.capacity == 0 || policy.refill_per_minute == 0 {
+                return Err(invalid(format!("rate_limit.policies.{}: capacity and refill_per_minute must be greater than zero", scope)));
+            }
+        }
+
+        if !["", "text", "json"].contains(&self.log.format.as_str()) {
+            return Err(invalid(format!("log.format: {} is neither text nor json", self.log.format)));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Settings;
+
+    #[test]
+    fn validate_rejects_misconfigured_cookie_and_cors() {
+        let mut settings = Settings::default();
+        settings.tls.enabled = false;
+        assert!(settings.validate().is_ok());
+
+        settings.cors.allowed_origins = vec!["https://example.com/app".to_string()];
+        assert!(settings.validate().is_err());
+
+        settings.cors.allowed_origins = vec!["https://example.com".to_string()];
+        settings.cookie.same_site = "none".to_string();
+        settings.cookie.secure = false;
+        assert!(settings.validate().is_err());
+
+        settings.cookie.secure = true;
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn metrics_are_off_unless_a_token_guards_them() {
+        let mut settings = Settings::default();
+        settings.tls.enabled = false;
+        assert!(!settings.metrics.enabled);
+
+        settings.metrics.enabled = true;
+        assert!(settings.validate().is_err());
+
+        settings.metrics.token = "scraper-token".to_string();
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn redacted_masks_secrets() {
+        let mut settings = Settings {
+            jwt_secret: "jwt-secret-value".to_string(),
+            ..Default::default()
+        };
+        settings.pg.password = Some("pg-password-value".to_string());
+
+        let dump = settings.redacted();
+
+        assert!(!dump.contains("jwt-secret-value"));
+        assert!(!dump.contains("pg-password-value"));
+        assert!(dump.contains("******"));
+    }
+}
@@ -0,0 +1,67 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+use crate::infra::error::error::ServiceError;
+
+// Without 0/O and 1/I/L, so codes can be read aloud or typed by hand.
+const CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+
+/// Generate a random code of `len` characters drawn from an unambiguous alphabet.
+pub fn random_code(len: usize) -> Result<String, ServiceError> {
+    // bytes above the largest multiple of the alphabet size are dropped to avoid modulo bias
+    let limit = 256 / CODE_ALPHABET.len() * CODE_ALPHABET.len();
+
+    let mut code = String::with_capacity(len);
+    let mut buf = [0u8; 32];
+
+    while code.len() < len {
+        rand_bytes(&mut buf)?;
+
+        for b in buf.iter().map(|b| *b as usize).filter(|b| *b < limit) {
+            if code.len() == len {
+                break;
+            }
+            code.push(CODE_ALPHABET[b % CODE_ALPHABET.len()] as char);
+        }
+    }
+
+    Ok(code)
+}
+
+/// Generate an opaque url safe token carrying `bytes` bytes of randomness.
+pub fn random_token(bytes: usize) -> Result<String, ServiceError> {
+    let mut buf = vec![0u8; bytes];
+    rand_bytes(&mut buf)?;
+
+    Ok(URL_SAFE_NO_PAD.encode(buf))
+}
+
+/// Hex encoded SHA-256 digest, used to keep tokens and codes hashed at rest.
+pub fn sha256_hex(input: &str) -> String {
+    sha256(input.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{random_code, sha256_hex, CODE_ALPHABET};
+
+    #[test]
+    fn sha256_hex_digest() {
+        assert_eq!(
+            sha256_hex("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn random_code_uses_alphabet() {
+        let code = random_code(32).unwrap();
+
+        assert_eq!(code.len(), 32);
+        assert!(code.bytes().all(|b| CODE_ALPHABET.contains(&b)), "code: {}", code);
+    }
+}
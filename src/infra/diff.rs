@@ -0,0 +1,152 @@
+use serde::Serialize;
+
+// the lcs table grows with the product of the line counts, 1 MB at most per diff.
+// beyond it the changed lines are reported as replaced as a whole
+const MAX_TABLE_CELLS: usize = 250_000;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// A line kept, inserted into the new text or deleted from the old one.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+impl DiffLine {
+    fn new(op: DiffOp, text: &str) -> Self {
+        DiffLine { op, text: text.to_string() }
+    }
+}
+
+/// Line diff of `old` to `new` by their longest common subsequence, deletions before
+/// insertions where lines were replaced.
+pub fn line_diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // the common head and tail need no table
+    let head = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let tail = old[head..].iter().rev()
+        .zip(new[head..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_middle = &old[head..old.len() - tail];
+    let new_middle = &new[head..new.len() - tail];
+
+    let mut lines: Vec<DiffLine> = old[..head].iter().map(|line| DiffLine::new(DiffOp::Equal, line)).collect();
+
+    if (old_middle.len() + 1) * (new_middle.len() + 1) > MAX_TABLE_CELLS {
+        lines.extend(old_middle.iter().map(|line| DiffLine::new(DiffOp::Delete, line)));
+        lines.extend(new_middle.iter().map(|line| DiffLine::new(DiffOp::Insert, line)));
+    } else {
+        lines.extend(lcs_diff(old_middle, new_middle));
+    }
+
+    lines.extend(old[old.len() - tail..].iter().map(|line| DiffLine::new(DiffOp::Equal, line)));
+
+    lines
+}
+
+fn lcs_diff(old: &[&str], new: &[&str]) -> Vec<DiffLine> {
+    let width = new.len() + 1;
+
+    // lengths[i * width + j] is the lcs length of old[i..] and new[j..]
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::new(DiffOp::Equal, old[i]));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            lines.push(DiffLine::new(DiffOp::Delete, old[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::new(DiffOp::Insert, new[j]));
+            j += 1;
+        }
+    }
+
+    lines.extend(old[i..].iter().map(|line| DiffLine::new(DiffOp::Delete, line)));
+    lines.extend(new[j..].iter().map(|line| DiffLine::new(DiffOp::Insert, line)));
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{line_diff, DiffLine, DiffOp};
+
+    fn render(lines: &[DiffLine]) -> Vec<String> {
+        lines.iter()
+            .map(|line| {
+                let sign = match line.op {
+                    DiffOp::Equal => ' ',
+                    DiffOp::Insert => '+',
+                    DiffOp::Delete => '-',
+                };
+                format!("{}{}", sign, line.text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn changed_lines_are_deleted_then_inserted() {
+        let old = "first\nsecond\nthird\nfourth";
+        let new = "first\n2nd\nthird\nfourth\nfifth";
+
+        assert_eq!(
+            render(&line_diff(old, new)),
+            vec![" first", "-second", "+2nd", " third", " fourth", "+fifth"],
+        );
+    }
+
+    #[test]
+    fn moved_line_keeps_the_longest_common_part() {
+        let old = "a\nb\nc\nd";
+        let new = "b\nc\nd\na";
+
+        assert_eq!(render(&line_diff(old, new)), vec!["-a", " b", " c", " d", "+a"]);
+    }
+
+    #[test]
+    fn empty_sides() {
+        assert!(line_diff("", "").is_empty());
+        assert_eq!(render(&line_diff("", "new")), vec!["+new"]);
+        assert_eq!(render(&line_diff("old", "")), vec!["-old"]);
+        assert_eq!(render(&line_diff("same", "same")), vec![" same"]);
+    }
+
+    #[test]
+    fn long_texts_are_replaced_as_a_whole() {
+        let old = (0..1000).map(|i| format!("old {}\n", i)).collect::<String>();
+        let new = (0..1000).map(|i| format!("new {}\n", i)).collect::<String>();
+
+        let lines = line_diff(&format!("same\n{}", old), &format!("same\n{}", new));
+
+        assert_eq!(lines.len(), 2001);
+        assert_eq!(lines[0].op, DiffOp::Equal);
+        assert!(lines[1..1001].iter().all(|line| line.op == DiffOp::Delete));
+        assert!(lines[1001..].iter().all(|line| line.op == DiffOp::Insert));
+    }
+}
@@ -6,5 +6,25 @@ pub enum BizKind {
     ClaimsNotFound,
     TokenInvalid,
     AuthorizationFailed,
-    ValidationFailed
-}
\ No newline at end of file
+    ValidationFailed,
+    PermissionDenied,
+    DataConflict,
+    RateLimited,
+}
+
+impl BizKind {
+    /// Stable code sent to clients, they may branch on it, so never rename one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            BizKind::Other => "OTHER",
+            BizKind::DataNotFound => "DATA_NOT_FOUND",
+            BizKind::ClaimsNotFound => "CLAIMS_NOT_FOUND",
+            BizKind::TokenInvalid => "TOKEN_INVALID",
+            BizKind::AuthorizationFailed => "AUTHORIZATION_FAILED",
+            BizKind::ValidationFailed => "VALIDATION_FAILED",
+            BizKind::PermissionDenied => "PERMISSION_DENIED",
+            BizKind::DataConflict => "DATA_CONFLICT",
+            BizKind::RateLimited => "RATE_LIMITED",
+        }
+    }
+}
@@ -2,14 +2,17 @@ use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 use actix_multipart::MultipartError;
 use actix_web::{HttpResponse, ResponseError};
-use actix_web::error::BlockingError;
+use actix_web::error::{BlockingError, JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::http::StatusCode;
 use chrono::{NaiveDateTime, Utc};
 use jsonwebtoken::errors::ErrorKind;
+use serde::Serialize;
 use tokio_postgres::error::SqlState;
-use crate::biz::courier::{SadCourier};
+use crate::biz::courier::Courier;
 use crate::infra::error::biz::BizKind;
-use crate::infra::error::biz::BizKind::{DataNotFound, TokenInvalid, AuthorizationFailed, ValidationFailed};
+use crate::infra::error::biz::BizKind::{DataNotFound, TokenInvalid, AuthorizationFailed, ValidationFailed, PermissionDenied, DataConflict, RateLimited};
 use crate::infra::error::error::Kind::{BizError, InfraError};
+use crate::infra::middleware::context::{request_id, user_id};
 
 #[derive(Debug, PartialEq, Default)]
 pub enum Kind {
@@ -18,6 +21,22 @@ pub enum Kind {
     InfraError,
 }
 
+/// Tells which field of the submitted form is invalid and why.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub reason: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, reason: &str) -> Self {
+        FieldError {
+            field: field.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ServiceError {
     kind: Kind,
@@ -25,6 +44,7 @@ pub struct ServiceError {
     when: NaiveDateTime,
     because: Box<dyn Error>,
     message: String,
+    fields: Vec<FieldError>,
 }
 
 #[derive(Debug)]
@@ -71,14 +91,18 @@ impl ServiceError {
         &self.when
     }
 
-    pub fn because(&self) -> &Box<dyn Error> {
-        &self.because
+    pub fn because(&self) -> &dyn Error {
+        self.because.as_ref()
     }
 
     pub fn message(&self) -> &String {
         &self.message
     }
 
+    pub fn fields(&self) -> &Vec<FieldError> {
+        &self.fields
+    }
+
     /// If err belongs to `Kind::Biz` error, return the concrete kind,
     /// or return `None`
     pub fn biz_kind(&self) -> Option<BizKind> {
@@ -98,6 +122,7 @@ pub struct ServerErrorBuilder {
     who: Option<i64>,
     when: NaiveDateTime,
     message: String,
+    fields: Vec<FieldError>,
 }
 
 impl Display for ServerErrorBuilder {
@@ -118,9 +143,11 @@ impl Default for ServerErrorBuilder {
         Self {
             kind: Default::default(),
             because: Box::new(UnknownError {}),
-            who: None,
+            // the authenticated user of the request being served, if any
+            who: user_id(),
             when: Utc::now().naive_utc(),
             message: "".to_string(),
+            fields: vec![],
         }
     }
 }
@@ -150,12 +177,23 @@ impl ServerErrorBuilder {
     }
 
 
-    // pub fn who(self, who: i64) -> Self {
-    //     Self {
-    //         who: Some(who),
-    //         ..self
-    //     }
-    // }
+    /// Attach the errors of single form fields, they are sent back along with a `ValidationFailed` response.
+    pub fn fields(self, fields: Vec<FieldError>) -> Self {
+        Self {
+            fields,
+            ..self
+        }
+    }
+
+
+    /// Blame another user than the one of the current request, e.g. outside of a request.
+    #[allow(dead_code)]
+    pub fn who(self, who: i64) -> Self {
+        Self {
+            who: Some(who),
+            ..self
+        }
+    }
 
 
     pub fn done(self) -> ServiceError {
@@ -165,46 +203,80 @@ impl ServerErrorBuilder {
             who: self.who,
             when: self.when,
             message: self.message,
+            fields: self.fields,
+        }
+    }
+}
+
+/// Detail of an error response, sent in the `extra` of the `Courier`.
+#[derive(Serialize, Debug, Default)]
+pub struct ErrorDetail {
+    pub code: &'static str,
+    pub fields: Vec<FieldError>,
+    pub request_id: Option<String>,
+}
+
+pub const INTERNAL_ERROR_CODE: &str = "INTERNAL_ERROR";
+
+impl ServiceError {
+    /// Machine readable code of the error, internal errors all share one.
+    pub fn code(&self) -> &'static str {
+        match self.biz_kind() {
+            Some(kind @ (AuthorizationFailed | TokenInvalid | DataNotFound | ValidationFailed | PermissionDenied | DataConflict | RateLimited)) => kind.code(),
+            _ => INTERNAL_ERROR_CODE,
+        }
+    }
+
+    /// The message safe to show to the client. Business errors carry the one they were built with,
+    /// the message of an internal error may reveal too much, so it is never sent.
+    fn public_message(&self) -> &str {
+        let fallback = match self.biz_kind() {
+            Some(AuthorizationFailed) => "Password or username is incorrect",
+            Some(TokenInvalid) => "Token is invalid or expired",
+            Some(DataNotFound) => "Data queried is not found",
+            Some(ValidationFailed) => "Form data is invalid",
+            Some(PermissionDenied) => "Permission denied",
+            Some(DataConflict) => "Data already exists",
+            Some(RateLimited) => "Too many requests, try again later",
+            _ => return "Internal server error due to an unknown reason",
+        };
+
+        if self.message.is_empty() {
+            fallback
+        } else {
+            &self.message
         }
     }
 }
 
 impl ResponseError for ServiceError {
-    fn error_response(&self) -> HttpResponse {
+    fn status_code(&self) -> StatusCode {
         match self.biz_kind() {
-            // if none, it indicates that the error instance is not type of business error.
-            None => {
-                HttpResponse::InternalServerError().json(
-                    SadCourier::sorry()
-                )
-            }
-            // otherwise business error
-            Some(biz_err_kind) => {
-                match biz_err_kind {
-                    AuthorizationFailed => {
-                        HttpResponse::Unauthorized().json(
-                            SadCourier::brief("Password or username is incorrect")
-                        )
-                    }
-                    DataNotFound => {
-                        HttpResponse::NotFound().json(
-                            SadCourier::brief("Data queried is not found")
-                        )
-                    }
-                    ValidationFailed => {
-                        HttpResponse::BadRequest().json(
-                            SadCourier::brief("Form data is invalid")
-                        )
-                    }
-                    _ => {
-                        HttpResponse::InternalServerError().json(
-                            SadCourier::sorry()
-                        )
-                    }
-                }
-            }
+            Some(AuthorizationFailed) | Some(TokenInvalid) => StatusCode::UNAUTHORIZED,
+            Some(DataNotFound) => StatusCode::NOT_FOUND,
+            Some(ValidationFailed) => StatusCode::BAD_REQUEST,
+            Some(PermissionDenied) => StatusCode::FORBIDDEN,
+            Some(DataConflict) => StatusCode::CONFLICT,
+            Some(RateLimited) => StatusCode::TOO_MANY_REQUESTS,
+            // not a business error, or one that only a bug can cause
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(
+            Courier::<String, ErrorDetail>::build()
+                .message(self.public_message())
+                .extra(
+                    ErrorDetail {
+                        code: self.code(),
+                        fields: self.fields.clone(),
+                        request_id: request_id(),
+                    }
+                )
+                .done()
+        )
+    }
 }
 
 impl From<bcrypt::BcryptError> for ServiceError {
@@ -228,6 +300,12 @@ impl From<tokio_postgres::Error> for ServiceError {
                     .because(Box::new(err))
                     .done();
             }
+            if code == &SqlState::UNIQUE_VIOLATION {
+                return ServiceError::build()
+                    .belong(BizError(DataConflict))
+                    .because(Box::new(err))
+                    .done();
+            }
         }
 
         ServiceError::build()
@@ -304,4 +382,109 @@ impl From<MultipartError> for ServiceError {
             .message("Multipart Error")
             .done()
     }
-}
\ No newline at end of file
+}
+
+impl From<prometheus::Error> for ServiceError {
+    fn from(err: prometheus::Error) -> Self {
+        ServiceError::build()
+            .belong(InfraError)
+            .because(Box::new(err))
+            .message("Failed to encode metrics")
+            .done()
+    }
+}
+
+impl From<openssl::error::ErrorStack> for ServiceError {
+    fn from(err: openssl::error::ErrorStack) -> Self {
+        ServiceError::build()
+            .belong(InfraError)
+            .because(Box::new(err))
+            .message("Openssl error")
+            .done()
+    }
+}
+
+/// Serde names the offending field in backticks, e.g. "missing field `page_size`", when it knows it.
+fn named_field(message: &str) -> Option<&str> {
+    let (_, rest) = message.split_once("field `")?;
+    let (field, _) = rest.split_once('`')?;
+
+    Some(field)
+}
+
+/// A request the extractors of actix can not parse, registered as the error handler of
+/// `JsonConfig`, `QueryConfig` and `PathConfig`.
+fn malformed(err: Box<dyn Error>, message: String) -> ServiceError {
+    let fields = named_field(&message)
+        .map(|field| vec![FieldError::new(field, &message)])
+        .unwrap_or_default();
+
+    ServiceError::build()
+        .belong(BizError(ValidationFailed))
+        .because(err)
+        .message(&message)
+        .fields(fields)
+        .done()
+}
+
+impl From<JsonPayloadError> for ServiceError {
+    fn from(err: JsonPayloadError) -> Self {
+        let message = format!("Request body is invalid: {}", err);
+        malformed(Box::new(err), message)
+    }
+}
+
+impl From<QueryPayloadError> for ServiceError {
+    fn from(err: QueryPayloadError) -> Self {
+        let message = format!("Query string is invalid: {}", err);
+        malformed(Box::new(err), message)
+    }
+}
+
+impl From<PathError> for ServiceError {
+    fn from(err: PathError) -> Self {
+        let message = format!("Path is invalid: {}", err);
+        malformed(Box::new(err), message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+    use crate::infra::error::biz::BizKind::ValidationFailed;
+    use crate::infra::error::error::Kind::{BizError, InfraError};
+    use actix_web::web::Query;
+    use crate::biz::courier::PaginateQuery;
+    use super::{INTERNAL_ERROR_CODE, ServiceError};
+
+    #[test]
+    fn internal_errors_hide_their_message() {
+        let err = ServiceError::build()
+            .belong(InfraError)
+            .message("connection to 10.0.0.3 refused")
+            .done();
+
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(err.code(), INTERNAL_ERROR_CODE);
+        assert_eq!(err.public_message(), "Internal server error due to an unknown reason");
+
+        let err = ServiceError::build()
+            .belong(BizError(ValidationFailed))
+            .message("naps must be greater than zero")
+            .done();
+
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.code(), "VALIDATION_FAILED");
+        assert_eq!(err.public_message(), "naps must be greater than zero");
+    }
+
+    #[test]
+    fn malformed_query_names_the_field() {
+        let err: ServiceError = Query::<PaginateQuery>::from_query("page_number=1").unwrap_err().into();
+
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.code(), "VALIDATION_FAILED");
+        assert_eq!(err.fields()[0].field, "page_size");
+    }
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod error;
 pub mod biz;
 mod infra;
\ No newline at end of file
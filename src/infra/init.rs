@@ -1,11 +1,44 @@
-use std::env;
-use config::{Config, ConfigError, File, FileFormat};
+use std::{env, fs};
+use std::io::Write;
+use chrono::Utc;
+use config::{Config, ConfigError, Environment, File, FileFormat};
 use crate::infra::config::Settings;
 use std::str::FromStr;
 use env_logger::{Builder, WriteStyle};
-use log::LevelFilter;
+use log::{info, LevelFilter};
+use serde_json::json;
+use crate::infra::middleware::context::{request_id, user_id};
 
 const ENV_KEY: &str = "FAMILY_API_ENV";
+// FAMILY_API__PG__PASSWORD overrides pg.password
+const ENV_PREFIX: &str = "FAMILY_API";
+const ENV_SEPARATOR: &str = "__";
+const BASE_CONFIG_PATH: &str = "config/base.yaml";
+const LOCAL_CONFIG_PATH: &str = "config/local.yaml";
+const CONFIG_FLAG: &str = "--config";
+// each of them may instead be read from the file named by `<key>_file`, e.g. a Docker secret
+const SECRET_KEYS: [&str; 5] = ["jwt_secret", "kimi_secret", "pg.password", "mail.smtp_password", "metrics.token"];
+
+/// The value of `--config <path>` or `--config=<path>` among the command line arguments.
+fn config_path_from_args(args: impl IntoIterator<Item=String>) -> Option<String> {
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == CONFIG_FLAG {
+            return args.next();
+        }
+        if let Some(path) = arg.strip_prefix(&format!("{}=", CONFIG_FLAG)) {
+            return Some(path.to_string());
+        }
+    }
+
+    None
+}
+
+/// `DEV`, `TEST` or `PROD` as set by `FAMILY_API_ENV`, `DEV` when unset.
+pub fn project_env() -> String {
+    env::var(ENV_KEY).unwrap_or_else(|_| "DEV".to_string())
+}
 
 #[derive(Clone)]
 pub struct Initializer {
@@ -14,14 +47,16 @@ pub struct Initializer {
 }
 
 impl Default for Initializer {
+    /// The environment file is `--config` if given, or the one chosen by `FAMILY_API_ENV`.
     fn default() -> Self {
-        let project_env = env::var(ENV_KEY).unwrap_or_else(|_| "DEV".to_string());
+        let project_env = project_env();
 
-        let config_path = match project_env.as_str() {
-            "TEST" => String::from("config/test.yaml"),
-            "PROD" => String::from("config/prod.yaml"),
-            _ => String::from("config/dev.yaml"),
-        };
+        let config_path = config_path_from_args(env::args().skip(1))
+            .unwrap_or_else(|| match project_env.as_str() {
+                "TEST" => String::from("config/test.yaml"),
+                "PROD" => String::from("config/prod.yaml"),
+                _ => String::from("config/dev.yaml"),
+            });
 
         Initializer {
             config_file_path: config_path,
@@ -44,14 +79,37 @@ impl Initializer {
     }
 
 
+    /// Layers, each overriding the ones before: `config/base.yaml` (optional), the environment file,
+    /// `config/local.yaml` (optional), then `FAMILY_API__*` environment variables.
     pub fn init_settings(mut self) -> Result<Initializer, ConfigError> {
-        let settings = Config::builder()
-            .set_default("default", "1")?
+        let layered = Config::builder()
+            .add_source(File::new(BASE_CONFIG_PATH, FileFormat::Yaml).required(false))
             .add_source(File::new(self.config_file_path.as_str(), FileFormat::Yaml))
-            .set_override("override", "1")?
+            .add_source(File::new(LOCAL_CONFIG_PATH, FileFormat::Yaml).required(false))
+            .add_source(
+                Environment::with_prefix(ENV_PREFIX)
+                    .prefix_separator(ENV_SEPARATOR)
+                    .separator(ENV_SEPARATOR)
+                    .list_separator(",")
+                    .with_list_parse_key("cors.allowed_origins")
+                    .try_parsing(true)
+            )
             .build()?;
 
-        self.settings = settings.try_deserialize::<Settings>()?;
+        let mut builder = Config::builder().add_source(layered.clone());
+
+        for key in SECRET_KEYS {
+            let file_key = format!("{}_file", key);
+
+            if let Ok(path) = layered.get_string(&file_key) {
+                let secret = fs::read_to_string(&path)
+                    .map_err(|err| ConfigError::Message(format!("{}: failed to read {}: {}", file_key, path, err)))?;
+
+                builder = builder.set_override(key, secret.trim_end().to_string())?;
+            }
+        }
+
+        self.settings = builder.build()?.try_deserialize::<Settings>()?;
 
         Ok(self)
     }
@@ -71,14 +129,52 @@ impl Initializer {
         };
 
         builder.write_style(color_mode);
+
+        // the request and the user it is served for, `-` outside of a request
+        if self.settings.log.format == "json" {
+            builder.format(|buf, record| {
+                let line = json!({
+                    "ts": Utc::now().to_rfc3339(),
+                    "level": record.level().as_str(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                    "request_id": request_id(),
+                    "user_id": user_id(),
+                });
+
+                writeln!(buf, "{}", line)
+            });
+        } else {
+            builder.format(|buf, record| {
+                let style = buf.default_level_style(record.level());
+
+                writeln!(
+                    buf,
+                    "[{} {style}{:<5}{style:#} {}] [{} {}] {}",
+                    Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+                    record.level(),
+                    record.target(),
+                    request_id().unwrap_or_else(|| "-".to_string()),
+                    user_id().map_or_else(|| "-".to_string(), |id| id.to_string()),
+                    record.args()
+                )
+            });
+        }
+
         builder.init();
 
         self
     }
 
     pub fn must_init(self) -> Result<Self, ConfigError> {
-        let ini = self.init_settings()?
-            .init_logger();
+        let ini = self.init_settings()?;
+
+        ini.settings.validate()?;
+
+        let ini = ini.init_logger();
+
+        info!("Config file is {}", ini.config_file_path);
+        info!("Effective settings: {}", ini.settings.redacted());
 
         Ok(ini)
     }
@@ -86,7 +182,17 @@ impl Initializer {
 
 #[cfg(test)]
 mod tests {
-    use super::Initializer;
+    use super::{config_path_from_args, Initializer};
+
+    #[test]
+    fn config_flag() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(config_path_from_args(args(&["--config", "a.yaml"])), Some("a.yaml".to_string()));
+        assert_eq!(config_path_from_args(args(&["migrate", "--config=b.yaml"])), Some("b.yaml".to_string()));
+        assert_eq!(config_path_from_args(args(&["--config"])), None);
+        assert_eq!(config_path_from_args(args(&[])), None);
+    }
 
     #[test]
     #[ignore]
@@ -97,11 +203,11 @@ mod tests {
 
         let settings = initializer.settings();
 
-        if settings.ip != "127.0.0.1".to_string() {
+        if settings.ip != "127.0.0.1" {
             let err_msg = format!("ip is {}", settings.ip);
             return Err(err_msg);
         }
-        if settings.port != "8000".to_string() {
+        if settings.port != "8000" {
             let err_msg = format!("port is {}", settings.port);
             return Err(err_msg);
         }
@@ -0,0 +1,113 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use crate::infra::config::LoginGuardConfig;
+
+/// Recent failed logins of a username, since its last success, and of an ip.
+#[derive(Debug, Default, Clone)]
+pub struct LoginFailures {
+    pub username_failures: i64,
+    pub username_last_failed_at: Option<NaiveDateTime>,
+    pub ip_failures: i64,
+    pub ip_last_failed_at: Option<NaiveDateTime>,
+}
+
+/// Decides how long a client has to wait before it may try to login again.
+#[derive(Debug, Clone)]
+pub struct LoginGuard {
+    config: LoginGuardConfig,
+}
+
+impl LoginGuard {
+    pub fn new(config: LoginGuardConfig) -> Self {
+        LoginGuard { config }
+    }
+
+    pub fn window(&self) -> TimeDelta {
+        TimeDelta::minutes(self.config.window_minutes)
+    }
+
+    /// Wait imposed on a username after `failures` failed logins in a row.
+    fn username_delay(&self, failures: i64) -> TimeDelta {
+        let config = &self.config;
+
+        if failures >= config.lockout_threshold {
+            return TimeDelta::minutes(config.lockout_minutes);
+        }
+        if failures < config.free_attempts {
+            return TimeDelta::zero();
+        }
+
+        // capped exponent, the delay is capped by max_delay_seconds long before anyway
+        let exponent = (failures - config.free_attempts).min(30) as u32;
+        let delay = config.base_delay_seconds.saturating_mul(1 << exponent);
+
+        TimeDelta::seconds(delay.min(config.max_delay_seconds))
+    }
+
+    fn ip_delay(&self, failures: i64) -> TimeDelta {
+        if failures >= self.config.ip_lockout_threshold {
+            TimeDelta::minutes(self.config.lockout_minutes)
+        } else {
+            TimeDelta::zero()
+        }
+    }
+
+    /// Seconds to wait before the next login attempt, `None` if it may go ahead right now.
+    pub fn retry_after(&self, failures: &LoginFailures, now: NaiveDateTime) -> Option<i64> {
+        let wait_until = |last_failed_at: Option<NaiveDateTime>, delay: TimeDelta| {
+            last_failed_at.map(|at| at + delay)
+        };
+
+        let username_wait = wait_until(failures.username_last_failed_at, self.username_delay(failures.username_failures));
+        let ip_wait = wait_until(failures.ip_last_failed_at, self.ip_delay(failures.ip_failures));
+
+        [username_wait, ip_wait]
+            .into_iter()
+            .flatten()
+            .map(|until| (until - now).num_seconds())
+            .filter(|secs| *secs > 0)
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+    use crate::infra::config::LoginGuardConfig;
+    use super::{LoginFailures, LoginGuard};
+
+    fn guard() -> LoginGuard {
+        LoginGuard::new(LoginGuardConfig::default())
+    }
+
+    #[test]
+    fn delay_doubles_then_locks() {
+        let guard = guard();
+
+        assert_eq!(guard.username_delay(2), TimeDelta::zero());
+        assert_eq!(guard.username_delay(3), TimeDelta::seconds(1));
+        assert_eq!(guard.username_delay(5), TimeDelta::seconds(4));
+        assert_eq!(guard.username_delay(9), TimeDelta::seconds(64));
+        assert_eq!(guard.username_delay(10), TimeDelta::minutes(30));
+    }
+
+    #[test]
+    fn retry_after_counts_from_last_failure() {
+        let guard = guard();
+        let now = Utc::now().naive_utc();
+
+        let failures = LoginFailures {
+            username_failures: 10,
+            username_last_failed_at: Some(now - TimeDelta::minutes(10)),
+            ..Default::default()
+        };
+        assert_eq!(guard.retry_after(&failures, now), Some(20 * 60));
+
+        let failures = LoginFailures {
+            username_failures: 1,
+            username_last_failed_at: Some(now),
+            ip_failures: 3,
+            ip_last_failed_at: Some(now),
+        };
+        assert_eq!(guard.retry_after(&failures, now), None);
+    }
+}
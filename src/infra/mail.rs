@@ -0,0 +1,154 @@
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::Arc;
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use log::info;
+use crate::infra::config::MailConfig;
+use crate::infra::error::error::Kind::InfraError;
+use crate::infra::error::error::ServiceError;
+
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers mails to users, picked by `mail.backend` in the settings.
+#[async_trait(?Send)]
+pub trait Mailer: Debug + Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), ServiceError>;
+}
+
+fn mail_error(err: Box<dyn std::error::Error>, message: &str) -> ServiceError {
+    ServiceError::build()
+        .belong(InfraError)
+        .because(err)
+        .message(message)
+        .done()
+}
+
+#[derive(Debug)]
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &MailConfig) -> Result<Self, String> {
+        let from = config.from
+            .parse::<Mailbox>()
+            .map_err(|err| format!("mail.from is invalid: {}", err))?;
+
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)
+            .map_err(|err| format!("mail.smtp_host is invalid: {}", err))?
+            .port(config.smtp_port)
+            .credentials(Credentials::new(config.smtp_username.clone(), config.smtp_password.clone()))
+            .build();
+
+        Ok(SmtpMailer { from, transport })
+    }
+}
+
+#[async_trait(?Send)]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: Mail) -> Result<(), ServiceError> {
+        let to = mail.to
+            .parse::<Mailbox>()
+            .map_err(|err| mail_error(Box::new(err), "Recipient address is invalid"))?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(mail.subject)
+            .body(mail.body)
+            .map_err(|err| mail_error(Box::new(err), "Failed to build mail"))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|err| mail_error(Box::new(err), "Failed to send mail by smtp"))?;
+
+        Ok(())
+    }
+}
+
+/// Writes every mail into a file under `dir`, for development and tests.
+#[derive(Debug)]
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileMailer { dir: dir.into() }
+    }
+}
+
+#[async_trait(?Send)]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), ServiceError> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        let now = Utc::now();
+        let filename = format!("{}-{}.eml", now.format("%Y%m%d%H%M%S%f"), sanitize_filename::sanitize(&mail.to));
+        let content = format!("To: {}\nSubject: {}\nDate: {}\n\n{}\n", mail.to, mail.subject, now.to_rfc2822(), mail.body);
+
+        tokio::fs::write(self.dir.join(filename), content).await?;
+
+        Ok(())
+    }
+}
+
+/// Only logs mails, nothing leaves the server.
+#[derive(Debug)]
+pub struct LogMailer;
+
+#[async_trait(?Send)]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: Mail) -> Result<(), ServiceError> {
+        info!("mail to: {} | subject: {}\n{}", mail.to, mail.subject, mail.body);
+
+        Ok(())
+    }
+}
+
+pub fn build_mailer(config: &MailConfig) -> Result<Arc<dyn Mailer>, String> {
+    match config.backend.as_str() {
+        "smtp" => Ok(Arc::new(SmtpMailer::new(config)?)),
+        "file" => Ok(Arc::new(FileMailer::new(&config.file_dir))),
+        "log" | "" => Ok(Arc::new(LogMailer)),
+        other => Err(format!("unknown mail backend: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileMailer, Mail, Mailer};
+
+    #[actix_web::test]
+    async fn file_mailer_writes_mail() {
+        let dir = std::env::temp_dir().join(format!("hammer-mail-{}", std::process::id()));
+
+        FileMailer::new(&dir)
+            .send(Mail {
+                to: "someone@example.com".to_string(),
+                subject: "Hello".to_string(),
+                body: "code: ABCD2345".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let entry = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+        let content = std::fs::read_to_string(entry.path()).unwrap();
+
+        assert!(content.contains("To: someone@example.com"));
+        assert!(content.contains("code: ABCD2345"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
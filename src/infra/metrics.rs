@@ -0,0 +1,126 @@
+use std::sync::LazyLock;
+use deadpool_postgres::Pool;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use crate::infra::error::error::ServiceError;
+
+// the upstream AI answers in seconds rather than milliseconds, so its buckets reach further
+const AI_BUCKETS: &[f64] = &[0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 40.0, 60.0];
+
+// routes not matched by any resource share one label, unknown paths must not grow the label set
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Every metric of the server, registered in a registry of its own.
+pub struct Metrics {
+    registry: Registry,
+    pub http_request_duration: HistogramVec,
+    pub service_errors: IntCounterVec,
+    pub upload_bytes: IntCounterVec,
+    pub ai_request_duration: HistogramVec,
+    pg_pool_max_size: IntGauge,
+    pg_pool_size: IntGauge,
+    pg_pool_available: IntGauge,
+    pg_pool_waiting: IntGauge,
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Time to serve a request, by route pattern"),
+            &["method", "route", "status"],
+        ).unwrap();
+
+        let service_errors = IntCounterVec::new(
+            Opts::new("service_errors_total", "Errors turned into responses, by BizKind or InfraError"),
+            &["kind"],
+        ).unwrap();
+
+        let upload_bytes = IntCounterVec::new(
+            Opts::new("upload_bytes_total", "Bytes of uploaded files written to disk"),
+            &["kind"],
+        ).unwrap();
+
+        let ai_request_duration = HistogramVec::new(
+            HistogramOpts::new("ai_request_duration_seconds", "Time until the upstream AI api answered, by status code")
+                .buckets(AI_BUCKETS.to_vec()),
+            &["status"],
+        ).unwrap();
+
+        let pg_pool_max_size = IntGauge::new("pg_pool_max_size", "Connections the pool may open").unwrap();
+        let pg_pool_size = IntGauge::new("pg_pool_size", "Connections the pool has open").unwrap();
+        let pg_pool_available = IntGauge::new("pg_pool_available", "Open connections idle in the pool").unwrap();
+        let pg_pool_waiting = IntGauge::new("pg_pool_waiting", "Requests waiting for a connection").unwrap();
+
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(service_errors.clone())).unwrap();
+        registry.register(Box::new(upload_bytes.clone())).unwrap();
+        registry.register(Box::new(ai_request_duration.clone())).unwrap();
+        registry.register(Box::new(pg_pool_max_size.clone())).unwrap();
+        registry.register(Box::new(pg_pool_size.clone())).unwrap();
+        registry.register(Box::new(pg_pool_available.clone())).unwrap();
+        registry.register(Box::new(pg_pool_waiting.clone())).unwrap();
+
+        Metrics {
+            registry,
+            http_request_duration,
+            service_errors,
+            upload_bytes,
+            ai_request_duration,
+            pg_pool_max_size,
+            pg_pool_size,
+            pg_pool_available,
+            pg_pool_waiting,
+        }
+    }
+
+    pub fn count_error(&self, err: &ServiceError) {
+        let kind = match err.biz_kind() {
+            Some(biz_kind) => format!("{:?}", biz_kind),
+            None => "InfraError".to_string(),
+        };
+
+        self.service_errors.with_label_values(&[kind.as_str()]).inc();
+    }
+
+    /// The pool is only looked at when scraped, it keeps its own counts anyway.
+    fn observe_pool(&self, pool: &Pool) {
+        let status = pool.status();
+
+        self.pg_pool_max_size.set(status.max_size as i64);
+        self.pg_pool_size.set(status.size as i64);
+        self.pg_pool_available.set(status.available as i64);
+        self.pg_pool_waiting.set(status.waiting as i64);
+    }
+
+    /// Every metric in the Prometheus text format.
+    pub fn render(&self, pool: &Pool) -> Result<String, ServiceError> {
+        self.observe_pool(pool);
+
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus::{Encoder, TextEncoder};
+    use super::METRICS;
+
+    #[test]
+    fn metrics_are_rendered_with_labels() {
+        METRICS.upload_bytes.with_label_values(&["image"]).inc_by(1024);
+        METRICS.http_request_duration.with_label_values(&["GET", "/journal", "200"]).observe(0.01);
+
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&METRICS.registry.gather(), &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+
+        assert!(text.contains(r#"upload_bytes_total{kind="image"} 1024"#), "{}", text);
+        assert!(text.contains(r#"http_request_duration_seconds_count{method="GET",route="/journal",status="200"} 1"#), "{}", text);
+    }
+}
@@ -0,0 +1,289 @@
+use std::rc::Rc;
+use actix_web::{dev::{Service, ServiceRequest, ServiceResponse, Transform}, Error, HttpMessage, web};
+use actix_web::dev::forward_ready;
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use log::debug;
+use crate::AppState;
+use crate::biz::account::recorder::select_roles;
+use crate::biz::family::courier::FamilyRole;
+use crate::biz::family::recorder::select_membership;
+use crate::biz::internal::get_pg;
+use crate::infra::error::biz::BizKind::{ClaimsNotFound, PermissionDenied};
+use crate::infra::error::error::Kind::{BizError, InfraError};
+use crate::infra::error::error::ServiceError;
+use crate::infra::middleware::jwt::Claims;
+
+/// Site wide roles stored in `account_role`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiteRole {
+    Admin,
+    Author,
+}
+
+impl SiteRole {
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "admin" => Some(SiteRole::Admin),
+            "author" => Some(SiteRole::Author),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// publish articles
+    WriteArticle,
+    /// set `is_trending`, `is_insight` and `is_recommend` of articles
+    CurateArticle,
+    /// maintain `article_category`
+    EditCategory,
+    /// add or edit the children of the family
+    ManageChild,
+    /// post health, diet and behavior records of the children
+    WriteChildRecord,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 5] = [
+        Permission::WriteArticle,
+        Permission::CurateArticle,
+        Permission::EditCategory,
+        Permission::ManageChild,
+        Permission::WriteChildRecord,
+    ];
+
+    /// Name of the permission when used as a scope of an API token.
+    pub fn scope(&self) -> &'static str {
+        match self {
+            Permission::WriteArticle => "article:write",
+            Permission::CurateArticle => "article:curate",
+            Permission::EditCategory => "category:edit",
+            Permission::ManageChild => "child:manage",
+            Permission::WriteChildRecord => "record:write",
+        }
+    }
+
+    pub fn from_scope(scope: &str) -> Option<Self> {
+        Permission::ALL
+            .into_iter()
+            .find(|permission| permission.scope() == scope)
+    }
+}
+
+/// Permissions granted to the caller, resolved from the site roles and the family role.
+#[derive(Debug, Clone, Default)]
+pub struct Grants {
+    permissions: Vec<Permission>,
+}
+
+impl Grants {
+    pub fn resolve(site_roles: &[SiteRole], family_role: Option<FamilyRole>) -> Self {
+        let mut permissions = Vec::new();
+
+        for site_role in site_roles {
+            match site_role {
+                SiteRole::Admin => permissions.extend([
+                    Permission::WriteArticle,
+                    Permission::CurateArticle,
+                    Permission::EditCategory,
+                ]),
+                SiteRole::Author => permissions.push(Permission::WriteArticle),
+            }
+        }
+
+        match family_role {
+            Some(FamilyRole::Owner) | Some(FamilyRole::Parent) => permissions.extend([
+                Permission::ManageChild,
+                Permission::WriteChildRecord,
+            ]),
+            Some(FamilyRole::Caregiver) => permissions.push(Permission::WriteChildRecord),
+            Some(FamilyRole::Viewer) | None => {}
+        }
+
+        Grants { permissions }
+    }
+
+    /// Narrow the grants down to the scopes of an API token.
+    pub fn limit(self, scopes: &[String]) -> Self {
+        let permissions = self.permissions
+            .into_iter()
+            .filter(|permission| scopes.iter().any(|scope| scope == permission.scope()))
+            .collect();
+
+        Grants { permissions }
+    }
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
+}
+
+/// Rejects the request with `PermissionDenied` unless the caller holds the required
+/// permission. The resolved [`Grants`] are stored in the request extensions
+/// for handlers that need finer checks.
+///
+/// Must be wrapped inside `JwtMiddleware`, since it relies on the `Claims`.
+pub struct Authorize {
+    required: Permission,
+}
+
+impl Authorize {
+    pub fn require(required: Permission) -> Self {
+        Authorize { required }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Authorize
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AuthorizeService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthorizeService { service: Rc::new(service), required: self.required }))
+    }
+}
+
+pub struct AuthorizeService<S> {
+    service: Rc<S>,
+    required: Permission,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthorizeService<S>
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let required = self.required;
+
+        Box::pin(async move {
+            let cached = req.extensions().get::<Grants>().cloned();
+
+            let grants = match cached {
+                Some(grants) => grants,
+                None => {
+                    let grants = resolve_grants(&req).await?;
+                    req.extensions_mut().insert(grants.clone());
+                    grants
+                }
+            };
+
+            if !grants.allows(required) {
+                debug!("permission {:?} denied, grants: {:?}", required, grants);
+                return Err(
+                    ServiceError::build()
+                        .belong(BizError(PermissionDenied))
+                        .message(&format!("{:?} permission is required", required))
+                        .done()
+                        .into()
+                );
+            }
+
+            service.call(req).await
+        })
+    }
+}
+
+async fn resolve_grants(req: &ServiceRequest) -> Result<Grants, ServiceError> {
+    let claims = req.extensions()
+        .get::<Claims>()
+        .cloned()
+        .ok_or_else(|| {
+            ServiceError::build()
+                .belong(BizError(ClaimsNotFound))
+                .done()
+        })?;
+
+    let app_state = req.app_data::<web::Data<AppState>>()
+        .cloned()
+        .ok_or_else(|| {
+            debug!("Failed to get data from AppState");
+            ServiceError::build()
+                .belong(InfraError)
+                .done()
+        })?;
+
+    let pg_client = get_pg(&app_state).await?;
+
+    let site_roles = select_roles(&pg_client, claims.sub)
+        .await?
+        .iter()
+        .filter_map(|role| SiteRole::parse(role))
+        .collect::<Vec<SiteRole>>();
+
+    let family_role = select_membership(&pg_client, claims.sub)
+        .await?
+        .and_then(|member| FamilyRole::parse(&member.role));
+
+    let grants = Grants::resolve(&site_roles, family_role);
+
+    match &claims.scopes {
+        Some(scopes) => Ok(grants.limit(scopes)),
+        None => Ok(grants),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biz::family::courier::FamilyRole;
+    use super::{Grants, Permission, SiteRole};
+
+    #[test]
+    fn viewer_can_not_write_child_record() {
+        let grants = Grants::resolve(&[], Some(FamilyRole::Viewer));
+
+        assert!(!grants.allows(Permission::WriteChildRecord));
+        assert!(!grants.allows(Permission::ManageChild));
+    }
+
+    #[test]
+    fn caregiver_writes_records_but_not_children() {
+        let grants = Grants::resolve(&[], Some(FamilyRole::Caregiver));
+
+        assert!(grants.allows(Permission::WriteChildRecord));
+        assert!(!grants.allows(Permission::ManageChild));
+    }
+
+    #[test]
+    fn only_admin_curates_articles() {
+        let author = Grants::resolve(&[SiteRole::Author], Some(FamilyRole::Owner));
+        let admin = Grants::resolve(&[SiteRole::Admin], None);
+
+        assert!(author.allows(Permission::WriteArticle));
+        assert!(!author.allows(Permission::CurateArticle));
+        assert!(admin.allows(Permission::CurateArticle));
+        assert!(admin.allows(Permission::EditCategory));
+    }
+
+    #[test]
+    fn scopes_narrow_grants() {
+        let grants = Grants::resolve(&[SiteRole::Author], Some(FamilyRole::Parent))
+            .limit(&["record:write".to_string(), "article:curate".to_string()]);
+
+        assert!(grants.allows(Permission::WriteChildRecord));
+        assert!(!grants.allows(Permission::WriteArticle));
+        assert!(!grants.allows(Permission::CurateArticle));
+    }
+
+    #[test]
+    fn scope_names_round_trip() {
+        for permission in Permission::ALL {
+            assert_eq!(Permission::from_scope(permission.scope()), Some(permission));
+        }
+        assert_eq!(Permission::from_scope("everything"), None);
+    }
+}
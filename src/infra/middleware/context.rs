@@ -0,0 +1,175 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use actix_web::{dev::{Service, ServiceRequest, ServiceResponse, Transform}, Error};
+use actix_web::body::EitherBody;
+use actix_web::dev::forward_ready;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use log::warn;
+use tracing::{field, info_span, Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use crate::infra::crypto::random_token;
+use crate::infra::telemetry::remote_context;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// 12 bytes are 16 url safe characters, plenty to tell requests apart
+const REQUEST_ID_BYTES: usize = 12;
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// What is known about the request being served, readable anywhere down the call chain.
+#[derive(Debug)]
+pub struct RequestContext {
+    pub request_id: String,
+    // set by `JwtMiddleware` once the caller is authenticated
+    pub user_id: Cell<Option<i64>>,
+}
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+/// The id of the request being served, `None` outside of a request.
+pub fn request_id() -> Option<String> {
+    REQUEST_CONTEXT
+        .try_with(|context| context.request_id.clone())
+        .ok()
+}
+
+/// The authenticated user of the request being served, `None` before `JwtMiddleware` ran.
+pub fn user_id() -> Option<i64> {
+    REQUEST_CONTEXT
+        .try_with(|context| context.user_id.get())
+        .ok()
+        .flatten()
+}
+
+pub fn set_user_id(user_id: i64) {
+    let _ = REQUEST_CONTEXT.try_with(|context| context.user_id.set(Some(user_id)));
+
+    Span::current().record("user.id", user_id);
+}
+
+/// An id passed in by a proxy or the client is kept if it is short and plain enough to log.
+fn accept_request_id(incoming: &str) -> Option<String> {
+    let plain = incoming
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+
+    if plain && !incoming.is_empty() && incoming.len() <= MAX_REQUEST_ID_LEN {
+        Some(incoming.to_string())
+    } else {
+        None
+    }
+}
+
+/// Gives every request an id, or keeps the `X-Request-Id` it came with. The id is sent back
+/// in the `X-Request-Id` header and in error bodies, and tags every log line of the request,
+/// so a client report can be matched to the server logs.
+///
+/// Errors are turned into responses in here rather than further out, so that `ServiceError`
+/// still sees the context when it renders the body. Wrap it outermost.
+pub struct RequestContextMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestContextMiddleware
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequestContextMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestContextMiddlewareService { service: Rc::new(service) }))
+    }
+}
+
+pub struct RequestContextMiddlewareService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestContextMiddlewareService<S>
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        let incoming = req.headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(accept_request_id);
+
+        let request_id = incoming.unwrap_or_else(|| {
+            random_token(REQUEST_ID_BYTES).unwrap_or_else(|err| {
+                warn!("Failed to generate a request id: {}", err);
+                "-".to_string()
+            })
+        });
+
+        // downstream, the access log included, sees the id the request is served with
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            req.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+        }
+
+        let context = RequestContext {
+            request_id: request_id.clone(),
+            user_id: Cell::new(None),
+        };
+
+        let route = req.match_pattern().unwrap_or_default();
+
+        let span = info_span!(
+            "http_request",
+            otel.name = %format!("{} {}", req.method(), route),
+            otel.kind = "server",
+            http.method = %req.method(),
+            http.route = %route,
+            http.status_code = field::Empty,
+            user.id = field::Empty,
+            request_id = %request_id,
+        );
+        span.set_parent(remote_context(req.headers()));
+
+        Box::pin(REQUEST_CONTEXT.scope(context, async move {
+            let http_req = req.request().clone();
+
+            let mut res = match service.call(req).await {
+                Ok(res) => res.map_into_left_body(),
+                Err(err) => ServiceResponse::from_err(err, http_req).map_into_right_body(),
+            };
+
+            Span::current().record("http.status_code", res.status().as_u16());
+
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+
+            Ok(res)
+        }.instrument(span)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::accept_request_id;
+
+    #[test]
+    fn incoming_request_id_must_be_plain() {
+        assert_eq!(accept_request_id("5f0c-4a1b.proxy:1"), Some("5f0c-4a1b.proxy:1".to_string()));
+        assert_eq!(accept_request_id(""), None);
+        assert_eq!(accept_request_id("id with spaces"), None);
+        assert_eq!(accept_request_id("id\nforged log line"), None);
+        assert_eq!(accept_request_id(&"a".repeat(129)), None);
+    }
+}
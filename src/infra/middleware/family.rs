@@ -0,0 +1,115 @@
+use std::rc::Rc;
+use actix_web::{dev::{Service, ServiceRequest, ServiceResponse, Transform}, Error, HttpMessage, web};
+use actix_web::dev::forward_ready;
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use log::debug;
+use crate::AppState;
+use crate::biz::family::courier::FamilyRole;
+use crate::biz::family::recorder::select_membership;
+use crate::biz::internal::get_pg;
+use crate::infra::error::biz::BizKind::{ClaimsNotFound, PermissionDenied};
+use crate::infra::error::error::Kind::{BizError, InfraError};
+use crate::infra::error::error::ServiceError;
+use crate::infra::middleware::jwt::Claims;
+
+/// The family the authenticated account belongs to and its role there.
+#[derive(Debug, Clone, Copy)]
+pub struct Membership {
+    pub account_id: i64,
+    pub family_id: i64,
+    pub role: FamilyRole,
+}
+
+/// Resolves the family membership of the caller and stores it as [`Membership`]
+/// in the request extensions, so that every family-scoped handler reads and writes
+/// within that family only. Callers outside any family are rejected.
+///
+/// Must be wrapped inside `JwtMiddleware`, since it relies on the `Claims`.
+pub struct FamilyMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for FamilyMiddleware
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = FamilyMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(FamilyMiddlewareService { service: Rc::new(service) }))
+    }
+}
+
+pub struct FamilyMiddlewareService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for FamilyMiddlewareService<S>
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let user_id = req.extensions()
+                .get::<Claims>()
+                .map(|claims| claims.sub)
+                .ok_or_else(|| {
+                    ServiceError::build()
+                        .belong(BizError(ClaimsNotFound))
+                        .done()
+                })?;
+
+            let app_state = req.app_data::<web::Data<AppState>>()
+                .cloned()
+                .ok_or_else(|| {
+                    debug!("Failed to get data from AppState");
+                    ServiceError::build()
+                        .belong(InfraError)
+                        .done()
+                })?;
+
+            let member_record = {
+                let pg_client = get_pg(&app_state).await?;
+                select_membership(&pg_client, user_id).await?
+            }
+                .ok_or_else(|| {
+                    ServiceError::build()
+                        .belong(BizError(PermissionDenied))
+                        .message("The user does not belong to any family")
+                        .done()
+                })?;
+
+            let role = FamilyRole::parse(&member_record.role)
+                .ok_or_else(|| {
+                    ServiceError::build()
+                        .belong(InfraError)
+                        .message(&format!("Unknown family role: {}", member_record.role))
+                        .done()
+                })?;
+
+            debug!("user {} is {} of family {}", user_id, role.as_str(), member_record.family_id);
+
+            req.extensions_mut().insert(
+                Membership {
+                    account_id: user_id,
+                    family_id: member_record.family_id,
+                    role,
+                }
+            );
+
+            service.call(req).await
+        })
+    }
+}
@@ -1,21 +1,40 @@
+use std::rc::Rc;
 use actix_web::{dev::{Service, ServiceRequest, ServiceResponse, Transform}, Error, HttpMessage, web};
 use actix_web::dev::forward_ready;
+use actix_web::http::header::AUTHORIZATION;
 use futures_util::{future::{Ready, ready, LocalBoxFuture}};
 use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm};
 use log::debug;
 use serde::{Deserialize, Serialize};
 use crate::AppState;
+use crate::biz::account::recorder::{is_session_active, use_api_token};
+use crate::biz::internal::get_pg;
+use crate::infra::crypto::sha256_hex;
 use crate::infra::error::biz::BizKind::TokenInvalid;
 use crate::infra::error::error::Kind::{BizError, InfraError};
 use crate::infra::error::error::ServiceError;
+use crate::infra::middleware::context::set_user_id;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Claims {
     pub sub: i64, // user_id
     pub exp: i64,  // expires
+    pub jti: String, // session id, checked against the session table on every request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>, // only set for API tokens
+}
+
+impl Claims {
+    /// Whether the request is authenticated by a personal API token rather than a login session.
+    pub fn is_api_token(&self) -> bool {
+        self.jti.starts_with(API_TOKEN_JTI_PREFIX)
+    }
 }
 
 pub const JWT_AUTH_KEY: &str = "t";
+pub const REFRESH_KEY: &str = "r";
+pub const API_TOKEN_PREFIX: &str = "hst_";
+const API_TOKEN_JTI_PREFIX: &str = "api:";
 
 pub struct JwtMiddleware;
 
@@ -31,12 +50,12 @@ impl<S, B> Transform<S, ServiceRequest> for JwtMiddleware
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(JwtMiddlewareService { service }))
+        ready(Ok(JwtMiddlewareService { service: Rc::new(service) }))
     }
 }
 
 pub struct JwtMiddlewareService<S> {
-    service: S,
+    service: Rc<S>,
 }
 
 impl<S, B> Service<ServiceRequest> for JwtMiddlewareService<S>
@@ -51,72 +70,112 @@ impl<S, B> Service<ServiceRequest> for JwtMiddlewareService<S>
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let secret_key: String;
-
-        debug!("req: {:#?}", req);
-
-        match req.app_data::<web::Data<AppState>>() {
-            Some(data) => {
-                secret_key = data.jwt_secret.clone();
-                debug!("secret key: {}", secret_key)
-            }
-            None => {
-                debug!("Failed to get data from AppState");
-                return Box::pin(
-                    async {
-                        Err(
-                            ServiceError::build()
-                                .belong(InfraError)
-                                .done()
-                                .into()
-                        )
-                    }
-                );
-            }
-        }
-
+        let service = Rc::clone(&self.service);
 
-        let token = req.cookie(JWT_AUTH_KEY).map_or_else(
-            || {
-                debug!("Failed to parse cookie");
-                "".to_string()
-            },
-            |cookie| {
-                let token = cookie.value();
-                debug!("token in cookie:{}", token);
-                token.to_string()
-            },
-        );
-
-
-        match decode::<Claims>(
-            &token,
-            &DecodingKey::from_secret(secret_key.as_bytes()),
-            &Validation::new(Algorithm::default()),
-        ) {
-            Ok(data) => {
-                debug!("validation success");
-                req.extensions_mut().insert(data.claims);
-            }
-            Err(e) => {
-                debug!("validation failed: {:?}",e);
-                return Box::pin(
-                    async {
-                        Err(
-                            ServiceError::build()
-                                .belong(BizError(TokenInvalid))
-                                .done()
-                                .into()
-                        )
-                    }
-                );
-            }
-        }
+        debug!("req: {} {}", req.method(), req.path());
 
-        let fut = self.service.call(req);
         Box::pin(async move {
-            let res = fut.await?;
-            Ok(res)
+            let app_state = match req.app_data::<web::Data<AppState>>() {
+                Some(data) => data.clone(),
+                None => {
+                    debug!("Failed to get data from AppState");
+                    return Err(
+                        ServiceError::build()
+                            .belong(InfraError)
+                            .done()
+                            .into()
+                    );
+                }
+            };
+
+            // non-browser clients send the token in the Authorization header instead of the cookie
+            let bearer = req.headers()
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(|token| token.trim().to_string());
+
+            let token = match bearer {
+                Some(token) => {
+                    debug!("token in authorization header");
+                    token
+                }
+                None => req.cookie(JWT_AUTH_KEY).map_or_else(
+                    || {
+                        debug!("Failed to parse cookie");
+                        "".to_string()
+                    },
+                    |cookie| {
+                        debug!("token in cookie");
+                        cookie.value().to_string()
+                    },
+                ),
+            };
+
+            let claims = if token.starts_with(API_TOKEN_PREFIX) {
+                claims_of_api_token(&app_state, &token).await?
+            } else {
+                claims_of_jwt(&app_state, &token).await?
+            };
+
+            set_user_id(claims.sub);
+
+            req.extensions_mut().insert(claims);
+
+            service.call(req).await
         })
     }
 }
+
+async fn claims_of_jwt(app_state: &web::Data<AppState>, token: &str) -> Result<Claims, ServiceError> {
+    let claims = match decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(app_state.jwt_secret.as_bytes()),
+        &Validation::new(Algorithm::default()),
+    ) {
+        Ok(data) => {
+            debug!("validation success");
+            data.claims
+        }
+        Err(e) => {
+            debug!("validation failed: {:?}",e);
+            return Err(token_invalid("Token is invalid or expired"));
+        }
+    };
+
+    // a valid signature is not enough, the session may have been logged out
+    let pg_client = get_pg(app_state).await?;
+
+    if claims.is_api_token() || !is_session_active(&pg_client, &claims.jti, claims.sub).await? {
+        debug!("session {} is revoked or expired", claims.jti);
+        return Err(token_invalid("Session is revoked or expired"));
+    }
+
+    Ok(claims)
+}
+
+async fn claims_of_api_token(app_state: &web::Data<AppState>, token: &str) -> Result<Claims, ServiceError> {
+    let pg_client = get_pg(app_state).await?;
+
+    let api_token = use_api_token(&pg_client, &sha256_hex(token))
+        .await?
+        .ok_or_else(|| token_invalid("API token is invalid, revoked or expired"))?;
+
+    debug!("api token {} of user {}", api_token.id, api_token.account_id);
+
+    Ok(
+        Claims {
+            sub: api_token.account_id,
+            exp: api_token.expires_at.map_or(i64::MAX, |expires_at| expires_at.and_utc().timestamp()),
+            jti: format!("{}{}", API_TOKEN_JTI_PREFIX, api_token.id),
+            scopes: api_token.scopes,
+        }
+    )
+}
+
+fn token_invalid(message: &str) -> ServiceError {
+    ServiceError::build()
+        .belong(BizError(TokenInvalid))
+        .message(message)
+        .done()
+}
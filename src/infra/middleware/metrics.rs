@@ -0,0 +1,73 @@
+use std::rc::Rc;
+use std::time::Instant;
+use actix_web::{dev::{Service, ServiceRequest, ServiceResponse, Transform}, Error};
+use actix_web::dev::forward_ready;
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use crate::infra::error::error::ServiceError;
+use crate::infra::metrics::{METRICS, UNMATCHED_ROUTE};
+
+/// Times every request by method, route pattern and status, and counts the `ServiceError`s
+/// they end with by kind.
+///
+/// Must be wrapped inside `RequestContextMiddleware`, which turns errors into responses.
+pub struct MetricsMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for MetricsMiddleware
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = MetricsMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MetricsMiddlewareService { service: Rc::new(service) }))
+    }
+}
+
+pub struct MetricsMiddlewareService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for MetricsMiddlewareService<S>
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        let started_at = Instant::now();
+        let method = req.method().to_string();
+        // the pattern, not the path, `/child/{id}` rather than one label per child
+        let route = req.match_pattern().unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+        Box::pin(async move {
+            let result = service.call(req).await;
+
+            let (status, error) = match &result {
+                Ok(res) => (res.status(), res.response().error()),
+                Err(err) => (err.as_response_error().status_code(), Some(err)),
+            };
+
+            if let Some(err) = error.and_then(|err| err.as_error::<ServiceError>()) {
+                METRICS.count_error(err);
+            }
+
+            METRICS.http_request_duration
+                .with_label_values(&[method.as_str(), route.as_str(), status.as_str()])
+                .observe(started_at.elapsed().as_secs_f64());
+
+            result
+        })
+    }
+}
@@ -1 +1,6 @@
-pub mod jwt;
\ No newline at end of file
+pub mod jwt;
+pub mod family;
+pub mod authorize;
+pub mod context;
+pub mod metrics;
+pub mod rate_limit;
@@ -0,0 +1,137 @@
+use std::rc::Rc;
+use actix_web::{dev::{Service, ServiceRequest, ServiceResponse, Transform}, Error, HttpMessage, HttpResponse, web};
+use actix_web::body::EitherBody;
+use actix_web::dev::forward_ready;
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use log::{debug, warn};
+use crate::AppState;
+use crate::infra::error::biz::BizKind::RateLimited;
+use crate::infra::error::error::Kind::{BizError, InfraError};
+use crate::infra::error::error::ServiceError;
+use crate::infra::middleware::jwt::Claims;
+use crate::infra::rate_limit::Decision;
+
+const RATE_LIMIT_LIMIT: &str = "ratelimit-limit";
+const RATE_LIMIT_REMAINING: &str = "ratelimit-remaining";
+const RATE_LIMIT_RESET: &str = "ratelimit-reset";
+
+fn insert_headers(headers: &mut HeaderMap, decision: &Decision) {
+    let values = [
+        (RATE_LIMIT_LIMIT, decision.limit as i64),
+        (RATE_LIMIT_REMAINING, decision.remaining as i64),
+        (RATE_LIMIT_RESET, decision.reset_seconds),
+    ];
+
+    for (name, value) in values {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+    }
+
+    if let Some(retry_after) = decision.retry_after_seconds {
+        headers.insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    }
+}
+
+/// Limits the requests to a scope with a token bucket per caller, following the policy
+/// of the scope in `rate_limit.policies`. Callers are told apart by their user id,
+/// or their ip when not authenticated.
+///
+/// Wrap it before `JwtMiddleware`, so that it runs after it and sees the `Claims`.
+pub struct RateLimitMiddleware {
+    scope: &'static str,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(scope: &'static str) -> Self {
+        RateLimitMiddleware { scope }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimitMiddleware
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddlewareService { service: Rc::new(service), scope: self.scope }))
+    }
+}
+
+pub struct RateLimitMiddlewareService<S> {
+    service: Rc<S>,
+    scope: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddlewareService<S>
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let scope = self.scope;
+
+        Box::pin(async move {
+            let app_state = req.app_data::<web::Data<AppState>>()
+                .cloned()
+                .ok_or_else(|| {
+                    debug!("Failed to get data from AppState");
+                    ServiceError::build()
+                        .belong(InfraError)
+                        .done()
+                })?;
+
+            let key = match req.extensions().get::<Claims>() {
+                Some(claims) => format!("user:{}", claims.sub),
+                None => format!(
+                    "ip:{}",
+                    app_state.rate_limiter.client_ip(req.request()).map_or_else(|| "unknown".to_string(), |ip| ip.to_string())
+                ),
+            };
+
+            // a broken store must not take the whole api down with it
+            let decision = match app_state.rate_limiter.take(scope, &key).await {
+                Ok(decision) => decision,
+                Err(err) => {
+                    warn!("Failed to rate limit {} in {}, let it through: {}", key, scope, err);
+                    None
+                }
+            };
+
+            let decision = match decision {
+                Some(decision) => decision,
+                None => return service.call(req).await.map(ServiceResponse::map_into_left_body),
+            };
+
+            if !decision.allowed {
+                debug!("{} is rate limited in {}", key, scope);
+
+                let mut response = HttpResponse::from_error(
+                    ServiceError::build()
+                        .belong(BizError(RateLimited))
+                        .done()
+                );
+                insert_headers(response.headers_mut(), &decision);
+
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+
+            let mut res = service.call(req).await?;
+            insert_headers(res.headers_mut(), &decision);
+
+            Ok(res.map_into_left_body())
+        })
+    }
+}
@@ -0,0 +1,285 @@
+use chrono::NaiveDateTime;
+use deadpool_postgres::Client as PgClient;
+use log::{info, warn};
+use crate::infra::error::error::Kind::InfraError;
+use crate::infra::error::error::ServiceError;
+
+/// A schema change embedded in the binary, applied in order of `version`.
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    up: &'static str,
+    down: &'static str,
+}
+
+macro_rules! migration {
+    ($version:literal, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../../migrations/", $name, ".up.sql")),
+            down: include_str!(concat!("../../migrations/", $name, ".down.sql")),
+        }
+    };
+}
+
+/// The migrations up to this one are the schema as it was created from the `sql/` files, before
+/// `schema_migrations` existed, the later ones are changes on top of it.
+pub const BASELINE_VERSION: i64 = 9;
+
+// created by the baseline migrations, all of them have to exist before these are recorded as applied
+const BASELINE_TABLES: &[&str] = &["account", "article", "article_category", "behavior", "diet", "draft", "health", "journal", "remark", "wish"];
+
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_account"),
+    migration!(2, "0002_article"),
+    migration!(3, "0003_behavior"),
+    migration!(4, "0004_diet"),
+    migration!(5, "0005_draft"),
+    migration!(6, "0006_health"),
+    migration!(7, "0007_journal"),
+    migration!(8, "0008_remark"),
+    migration!(9, "0009_wish"),
+    migration!(10, "0010_family"),
+    migration!(11, "0011_child"),
+    migration!(12, "0012_child_record"),
+    migration!(13, "0013_family_record"),
+    migration!(14, "0014_account_role"),
+    migration!(15, "0015_session"),
+    migration!(16, "0016_api_token"),
+    migration!(17, "0017_password_reset"),
+    migration!(18, "0018_account_username_lower"),
+    migration!(19, "0019_login_attempt"),
+    migration!(20, "0020_mfa"),
+    migration!(21, "0021_rate_limit_bucket"),
+    migration!(22, "0022_article_lifecycle"),
+    migration!(23, "0023_article_revision"),
+    migration!(24, "0024_search"),
+    migration!(25, "0025_article_filter_index"),
+    migration!(26, "0026_article_category_link"),
+];
+
+// any constant shared by every instance, keeps two migrators from running at once
+const ADVISORY_LOCK_KEY: i64 = 0x6861_6d6d_6572;
+
+async fn ensure_table(pc: &PgClient) -> Result<(), ServiceError> {
+    let stmt = r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version     BIGINT PRIMARY KEY,
+            name        VARCHAR(255) NOT NULL,
+            applied_at  TIMESTAMP WITHOUT TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        );
+    "#;
+
+    pc.batch_execute(stmt).await?;
+
+    Ok(())
+}
+
+/// Versions recorded in `schema_migrations` with the time they were applied, ascending.
+async fn applied(pc: &PgClient) -> Result<Vec<(i64, NaiveDateTime)>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            version, applied_at
+        FROM
+            schema_migrations
+        ORDER BY
+            version;
+    "#;
+
+    let rows = pc.query(stmt, &[]).await?;
+
+    Ok(
+        rows.iter()
+            .map(|row| (row.get("version"), row.get("applied_at")))
+            .collect()
+    )
+}
+
+/// Take the migration lock, waiting for another migrator to finish first.
+async fn lock(pc: &PgClient) -> Result<(), ServiceError> {
+    pc.execute("SELECT pg_advisory_lock($1);", &[&ADVISORY_LOCK_KEY]).await?;
+
+    ensure_table(pc).await
+}
+
+async fn unlock(pc: &PgClient) -> Result<(), ServiceError> {
+    pc.execute("SELECT pg_advisory_unlock($1);", &[&ADVISORY_LOCK_KEY]).await?;
+
+    Ok(())
+}
+
+async fn apply(pc: &mut PgClient, migration: &Migration, up: bool) -> Result<(), ServiceError> {
+    let tx = pc.transaction().await?;
+
+    let script = if up { migration.up } else { migration.down };
+
+    tx.batch_execute(script).await.map_err(|err| {
+        ServiceError::build()
+            .belong(InfraError)
+            .message(&format!("Migration {} failed: {}", migration.name, err))
+            .because(Box::new(err))
+            .done()
+    })?;
+
+    if up {
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name) VALUES ($1, $2);",
+            &[&migration.version, &migration.name],
+        ).await?;
+    } else {
+        tx.execute("DELETE FROM schema_migrations WHERE version = $1;", &[&migration.version]).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Apply every pending migration, return the ones applied.
+pub async fn up(pc: &mut PgClient) -> Result<Vec<&'static Migration>, ServiceError> {
+    lock(pc).await?;
+    let result = up_locked(pc).await;
+    unlock(pc).await?;
+
+    result
+}
+
+async fn up_locked(pc: &mut PgClient) -> Result<Vec<&'static Migration>, ServiceError> {
+    let applied = applied(pc).await?;
+
+    let known_latest = MIGRATIONS.last().map(|m| m.version).unwrap_or(0);
+    if let Some((version, _)) = applied.iter().find(|(version, _)| *version > known_latest) {
+        warn!("Database has migration {} applied, which this binary does not know", version);
+    }
+
+    let mut done = vec![];
+
+    for migration in MIGRATIONS.iter().filter(|m| !applied.iter().any(|(version, _)| *version == m.version)) {
+        apply(pc, migration, true).await?;
+        info!("Applied migration {}", migration.name);
+        done.push(migration);
+    }
+
+    Ok(done)
+}
+
+/// Revert the latest `steps` applied migrations, return the ones reverted.
+pub async fn down(pc: &mut PgClient, steps: usize) -> Result<Vec<&'static Migration>, ServiceError> {
+    lock(pc).await?;
+    let result = down_locked(pc, steps).await;
+    unlock(pc).await?;
+
+    result
+}
+
+async fn down_locked(pc: &mut PgClient, steps: usize) -> Result<Vec<&'static Migration>, ServiceError> {
+    let applied = applied(pc).await?;
+
+    let mut done = vec![];
+
+    for (version, _) in applied.iter().rev().take(steps) {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|m| m.version == *version)
+            .ok_or_else(|| {
+                ServiceError::build()
+                    .belong(InfraError)
+                    .message(&format!("Migration {} is unknown to this binary, can not revert it", version))
+                    .done()
+            })?;
+
+        apply(pc, migration, false).await?;
+        info!("Reverted migration {}", migration.name);
+        done.push(migration);
+    }
+
+    Ok(done)
+}
+
+/// Record the migrations up to `BASELINE_VERSION` as applied without running them, for a database
+/// created from the `sql/` files before `schema_migrations` existed. Return the ones recorded.
+pub async fn baseline(pc: &mut PgClient) -> Result<Vec<&'static Migration>, ServiceError> {
+    lock(pc).await?;
+    let result = baseline_locked(pc).await;
+    unlock(pc).await?;
+
+    result
+}
+
+async fn baseline_locked(pc: &mut PgClient) -> Result<Vec<&'static Migration>, ServiceError> {
+    let stmt = r#"
+        SELECT
+            name
+        FROM
+            unnest($1::TEXT[]) AS name
+        WHERE
+            to_regclass(name) IS NULL;
+    "#;
+
+    let missing = pc.query(stmt, &[&BASELINE_TABLES]).await?;
+    if let Some(row) = missing.first() {
+        let table: String = row.get("name");
+        return Err(
+            ServiceError::build()
+                .belong(InfraError)
+                .message(&format!("Table {} of the baseline schema does not exist, run migrate up instead", table))
+                .done()
+        );
+    }
+
+    let applied = applied(pc).await?;
+
+    let tx = pc.transaction().await?;
+
+    let mut done = vec![];
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version <= BASELINE_VERSION && !applied.iter().any(|(applied_version, _)| *applied_version == m.version)) {
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name) VALUES ($1, $2);",
+            &[&migration.version, &migration.name],
+        ).await?;
+        done.push(migration);
+    }
+
+    tx.commit().await?;
+
+    Ok(done)
+}
+
+/// Every known migration with the time it was applied, `None` if pending.
+pub async fn status(pc: &PgClient) -> Result<Vec<(&'static Migration, Option<NaiveDateTime>)>, ServiceError> {
+    ensure_table(pc).await?;
+
+    let applied = applied(pc).await?;
+
+    Ok(
+        MIGRATIONS
+            .iter()
+            .map(|m| {
+                let applied_at = applied
+                    .iter()
+                    .find(|(version, _)| *version == m.version)
+                    .map(|(_, applied_at)| *applied_at);
+                (m, applied_at)
+            })
+            .collect()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BASELINE_VERSION, MIGRATIONS};
+
+    #[test]
+    fn migrations_are_ordered_and_named_by_version() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i64 + 1);
+            assert!(migration.name.starts_with(&format!("{:04}_", migration.version)), "{}", migration.name);
+            assert!(migration.up.trim_end().ends_with(';'), "{} up", migration.name);
+            assert!(migration.down.trim_end().ends_with(';'), "{} down", migration.name);
+        }
+        assert!(MIGRATIONS.iter().any(|m| m.version == BASELINE_VERSION));
+    }
+}
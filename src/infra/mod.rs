@@ -2,3 +2,14 @@ pub mod config;
 pub mod init;
 pub mod middleware;
 pub mod error;
+pub mod crypto;
+pub mod mail;
+pub mod password;
+pub mod login_guard;
+pub mod totp;
+pub mod tls;
+pub mod migrate;
+pub mod metrics;
+pub mod telemetry;
+pub mod rate_limit;
+pub mod diff;
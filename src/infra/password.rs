@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use crate::infra::config::PasswordConfig;
+use crate::infra::error::error::FieldError;
+
+// bcrypt ignores everything after the 72nd byte
+const MAX_PASSWORD_BYTES: usize = 72;
+
+/// Rules a new password has to follow, built once from the settings at startup.
+#[derive(Debug, Default)]
+pub struct PasswordPolicy {
+    min_length: usize,
+    min_classes: usize,
+    breached: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    pub fn load(config: &PasswordConfig) -> io::Result<Self> {
+        let breached = if config.breached_list.is_empty() {
+            HashSet::new()
+        } else {
+            fs::read_to_string(&config.breached_list)?
+                .lines()
+                .map(|line| line.trim().to_lowercase())
+                .filter(|line| !line.is_empty())
+                .collect()
+        };
+
+        Ok(PasswordPolicy {
+            min_length: config.min_length,
+            min_classes: config.min_classes,
+            breached,
+        })
+    }
+
+    /// Check `password` chosen by `username`, every broken rule is reported against `field`.
+    pub fn check(&self, field: &str, password: &str, username: &str) -> Vec<FieldError> {
+        let mut errors = vec![];
+
+        if password.chars().count() < self.min_length {
+            errors.push(FieldError::new(field, &format!("must be at least {} characters", self.min_length)));
+        }
+        if password.len() > MAX_PASSWORD_BYTES {
+            errors.push(FieldError::new(field, &format!("must be at most {} bytes", MAX_PASSWORD_BYTES)));
+        }
+
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ]
+            .iter()
+            .filter(|has| **has)
+            .count();
+
+        if classes < self.min_classes {
+            errors.push(FieldError::new(
+                field,
+                &format!("must mix at least {} of lowercase, uppercase, digit and symbol", self.min_classes),
+            ));
+        }
+
+        let lowered = password.to_lowercase();
+
+        if !username.is_empty() && lowered == username.to_lowercase() {
+            errors.push(FieldError::new(field, "must not be the same as the username"));
+        }
+        if self.breached.contains(&lowered) {
+            errors.push(FieldError::new(field, "is known from a data breach, choose another one"));
+        }
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use super::PasswordPolicy;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            min_classes: 2,
+            breached: HashSet::from(["password1".to_string()]),
+        }
+    }
+
+    #[test]
+    fn strong_password_passes() {
+        assert!(policy().check("password", "correct-horse-42", "alice").is_empty());
+    }
+
+    #[test]
+    fn weak_passwords_are_reported() {
+        let policy = policy();
+
+        assert_eq!(policy.check("password", "a1", "alice").len(), 1);
+        assert_eq!(policy.check("password", "abcdefghij", "alice").len(), 1);
+        assert_eq!(policy.check("password", "Password1", "alice").len(), 1);
+        assert_eq!(policy.check("password", "Alice2024", "alice2024").len(), 1);
+    }
+}
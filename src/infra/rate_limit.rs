@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use actix_web::HttpRequest;
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use deadpool_postgres::Pool;
+use log::{debug, warn};
+use crate::infra::config::{RateLimitConfig, RateLimitPolicy};
+use crate::infra::error::error::Kind::InfraError;
+use crate::infra::error::error::ServiceError;
+
+// buckets left alone for a while are dropped, they refilled completely by then
+// under any sensible policy and a new bucket starts out full anyway
+const PRUNE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const PRUNE_IDLE_MINUTES: i64 = 60;
+
+/// Tokens left for a key and when they were counted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bucket {
+    pub tokens: f64,
+    pub updated_at: NaiveDateTime,
+}
+
+/// The outcome of taking a token, with what the `RateLimit-*` headers tell the client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    // seconds until the bucket is full again
+    pub reset_seconds: i64,
+    // seconds until the next token, only when denied
+    pub retry_after_seconds: Option<i64>,
+}
+
+impl RateLimitPolicy {
+    fn refill_per_second(&self) -> f64 {
+        self.refill_per_minute as f64 / 60.0
+    }
+
+    /// Refill `bucket` up to `now` and take a token from it if there is a whole one.
+    /// A key seen for the first time starts with a full bucket.
+    pub fn take(&self, bucket: Option<Bucket>, now: NaiveDateTime) -> (Bucket, Decision) {
+        let capacity = self.capacity as f64;
+        let rate = self.refill_per_second();
+
+        let tokens = match bucket {
+            Some(bucket) => {
+                let elapsed = (now - bucket.updated_at).num_milliseconds().max(0) as f64 / 1000.0;
+                (bucket.tokens + elapsed * rate).min(capacity)
+            }
+            None => capacity,
+        };
+
+        let allowed = tokens >= 1.0;
+        let tokens = if allowed { tokens - 1.0 } else { tokens };
+
+        let decision = Decision {
+            allowed,
+            limit: self.capacity,
+            remaining: tokens.floor() as u32,
+            reset_seconds: ((capacity - tokens) / rate).ceil() as i64,
+            retry_after_seconds: if allowed { None } else { Some(((1.0 - tokens) / rate).ceil() as i64) },
+        };
+
+        (Bucket { tokens, updated_at: now }, decision)
+    }
+}
+
+/// Keeps the buckets, picked by `rate_limit.backend` in the settings.
+#[async_trait(?Send)]
+pub trait RateLimitStore: Debug + Send + Sync {
+    async fn take(&self, key: &str, policy: &RateLimitPolicy) -> Result<Decision, ServiceError>;
+
+    /// Drop the buckets not touched since `idle_since`, returning how many.
+    async fn prune(&self, idle_since: NaiveDateTime) -> Result<u64, ServiceError>;
+}
+
+/// Buckets of this process only, every instance behind a load balancer counts on its own.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[async_trait(?Send)]
+impl RateLimitStore for MemoryStore {
+    async fn take(&self, key: &str, policy: &RateLimitPolicy) -> Result<Decision, ServiceError> {
+        let now = Utc::now().naive_utc();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let (bucket, decision) = policy.take(buckets.get(key).copied(), now);
+        buckets.insert(key.to_string(), bucket);
+
+        Ok(decision)
+    }
+
+    async fn prune(&self, idle_since: NaiveDateTime) -> Result<u64, ServiceError> {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let before = buckets.len();
+        buckets.retain(|_, bucket| bucket.updated_at > idle_since);
+
+        Ok((before - buckets.len()) as u64)
+    }
+}
+
+/// Buckets in Postgres, shared by every instance using the database.
+#[derive(Debug)]
+pub struct PostgresStore {
+    pool: Pool,
+}
+
+#[async_trait(?Send)]
+impl RateLimitStore for PostgresStore {
+    async fn take(&self, key: &str, policy: &RateLimitPolicy) -> Result<Decision, ServiceError> {
+        let mut pc = self.pool.get().await.map_err(|err| {
+            ServiceError::build()
+                .belong(InfraError)
+                .because(Box::new(err))
+                .message("Failed to check out a connection for rate limiting")
+                .done()
+        })?;
+
+        let tx = pc.transaction().await?;
+
+        // the row is locked until commit, concurrent requests of the key take their turn
+        let stmt = r#"
+            SELECT
+                tokens, updated_at, (NOW() AT TIME ZONE 'UTC') AS now
+            FROM
+                rate_limit_bucket
+            WHERE
+                key = $1
+            FOR UPDATE;
+        "#;
+
+        let row = tx.query_opt(stmt, &[&key]).await?;
+
+        let now: NaiveDateTime = match &row {
+            Some(row) => row.get("now"),
+            None => tx.query_one("SELECT (NOW() AT TIME ZONE 'UTC') AS now;", &[]).await?.get("now"),
+        };
+
+        let bucket = row.map(|row| Bucket {
+            tokens: row.get("tokens"),
+            updated_at: row.get("updated_at"),
+        });
+
+        let (bucket, decision) = policy.take(bucket, now);
+
+        let stmt = r#"
+            INSERT INTO
+                rate_limit_bucket (key, tokens, updated_at)
+            VALUES
+                ($1, $2, $3)
+            ON CONFLICT (key) DO UPDATE
+            SET
+                tokens = EXCLUDED.tokens,
+                updated_at = EXCLUDED.updated_at;
+        "#;
+
+        tx.execute(stmt, &[&key, &bucket.tokens, &bucket.updated_at]).await?;
+
+        tx.commit().await?;
+
+        Ok(decision)
+    }
+
+    async fn prune(&self, idle_since: NaiveDateTime) -> Result<u64, ServiceError> {
+        let pc = self.pool.get().await.map_err(|err| {
+            ServiceError::build()
+                .belong(InfraError)
+                .because(Box::new(err))
+                .message("Failed to check out a connection for rate limiting")
+                .done()
+        })?;
+
+        let stmt = r#"
+            DELETE FROM rate_limit_bucket
+            WHERE
+                updated_at <= $1;
+        "#;
+
+        let pruned = pc.execute(stmt, &[&idle_since]).await?;
+
+        Ok(pruned)
+    }
+}
+
+/// The store and the policy of each scope, scopes without a policy are not limited.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    enabled: bool,
+    store: Arc<dyn RateLimitStore>,
+    policies: HashMap<String, RateLimitPolicy>,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig, pool: &Pool) -> Result<Self, String> {
+        let store: Arc<dyn RateLimitStore> = match config.backend.as_str() {
+            "memory" | "" => Arc::new(MemoryStore::default()),
+            "postgres" => Arc::new(PostgresStore { pool: pool.clone() }),
+            other => return Err(format!("unknown rate limit backend: {}", other)),
+        };
+
+        let trusted_proxies = config.trusted_proxies
+            .iter()
+            .map(|proxy| proxy.parse::<IpAddr>().map_err(|_| format!("trusted proxy is not an ip: {}", proxy)))
+            .collect::<Result<Vec<IpAddr>, String>>()?;
+
+        Ok(
+            RateLimiter {
+                enabled: config.enabled,
+                store,
+                policies: config.policies.clone(),
+                trusted_proxies,
+            }
+        )
+    }
+
+    /// Whether the client address forwarded by `peer` may be believed.
+    pub fn trusts(&self, peer: IpAddr) -> bool {
+        self.trusted_proxies.contains(&peer)
+    }
+
+    /// The ip of the client, as forwarded by the peer only when it is a trusted proxy,
+    /// anyone else could claim any address to dodge the limits.
+    pub fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let peer = req.peer_addr()?.ip();
+
+        if !self.trusts(peer) {
+            return Some(peer);
+        }
+
+        let connection_info = req.connection_info();
+        let forwarded = connection_info.realip_remote_addr().unwrap_or_default();
+
+        // with or without a port, ipv6 in brackets when it has one
+        let ip = forwarded.parse::<IpAddr>().ok()
+            .or_else(|| forwarded.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+            .unwrap_or(peer);
+
+        Some(ip)
+    }
+
+    /// Drop idle buckets every few minutes, for as long as the server runs.
+    pub async fn prune_periodically(self) {
+        let mut ticker = tokio::time::interval(PRUNE_INTERVAL);
+
+        loop {
+            ticker.tick().await;
+
+            let idle_since = Utc::now().naive_utc() - TimeDelta::minutes(PRUNE_IDLE_MINUTES);
+
+            match self.store.prune(idle_since).await {
+                Ok(pruned) => debug!("Pruned {} idle rate limit buckets", pruned),
+                Err(err) => warn!("Failed to prune idle rate limit buckets: {}", err),
+            }
+        }
+    }
+
+    /// Take a token of `key` in `scope`, `None` if the scope is not limited.
+    pub async fn take(&self, scope: &str, key: &str) -> Result<Option<Decision>, ServiceError> {
+        let policy = match self.policies.get(scope) {
+            Some(policy) if self.enabled => policy,
+            _ => return Ok(None),
+        };
+
+        let key = format!("{}:{}", scope, key);
+
+        self.store.take(&key, policy).await.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use chrono::{TimeDelta, Utc};
+    use deadpool_postgres::Config;
+    use tokio_postgres::NoTls;
+    use crate::infra::config::{RateLimitConfig, RateLimitPolicy};
+    use super::{MemoryStore, RateLimiter, RateLimitStore};
+
+    fn policy() -> RateLimitPolicy {
+        RateLimitPolicy {
+            capacity: 3,
+            refill_per_minute: 6,
+        }
+    }
+
+    #[test]
+    fn bucket_drains_then_refills() {
+        let policy = policy();
+        let now = Utc::now().naive_utc();
+
+        let (bucket, decision) = policy.take(None, now);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 2);
+        assert_eq!(decision.reset_seconds, 10);
+
+        let (bucket, _) = policy.take(Some(bucket), now);
+        let (bucket, _) = policy.take(Some(bucket), now);
+        let (bucket, decision) = policy.take(Some(bucket), now);
+        assert!(!decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.retry_after_seconds, Some(10));
+
+        // a token every 10 seconds
+        let (_, decision) = policy.take(Some(bucket), now + TimeDelta::seconds(10));
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+    }
+
+    #[actix_web::test]
+    async fn memory_store_counts_keys_apart() {
+        let store = MemoryStore::default();
+        let policy = policy();
+
+        for _ in 0..3 {
+            assert!(store.take("ai:user:1", &policy).await.unwrap().allowed);
+        }
+        assert!(!store.take("ai:user:1", &policy).await.unwrap().allowed);
+        assert!(store.take("ai:user:2", &policy).await.unwrap().allowed);
+    }
+
+    #[actix_web::test]
+    async fn memory_store_prunes_idle_buckets() {
+        let store = MemoryStore::default();
+        let policy = policy();
+
+        store.take("ai:user:1", &policy).await.unwrap();
+
+        let before = Utc::now().naive_utc() - TimeDelta::minutes(1);
+        assert_eq!(store.prune(before).await.unwrap(), 0);
+
+        let after = Utc::now().naive_utc() + TimeDelta::minutes(1);
+        assert_eq!(store.prune(after).await.unwrap(), 1);
+        assert!(store.buckets.lock().unwrap().is_empty());
+    }
+
+    fn rate_limiter(trusted_proxies: &[&str]) -> RateLimiter {
+        let config = RateLimitConfig {
+            trusted_proxies: trusted_proxies.iter().map(|proxy| proxy.to_string()).collect(),
+            ..Default::default()
+        };
+        // connects lazily, never in this test
+        let mut pg = Config::new();
+        pg.dbname = Some("postgres".to_string());
+        let pool = pg.create_pool(None, NoTls).unwrap();
+
+        RateLimiter::new(&config, &pool).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn forwarded_ip_is_believed_from_trusted_proxies_only() {
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.2:40000".parse().unwrap())
+            .insert_header(("x-forwarded-for", "203.0.113.7"))
+            .to_http_request();
+
+        assert_eq!(rate_limiter(&[]).client_ip(&req).unwrap().to_string(), "10.0.0.2");
+        assert_eq!(rate_limiter(&["10.0.0.2"]).client_ip(&req).unwrap().to_string(), "203.0.113.7");
+    }
+}
@@ -0,0 +1,181 @@
+use std::time::Duration;
+use actix_web::http::header::HeaderMap;
+use log::{error, info};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{TraceError, TracerProvider as _};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::runtime::TokioCurrentThread;
+use opentelemetry_sdk::trace::{Config, Sampler, TracerProvider};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use crate::infra::config::TracingConfig;
+
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Keeps the span exporter alive, the spans still buffered are flushed when it is dropped.
+pub struct Telemetry {
+    provider: Option<TracerProvider>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(err) = provider.shutdown() {
+                error!("Failed to flush the remaining spans: {}", err);
+            }
+        }
+    }
+}
+
+/// A provider batching spans to the OTLP/HTTP endpoint of `config`.
+///
+/// Spans are exported from a thread of their own, the actix runtime being single threaded
+/// would otherwise deadlock on flush.
+fn build_provider(config: &TracingConfig) -> Result<TracerProvider, TraceError> {
+    let exporter = opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(&config.endpoint)
+        .with_timeout(EXPORT_TIMEOUT);
+
+    let trace_config = Config::default()
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+        .with_resource(Resource::new(vec![KeyValue::new("service.name", config.service_name.clone())]));
+
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(trace_config)
+        .install_batch(TokioCurrentThread)
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// The trace the caller is part of, from its W3C `traceparent` header, so the request
+/// span continues it and the parent based sampler follows its decision.
+pub fn remote_context(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// Export the spans of requests, recorders and upstream calls when `tracing.enabled`,
+/// without it they cost next to nothing as no subscriber listens.
+pub fn init(config: &TracingConfig) -> Result<Telemetry, TraceError> {
+    if !config.enabled {
+        return Ok(Telemetry { provider: None });
+    }
+
+    let provider = build_provider(config)?;
+
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("hammer-server"));
+
+    tracing_subscriber::registry()
+        .with(layer)
+        .try_init()
+        .map_err(|err| TraceError::Other(err.into()))?;
+
+    info!("Exporting traces to {}", config.endpoint);
+
+    Ok(Telemetry { provider: Some(provider) })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+    use opentelemetry::trace::{TraceContextExt, TraceId, Tracer, TracerProvider as _};
+    use crate::infra::config::TracingConfig;
+    use super::{build_provider, remote_context};
+
+    /// Stands in for a collector: answers one request and reports its request line.
+    fn collector() -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            reader.get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .unwrap();
+
+            sender.send(request_line).unwrap();
+        });
+
+        (endpoint, receiver)
+    }
+
+    #[test]
+    fn caller_trace_is_continued() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::from_static("traceparent"),
+            HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+
+        let context = remote_context(&headers);
+        let span_context = context.span().span_context().clone();
+
+        assert!(span_context.is_remote());
+        assert!(span_context.is_sampled());
+        assert_eq!(span_context.trace_id(), TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap());
+
+        assert!(!remote_context(&HeaderMap::new()).has_active_span());
+    }
+
+    // the batch exporter needs a runtime to start in, like it has in `main`
+    #[actix_web::test]
+    async fn spans_are_exported_over_otlp_http() {
+        let (endpoint, received) = collector();
+
+        let config = TracingConfig {
+            enabled: true,
+            endpoint,
+            ..Default::default()
+        };
+        let provider = build_provider(&config).unwrap();
+
+        provider.tracer("test").in_span("recorder", |_| {});
+
+        for result in provider.force_flush() {
+            result.unwrap();
+        }
+
+        let request_line = received.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(request_line.starts_with("POST /v1/traces"), "{}", request_line);
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::http::header::LOCATION;
+use log::{error, info};
+use openssl::error::ErrorStack;
+use openssl::ssl::{ClientHelloResponse, SslAcceptor, SslAcceptorBuilder, SslContext, SslFiletype, SslMethod};
+use tokio::signal::unix::{signal, SignalKind};
+
+fn load_context(key_path: &str, cert_path: &str) -> Result<SslContext, ErrorStack> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    builder.set_private_key_file(key_path, SslFiletype::PEM)?;
+    builder.set_certificate_chain_file(cert_path)?;
+    builder.check_private_key()?;
+
+    Ok(builder.build().into_context())
+}
+
+/// Keeps the certificate served to new connections in sync with the files on disk.
+///
+/// Every handshake swaps in the most recently loaded context from the client hello callback,
+/// which unlike the servername callback also runs for clients not sending SNI,
+/// so a reload takes effect without restarting the server. Connections already open are untouched.
+pub struct CertReloader {
+    key_path: String,
+    cert_path: String,
+    context: RwLock<SslContext>,
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl CertReloader {
+    pub fn new(key_path: &str, cert_path: &str) -> Result<Arc<Self>, ErrorStack> {
+        let reloader = CertReloader {
+            key_path: key_path.to_string(),
+            cert_path: cert_path.to_string(),
+            context: RwLock::new(load_context(key_path, cert_path)?),
+            modified: Mutex::new(None),
+        };
+        *reloader.modified.lock().unwrap() = reloader.last_modified();
+
+        Ok(Arc::new(reloader))
+    }
+
+    /// The acceptor to bind the server with.
+    pub fn acceptor(self: &Arc<Self>) -> Result<SslAcceptorBuilder, ErrorStack> {
+        let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+        builder.set_private_key_file(&self.key_path, SslFiletype::PEM)?;
+        builder.set_certificate_chain_file(&self.cert_path)?;
+
+        let reloader = self.clone();
+        builder.set_client_hello_callback(move |ssl, _alert| {
+            let context = reloader.context.read().unwrap();
+            ssl.set_ssl_context(&context)?;
+            Ok(ClientHelloResponse::SUCCESS)
+        });
+
+        Ok(builder)
+    }
+
+    fn last_modified(&self) -> Option<SystemTime> {
+        [&self.key_path, &self.cert_path]
+            .iter()
+            .filter_map(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+            .max()
+    }
+
+    /// Load the files again, the current certificate stays in use if they are broken.
+    pub fn reload(&self) {
+        match load_context(&self.key_path, &self.cert_path) {
+            Ok(context) => {
+                *self.context.write().unwrap() = context;
+                info!("Reloaded certificate from {}", self.cert_path);
+            }
+            Err(err) => error!("Failed to reload certificate, keep the current one: {}", err),
+        }
+    }
+
+    fn reload_if_modified(&self) {
+        let modified = self.last_modified();
+        let mut last = self.modified.lock().unwrap();
+
+        if modified.is_some() && modified != *last {
+            *last = modified;
+            drop(last);
+            self.reload();
+        }
+    }
+
+    /// Reload on SIGHUP, and whenever the files changed when checked every `interval`.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(err) => {
+                error!("Failed to listen to SIGHUP, certificate is only reloaded on file change: {}", err);
+                loop {
+                    tokio::time::sleep(interval).await;
+                    self.reload_if_modified();
+                }
+            }
+        };
+
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = hangup.recv() => {
+                    info!("Received SIGHUP");
+                    self.reload();
+                }
+                _ = ticker.tick() => self.reload_if_modified(),
+            }
+        }
+    }
+}
+
+/// Redirect a plain http request to the same host and path on the https port.
+pub async fn redirect_to_https(req: HttpRequest, https_port: web::Data<String>) -> HttpResponse {
+    let connection_info = req.connection_info();
+    let host = connection_info.host();
+
+    // drop the port of the plain listener, but not the colons of an ipv6 address
+    let hostname = match host.rfind(':') {
+        Some(i) if !host[i..].contains(']') => &host[..i],
+        _ => host,
+    };
+
+    let path = req.uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+
+    let location = if https_port.as_str() == "443" {
+        format!("https://{}{}", hostname, path)
+    } else {
+        format!("https://{}:{}{}", hostname, https_port.as_str(), path)
+    };
+
+    HttpResponse::PermanentRedirect()
+        .insert_header((LOCATION, location))
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{HOST, LOCATION};
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use actix_web::web;
+    use super::redirect_to_https;
+
+    async fn location(host: &str, uri: &str, https_port: &str) -> String {
+        let req = TestRequest::get()
+            .uri(uri)
+            .insert_header((HOST, host))
+            .to_http_request();
+
+        let res = redirect_to_https(req, web::Data::new(https_port.to_string())).await;
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+
+        res.headers().get(LOCATION).unwrap().to_str().unwrap().to_string()
+    }
+
+    #[actix_web::test]
+    async fn default_port_is_left_out() {
+        assert_eq!(location("example.com:8080", "/api/article?page=2", "443").await, "https://example.com/api/article?page=2");
+        assert_eq!(location("example.com", "/", "443").await, "https://example.com/");
+    }
+
+    #[actix_web::test]
+    async fn other_port_replaces_the_plain_one() {
+        assert_eq!(location("example.com:8080", "/healthz", "8443").await, "https://example.com:8443/healthz");
+    }
+
+    #[actix_web::test]
+    async fn ipv6_host_keeps_its_colons() {
+        assert_eq!(location("[::1]:8080", "/", "8443").await, "https://[::1]:8443/");
+        assert_eq!(location("[::1]", "/", "443").await, "https://[::1]/");
+    }
+}
@@ -0,0 +1,167 @@
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rand::rand_bytes;
+use openssl::sign::Signer;
+use crate::infra::error::error::ServiceError;
+
+// RFC 6238 defaults, the ones every authenticator app understands
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+const SECRET_BYTES: usize = 20;
+// steps before and after the current one still accepted, to tolerate clock drift
+const DRIFT_STEPS: i64 = 1;
+
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// RFC 4648 base32 without padding, the encoding authenticator apps expect secrets in.
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity((data.len() * 8).div_ceil(5));
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+/// Decode base32, ignoring case, spaces and padding. `None` if it holds any other character.
+pub fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in encoded.chars().filter(|c| *c != ' ' && *c != '=') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a as char == c.to_ascii_uppercase())? as u32;
+
+        buffer = (buffer << 5) | value;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Some(decoded)
+}
+
+/// A new random secret, base32 encoded.
+pub fn generate_secret() -> Result<String, ServiceError> {
+    let mut secret = [0u8; SECRET_BYTES];
+    rand_bytes(&mut secret)?;
+
+    Ok(base32_encode(&secret))
+}
+
+/// RFC 4226 HOTP value of `counter`, before it is cut down to digits.
+fn hotp(key: &[u8], counter: u64) -> Result<u32, ServiceError> {
+    let pkey = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &pkey)?;
+    signer.update(&counter.to_be_bytes())?;
+    let hmac = signer.sign_to_vec()?;
+
+    let offset = (hmac[hmac.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hmac[offset], hmac[offset + 1], hmac[offset + 2], hmac[offset + 3]]) & 0x7fff_ffff;
+
+    Ok(binary)
+}
+
+fn code_at_step(key: &[u8], step: i64) -> Result<String, ServiceError> {
+    let code = hotp(key, step as u64)? % 10u32.pow(DIGITS);
+
+    Ok(format!("{:0width$}", code, width = DIGITS as usize))
+}
+
+/// Check `code` against `secret` around `unix_time`, return the time step it matched.
+/// The caller should refuse steps not newer than the last accepted one, so a code can not be replayed.
+pub fn verify(secret: &str, code: &str, unix_time: i64) -> Result<Option<i64>, ServiceError> {
+    let key = match base32_decode(secret) {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+
+    let code = code.trim();
+
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    let current = unix_time / STEP_SECONDS;
+
+    for step in (current - DRIFT_STEPS)..=(current + DRIFT_STEPS) {
+        if code_at_step(&key, step)? == code {
+            return Ok(Some(step));
+        }
+    }
+
+    Ok(None)
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// The `otpauth://` uri that authenticator apps import, usually shown as a QR code.
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        secret,
+        percent_encode(issuer),
+        DIGITS,
+        STEP_SECONDS
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base32_decode, base32_encode, code_at_step, verify};
+
+    // RFC 6238 appendix B, SHA1 key
+    const RFC_KEY: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn base32_round_trip() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("mzxw6ytboi======").unwrap(), b"foobar");
+        assert!(base32_decode("not base32!").is_none());
+    }
+
+    #[test]
+    fn rfc6238_test_vectors() {
+        // the RFC lists 8 digit codes, these are their last 6 digits
+        assert_eq!(code_at_step(RFC_KEY, 59 / 30).unwrap(), "287082");
+        assert_eq!(code_at_step(RFC_KEY, 1111111109 / 30).unwrap(), "081804");
+        assert_eq!(code_at_step(RFC_KEY, 1234567890 / 30).unwrap(), "005924");
+    }
+
+    #[test]
+    fn verify_tolerates_one_step_of_drift() {
+        let secret = base32_encode(RFC_KEY);
+
+        assert_eq!(verify(&secret, "081804", 1111111109 + 30).unwrap(), Some(1111111109 / 30));
+        assert_eq!(verify(&secret, "081804", 1111111109 + 90).unwrap(), None);
+        assert_eq!(verify(&secret, "08180", 1111111109).unwrap(), None);
+    }
+}
@@ -1,35 +1,55 @@
 mod biz;
+mod cli;
 mod infra;
 
-use std::io;
+use std::{env, io};
+use std::sync::Arc;
+use std::time::Duration;
 
 use actix_cors::Cors;
-use actix_files;
 use actix_web::{App, HttpServer};
 use actix_web::http::Method;
 use actix_web::middleware::Logger;
 use actix_web::web::{self, Data};
 use deadpool_postgres::Pool;
-use log::info;
-use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use log::{error, info};
 use tokio_postgres::NoTls;
 
-use biz::account::handler::{login, register};
+use biz::account::handler::{change_password, confirm_mfa, create_api_token, disable_mfa, enroll_mfa, login, login_mfa, logout, logout_all, read_api_tokens, refresh, register, request_password_reset, reset_password, revoke_token};
 use crate::biz::ai::handler::get_ai_response;
-use crate::biz::article::handler::{create_article, read_article_owned, read_article_paginated};
-use crate::biz::article_category::handler::read_all_category;
+use crate::biz::article::handler::{create_article, curate_article, delete_article, diff_revisions, patch_article, publish_article, read_article, read_article_owned, read_article_paginated, read_revision, read_revisions, restore_revision, unpublish_article, update_article};
+use crate::biz::article_category::handler::{create_category, delete_category, read_all_category, read_category_tree, update_category};
+use crate::biz::child::handler::{create_child, read_child, read_children, update_child};
 use crate::biz::behavior::handler::{create_behavior, read_all_behavior_record, read_paginated_behavior};
 use crate::biz::diet::handler::{create_diet_record, read_all_diet_record, read_paginated_diet_record};
+use crate::biz::family::handler::{accept_invitation, create_family, create_invitation, decline_invitation, read_family_owned, read_pending_invitation, remove_member, update_member_role};
 use crate::biz::draft::handler::{create_draft, read_draft_owned};
 use crate::biz::user::handler::{get_current_user, get_user_info_in_batches, update_user_info, use_public_info};
-use crate::biz::file::handler::{save_document, save_image};
+use crate::biz::file::handler::{read_file, read_public_file, save_article_image, save_document, save_image};
 use crate::biz::journal::handler::{create_journal, read_paginated_journal};
 use crate::biz::health::handler::{create_health_record, read_all_health_record, read_health_record_paginated};
+use crate::biz::probe::handler::{healthz, metrics, readyz};
+use crate::biz::probe::recorder::ping;
 use crate::biz::remark::handler::{create_remark, read_remark_paginated};
+use crate::biz::search::handler::search;
 use crate::biz::wish::handler::{create_wish, get_paginated_wish};
+use crate::infra::error::error::ServiceError;
 use crate::infra::{
     init::Initializer,
 };
+use crate::cli::Command;
+use crate::infra::mail::{build_mailer, Mailer};
+use crate::infra::migrate;
+use crate::infra::telemetry;
+use crate::infra::tls::{CertReloader, redirect_to_https};
+use crate::infra::config::{CookieConfig, MetricsConfig, TokenConfig};
+use crate::infra::login_guard::LoginGuard;
+use crate::infra::password::PasswordPolicy;
+use crate::infra::rate_limit::RateLimiter;
+use crate::infra::middleware::context::RequestContextMiddleware;
+use crate::infra::middleware::family::FamilyMiddleware;
+use crate::infra::middleware::metrics::MetricsMiddleware;
+use crate::infra::middleware::rate_limit::RateLimitMiddleware;
 use crate::infra::middleware::jwt::JwtMiddleware;
 
 
@@ -40,63 +60,153 @@ struct AppState {
     image_static_dir: String,
     document_static_dir: String,
     kimi_secret: String,
+    mailer: Arc<dyn Mailer>,
+    password_policy: Arc<PasswordPolicy>,
+    login_guard: LoginGuard,
+    cookie: CookieConfig,
+    token: TokenConfig,
+    metrics: MetricsConfig,
+    rate_limiter: RateLimiter,
 }
 
 
 #[actix_web::main]
 async fn main() -> io::Result<()> {
+    let command = match cli::parse(env::args().skip(1)) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if command == Command::Help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+
+    // reports what is wrong with the settings rather than panicking on it
+    if command == Command::CheckConfig {
+        return match Initializer::default().init_settings() {
+            Ok(initializer) => cli::check_config(initializer.settings()).await,
+            Err(err) => {
+                eprintln!("Failed to read the settings: {}", err);
+                Err(io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))
+            }
+        };
+    }
+
     let initializer = Initializer::default()
         .must_init()
         .expect("Failed to init setup");
 
     let settings = initializer.settings().clone();
 
-    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
-    builder.set_private_key_file(settings.path_to_cert_key, SslFiletype::PEM).unwrap();
-    builder.set_certificate_chain_file(settings.path_to_cert_file).unwrap();
+    // flushes the remaining spans when main returns
+    let _telemetry = telemetry::init(&settings.tracing).expect("Failed to set up trace export");
 
     let pool = settings.pg.create_pool(None, NoTls).expect("Failed to create a pg pool");
 
+    if let Err(err) = ping(&pool).await {
+        error!("Postgres at {:?}:{:?} is unreachable: {}", settings.pg.host, settings.pg.port, err);
+
+        return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "Postgres is unreachable"));
+    }
+
+    if command != Command::Serve {
+        return cli::run(&pool, &settings, command).await;
+    }
+
+    if settings.auto_migrate {
+        let mut pg_client = pool.get().await.expect("Failed to check out a connection to migrate");
+
+        let applied = migrate::up(&mut pg_client).await.expect("Failed to migrate the database");
+
+        info!("Auto migration applied {} migrations", applied.len());
+    }
+
+    let mailer = build_mailer(&settings.mail).expect("Failed to build the mailer");
+
+    let password_policy = PasswordPolicy::load(&settings.password).expect("Failed to load the breached password list");
+
+    let rate_limiter = RateLimiter::new(&settings.rate_limit, &pool).expect("Failed to set up rate limiting");
+
+    actix_web::rt::spawn(rate_limiter.clone().prune_periodically());
+
     let app_data = AppState {
         jwt_secret: settings.jwt_secret.clone(),
         pool: pool.clone(),
         image_static_dir: settings.path_to_image_static_dir.clone(),
         document_static_dir: settings.path_to_document_static_dir.clone(),
         kimi_secret: settings.kimi_secret.clone(),
+        mailer,
+        password_policy: Arc::new(password_policy),
+        login_guard: LoginGuard::new(settings.login_guard.clone()),
+        cookie: settings.cookie.clone(),
+        token: settings.token.clone(),
+        metrics: settings.metrics.clone(),
+        rate_limiter,
     };
 
+    let allowed_origins = settings.cors.allowed_origins.clone();
+
     let server = HttpServer::new(move || {
         let app = App::new();
 
-        let app = app.app_data(Data::new(app_data.clone()));
+        let app = app
+            .app_data(Data::new(app_data.clone()))
+            // malformed bodies, queries and paths are answered like any other invalid form
+            .app_data(web::JsonConfig::default().error_handler(|err, _| ServiceError::from(err).into()))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| ServiceError::from(err).into()))
+            .app_data(web::PathConfig::default().error_handler(|err, _| ServiceError::from(err).into()));
 
 
-        let cors = Cors::default()
-            .allowed_origin("https://localhost:5173")
-            .allowed_origin("https://127.0.0.1:5173")
+        let cors = allowed_origins
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
             .supports_credentials()
             .allowed_methods(
                 vec![
                     Method::GET,
                     Method::POST,
                     Method::PUT,
+                    Method::PATCH,
                     Method::DELETE,
                     Method::OPTIONS,
                 ]
             )
-            .allow_any_header();
+            .allow_any_header()
+            // readable by the frontend, not only by the browser
+            .expose_headers(vec!["x-request-id", "retry-after", "ratelimit-limit", "ratelimit-remaining", "ratelimit-reset"]);
 
         let app = app
-            .wrap(Logger::new("%a | %t | %r | %s | %Ts"))
-            .wrap(cors);
+            .wrap(Logger::new("%a | %t | %r | %s | %Ts | %{x-request-id}i"))
+            .wrap(cors)
+            .wrap(MetricsMiddleware)
+            .wrap(RequestContextMiddleware);
 
 
         let account_scope = web::scope("/account")
+            .wrap(RateLimitMiddleware::new("account"))
             .service(login)
-            .service(register);
+            .service(register)
+            .service(refresh)
+            .service(logout)
+            .service(logout_all)
+            .service(create_api_token)
+            .service(read_api_tokens)
+            .service(revoke_token)
+            .service(change_password)
+            .service(request_password_reset)
+            .service(reset_password)
+            .service(login_mfa)
+            .service(enroll_mfa)
+            .service(confirm_mfa)
+            .service(disable_mfa);
 
 
         let user_scope = web::scope("/user")
+            .wrap(RateLimitMiddleware::new("user"))
             .wrap(JwtMiddleware)
             .service(get_user_info_in_batches)
             .service(get_current_user)
@@ -104,100 +214,202 @@ async fn main() -> io::Result<()> {
             .service(use_public_info);
 
         let file_scope = web::scope("/file")
+            .wrap(RateLimitMiddleware::new("file"))
+            .wrap(FamilyMiddleware)
             .wrap(JwtMiddleware)
             .service(save_image)
-            .service(save_document);
+            .service(save_document)
+            .service(read_file);
+
+        // article assets, public unlike the family files
+        let asset_scope = web::scope("/asset")
+            .wrap(RateLimitMiddleware::new("file"))
+            .wrap(JwtMiddleware)
+            .service(save_article_image);
 
         let wish_scope = web::scope("/wish")
+            .wrap(RateLimitMiddleware::new("wish"))
+            .wrap(FamilyMiddleware)
             .wrap(JwtMiddleware)
             .service(create_wish)
             .service(get_paginated_wish);
 
         let journal_scope = web::scope("/journal")
+            .wrap(RateLimitMiddleware::new("journal"))
+            .wrap(FamilyMiddleware)
             .wrap(JwtMiddleware)
             .service(create_journal)
             .service(read_paginated_journal);
 
+        let family_scope = web::scope("/family")
+            .wrap(RateLimitMiddleware::new("family"))
+            .wrap(JwtMiddleware)
+            .service(create_family)
+            .service(read_family_owned)
+            .service(create_invitation)
+            .service(read_pending_invitation)
+            .service(accept_invitation)
+            .service(decline_invitation)
+            .service(update_member_role)
+            .service(remove_member);
+
+        let child_scope = web::scope("/child")
+            .wrap(RateLimitMiddleware::new("child"))
+            .wrap(FamilyMiddleware)
+            .wrap(JwtMiddleware)
+            .service(create_child)
+            .service(read_children)
+            .service(read_child)
+            .service(update_child);
+
         let health_scope = web::scope("/health")
+            .wrap(RateLimitMiddleware::new("health"))
+            .wrap(FamilyMiddleware)
             .wrap(JwtMiddleware)
             .service(create_health_record)
             .service(read_health_record_paginated)
             .service(read_all_health_record);
 
         let diet_scope = web::scope("/diet")
+            .wrap(RateLimitMiddleware::new("diet"))
+            .wrap(FamilyMiddleware)
             .wrap(JwtMiddleware)
             .service(create_diet_record)
             .service(read_paginated_diet_record)
             .service(read_all_diet_record);
 
         let behavior_scope = web::scope("/behavior")
+            .wrap(RateLimitMiddleware::new("behavior"))
+            .wrap(FamilyMiddleware)
             .wrap(JwtMiddleware)
             .service(create_behavior)
             .service(read_paginated_behavior)
             .service(read_all_behavior_record);
 
         let ai_scope = web::scope("/ai")
+            .wrap(RateLimitMiddleware::new("ai"))
             .wrap(JwtMiddleware)
             .service(get_ai_response);
 
         let article_scope = web::scope("/article")
+            .wrap(RateLimitMiddleware::new("article"))
             .wrap(JwtMiddleware)
             .service(create_article)
+            .service(curate_article)
             .service(read_article_owned)
             .service(read_article_paginated)
+            .service(read_article)
+            .service(update_article)
+            .service(patch_article)
+            .service(delete_article)
+            .service(publish_article)
+            .service(unpublish_article)
+            .service(read_revisions)
+            .service(diff_revisions)
+            .service(read_revision)
+            .service(restore_revision)
             .service(
                 web::scope("/category")
                     .service(read_all_category)
+                    .service(read_category_tree)
+                    .service(create_category)
+                    .service(update_category)
+                    .service(delete_category)
             );
 
         let draft_scope = web::scope("/draft")
+            .wrap(RateLimitMiddleware::new("draft"))
             .wrap(JwtMiddleware)
             .service(create_draft)
             .service(read_draft_owned);
 
 
         let remark_scope = web::scope("/remark")
+            .wrap(RateLimitMiddleware::new("remark"))
             .wrap(JwtMiddleware)
             .service(create_remark)
             .service(read_remark_paginated);
 
+        let search_scope = web::scope("/search")
+            .wrap(RateLimitMiddleware::new("search"))
+            .wrap(JwtMiddleware)
+            .service(search);
+
         let api_service = web::scope("/api")
             .service(account_scope)
             .service(user_scope)
             .service(file_scope)
+            .service(asset_scope)
             .service(wish_scope)
             .service(journal_scope)
+            .service(family_scope)
+            .service(child_scope)
             .service(health_scope)
             .service(diet_scope)
             .service(behavior_scope)
             .service(ai_scope)
             .service(article_scope)
             .service(draft_scope)
-            .service(remark_scope);
-
-        let static_file_service = web::scope("/static")
-            .service(
-                actix_files::Files::new("/image", &settings.path_to_image_static_dir)
-                    .show_files_listing()
-                    .use_etag(true)
-                    .use_last_modified(true)
-            )
-            .service(
-                actix_files::Files::new("/document", &settings.path_to_document_static_dir)
-                    .show_files_listing()
-                    .use_etag(true)
-                    .use_last_modified(true)
-            );
-
-        app.service(api_service)
-            .service(static_file_service)
-    });
+            .service(remark_scope)
+            .service(search_scope);
+
+        let app = if app_data.metrics.enabled {
+            app.service(metrics)
+        } else {
+            app
+        };
+
+        app
+            .service(healthz)
+            .service(readyz)
+            .service(api_service)
+            .service(web::scope("/static").service(read_public_file))
+    })
+        .shutdown_timeout(settings.shutdown_timeout);
 
     info!("Running on {}:{}",settings.ip, settings.port);
     info!("Log Level is {}",settings.log.level);
 
-    server
-        .bind_openssl(format!("{}:{}", settings.ip, settings.port), builder)?
-        .run()
+    let address = format!("{}:{}", settings.ip, settings.port);
+
+    if !settings.tls.enabled {
+        info!("TLS is disabled, serving plain http");
+
+        return server
+            .bind(address)?
+            .run()
+            .await;
+    }
+
+    let reloader = CertReloader::new(&settings.path_to_cert_key, &settings.path_to_cert_file)
+        .expect("Failed to load the certificate");
+
+    actix_web::rt::spawn(
+        reloader.clone().watch(Duration::from_secs(settings.tls.reload_interval_seconds))
+    );
+
+    let server = server
+        .bind_openssl(address, reloader.acceptor().expect("Failed to build the tls acceptor"))?
+        .run();
+
+    if settings.tls.redirect_port.is_empty() {
+        return server.await;
+    }
+
+    info!("Redirecting plain http on {}:{} to https", settings.ip, settings.tls.redirect_port);
+
+    let https_port = settings.port.clone();
+
+    let redirect = HttpServer::new(move || {
+        App::new()
+            .app_data(Data::new(https_port.clone()))
+            .default_service(web::to(redirect_to_https))
+    })
+        .shutdown_timeout(settings.shutdown_timeout)
+        .bind(format!("{}:{}", settings.ip, settings.tls.redirect_port))?
+        .run();
+
+    futures::future::try_join(server, redirect)
         .await
+        .map(|_| ())
 }